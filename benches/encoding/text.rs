@@ -6,7 +6,6 @@ use prometheus_client::metrics::counter::Counter;
 use prometheus_client::metrics::family::Family;
 use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
 use prometheus_client::registry::Registry;
-use std::io::Write;
 
 pub fn text(c: &mut Criterion) {
     c.bench_function("encode", |b| {
@@ -24,27 +23,18 @@ pub fn text(c: &mut Criterion) {
             Put,
         }
 
-        #[derive(Clone, Hash, PartialEq, Eq)]
+        #[derive(Clone, Hash, PartialEq, Eq, Encode)]
         enum Status {
+            #[prometheus(value = "200")]
             Two,
             #[allow(dead_code)]
+            #[prometheus(value = "400")]
             Four,
             #[allow(dead_code)]
+            #[prometheus(value = "500")]
             Five,
         }
 
-        impl Encode for Status {
-            fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
-                let status = match self {
-                    Status::Two => b"200",
-                    Status::Four => b"400",
-                    Status::Five => b"500",
-                };
-                writer.write_all(status)?;
-                Ok(())
-            }
-        }
-
         let mut registry = Registry::<Box<dyn EncodeMetric>>::default();
 
         for i in 0..100 {
@@ -1,6 +1,9 @@
 use criterion::{criterion_group, criterion_main, Criterion};
+#[cfg(feature = "smallvec")]
+use prometheus_client::encoding::text::SmallLabelSet;
 use prometheus_client::metrics::counter::Counter;
 use prometheus_client::metrics::family::Family;
+use std::borrow::Cow;
 
 pub fn family(c: &mut Criterion) {
     c.bench_function("counter family with Vec<(String, String)> label set", |b| {
@@ -16,6 +19,71 @@ pub fn family(c: &mut Criterion) {
         })
     });
 
+    c.bench_function(
+        "counter family with large Vec<(String, String)> label set",
+        |b| {
+            let family = Family::<Vec<(String, String)>, Counter>::default();
+            let label_set: Vec<(String, String)> = (0..32)
+                .map(|i| (format!("key_{}", i), "value".repeat(16)))
+                .collect();
+
+            b.iter(|| {
+                family.get_or_create(&label_set).inc();
+            })
+        },
+    );
+
+    // Compare with the above to measure the family overhead for a single,
+    // constant label set.
+    c.bench_function(
+        "counter with constant Vec<(String, String)> label set",
+        |b| {
+            let counter: Counter = Counter::default();
+            let _labeled = counter.clone().with_labels(vec![
+                ("method".to_owned(), "GET".to_owned()),
+                ("status".to_owned(), "200".to_owned()),
+            ]);
+
+            b.iter(|| {
+                counter.inc();
+            })
+        },
+    );
+
+    // Compare with the below, using the same label names and values, to measure
+    // the heap allocation per lookup saved by `SmallLabelSet`.
+    c.bench_function(
+        "counter family with 3 label Vec<(Cow<'static, str>, Cow<'static, str>)> label set",
+        |b| {
+            let family = Family::<Vec<(Cow<'static, str>, Cow<'static, str>)>, Counter>::default();
+
+            b.iter(|| {
+                family
+                    .get_or_create(&vec![
+                        (Cow::Borrowed("method"), Cow::Borrowed("GET")),
+                        (Cow::Borrowed("status"), Cow::Borrowed("200")),
+                        (Cow::Borrowed("handler"), Cow::Borrowed("api")),
+                    ])
+                    .inc();
+            })
+        },
+    );
+
+    #[cfg(feature = "smallvec")]
+    c.bench_function("counter family with 3 label SmallLabelSet label set", |b| {
+        let family = Family::<SmallLabelSet<3>, Counter>::default();
+
+        b.iter(|| {
+            family
+                .get_or_create(&SmallLabelSet::from_iter([
+                    (Cow::Borrowed("method"), Cow::Borrowed("GET")),
+                    (Cow::Borrowed("status"), Cow::Borrowed("200")),
+                    (Cow::Borrowed("handler"), Cow::Borrowed("api")),
+                ]))
+                .inc();
+        })
+    });
+
     c.bench_function("counter family with custom type label set", |b| {
         #[derive(Clone, Hash, PartialEq, Eq)]
         struct Labels {
@@ -51,5 +119,28 @@ pub fn family(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, family);
+pub fn family_contention(c: &mut Criterion) {
+    // Compare with `--features parking_lot` to measure the lock overhead.
+    c.bench_function("counter family with 4 threads contending", |b| {
+        let family = Family::<Vec<(String, String)>, Counter>::default();
+        let label_sets: Vec<Vec<(String, String)>> = (0..4)
+            .map(|i| vec![("thread".to_owned(), i.to_string())])
+            .collect();
+
+        b.iter(|| {
+            std::thread::scope(|s| {
+                for label_set in label_sets.iter() {
+                    let family = &family;
+                    s.spawn(move || {
+                        for _ in 0..10_000 {
+                            family.get_or_create(label_set).inc();
+                        }
+                    });
+                }
+            })
+        })
+    });
+}
+
+criterion_group!(benches, family, family_contention);
 criterion_main!(benches);
@@ -3,19 +3,64 @@ extern crate proc_macro;
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
 use syn::DeriveInput;
 
-#[proc_macro_derive(Encode)]
+#[proc_macro_derive(Encode, attributes(prometheus))]
 pub fn derive_encode(input: TokenStream) -> TokenStream {
     let ast: DeriveInput = syn::parse(input).unwrap();
     let name = &ast.ident;
 
+    let mut const_labels = vec![];
+    let mut encode_discriminant = false;
+    for attribute in parse_attributes(&ast.attrs) {
+        match attribute {
+            PrometheusAttribute::ConstLabel(const_label) => {
+                let key = const_label.key.value();
+                if !is_valid_label_name(&key) {
+                    return syn::Error::new(
+                        const_label.key.span(),
+                        format!(
+                            "Invalid label name `{}`, expected `[a-zA-Z_][a-zA-Z0-9_]*`.",
+                            key
+                        ),
+                    )
+                    .to_compile_error()
+                    .into();
+                }
+                const_labels.push(const_label)
+            }
+            PrometheusAttribute::Value(value) if value.value() == "discriminant" => {
+                encode_discriminant = true
+            }
+            PrometheusAttribute::Value(_) => {
+                panic!("Expected `#[prometheus(value = \"discriminant\")]` on enum.")
+            }
+        }
+    }
+
+    let type_params: Vec<syn::Ident> = ast
+        .generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect();
+    // Bounds on the types of the fields mentioning a type parameter, e.g.
+    // `T: Encode` for a field `status: T`.
+    let mut field_bounds: Vec<syn::Type> = vec![];
+
     let body = match ast.data {
+        syn::Data::Struct(_) if encode_discriminant => {
+            panic!("Can not derive Encode with `value = \"discriminant\"` for struct.")
+        }
         syn::Data::Struct(s) => match s.fields {
             syn::Fields::Named(syn::FieldsNamed { named, .. }) => named
                 .into_iter()
-                .enumerate()
-                .map(|(i, f)| {
+                .map(|f| {
+                    let ty = &f.ty;
+                    if mentions_type_param(quote! { #ty }, &type_params) {
+                        field_bounds.push(f.ty.clone());
+                    }
                     let ident = f.ident.unwrap();
                     let ident_string = KEYWORD_IDENTIFIERS
                         .iter()
@@ -23,6 +68,24 @@ pub fn derive_encode(input: TokenStream) -> TokenStream {
                         .map(|pair| pair.0.to_string())
                         .unwrap_or_else(|| ident.to_string());
 
+                    (
+                        ident_string,
+                        quote! {
+                            prometheus_client::encoding::text::encode_label_value(&self.#ident, writer)?;
+                        },
+                    )
+                })
+                .chain(const_labels.into_iter().map(|ConstLabel { key, value }| {
+                    let value = escape_label_value(&value);
+                    (
+                        key.value(),
+                        quote! {
+                            writer.write_all(#value.as_bytes())?;
+                        },
+                    )
+                }))
+                .enumerate()
+                .map(|(i, (ident_string, encode_value))| {
                     let maybe_comma = if i == 0 {
                         TokenStream2::default()
                     } else {
@@ -31,23 +94,79 @@ pub fn derive_encode(input: TokenStream) -> TokenStream {
                     quote! {
                         #maybe_comma
                         writer.write_all(concat!(#ident_string, "=\"").as_bytes())?;
-                        prometheus_client::encoding::text::Encode::encode(&self.#ident, writer)?;
+                        #encode_value
                         writer.write_all(b"\"")?;
                     }
                 })
                 .collect(),
+            // Newtype, e.g. `struct Shard(u64)`, encoded like its inner type.
+            syn::Fields::Unnamed(syn::FieldsUnnamed { unnamed, .. }) if unnamed.len() == 1 => {
+                if !const_labels.is_empty() {
+                    panic!("Can not derive Encode with constant labels for newtype struct.")
+                }
+                let ty = &unnamed[0].ty;
+                if mentions_type_param(quote! { #ty }, &type_params) {
+                    field_bounds.push(ty.clone());
+                }
+
+                quote! {
+                    prometheus_client::encoding::text::Encode::encode(&self.0, writer)?;
+                }
+            }
             syn::Fields::Unnamed(_) => {
-                panic!("Can not derive Encode for struct with unnamed fields.")
+                panic!("Can not derive Encode for struct with more than one unnamed field.")
             }
             syn::Fields::Unit => panic!("Can not derive Encode for struct with unit field."),
         },
         syn::Data::Enum(syn::DataEnum { variants, .. }) => {
+            if !const_labels.is_empty() {
+                panic!("Can not derive Encode with constant labels for enum.")
+            }
+
+            // Tracks the last explicit discriminant, if any, and the number of
+            // variants since, to derive implicit discriminants.
+            let mut discriminant: Option<(Option<syn::Expr>, usize)> = None;
             let match_arms: TokenStream2 = variants
                 .into_iter()
                 .map(|v| {
                     let ident = v.ident;
+
+                    discriminant = match (v.discriminant, discriminant.take()) {
+                        (Some((_, expr)), _) => Some((Some(expr), 0)),
+                        (None, Some((expr, offset))) => Some((expr, offset + 1)),
+                        (None, None) => Some((None, 0)),
+                    };
+
+                    let value = parse_attributes(&v.attrs)
+                        .into_iter()
+                        .map(|attribute| match attribute {
+                            PrometheusAttribute::Value(value) => value,
+                            PrometheusAttribute::ConstLabel(_) => {
+                                panic!("Can not derive Encode with constant labels for enum variant.")
+                            }
+                        })
+                        .last();
+
+                    let encode_value = match value.as_ref().map(escape_label_value) {
+                        Some(value) => quote! { writer.write_all(#value.as_bytes())? },
+                        None if encode_discriminant => {
+                            let offset = discriminant.as_ref().map(|(_, offset)| {
+                                proc_macro2::Literal::usize_unsuffixed(*offset)
+                            });
+                            let discriminant = match (&discriminant, offset) {
+                                (Some((Some(expr), _)), Some(offset)) => {
+                                    quote! { (#expr) + #offset }
+                                }
+                                (Some((None, _)), Some(offset)) => quote! { #offset },
+                                _ => unreachable!("Discriminant to be set above."),
+                            };
+                            quote! { writer.write_fmt(format_args!("{}", #discriminant))? }
+                        }
+                        None => quote! { writer.write_all(stringify!(#ident).as_bytes())? },
+                    };
+
                     quote! {
-                        #name::#ident => writer.write_all(stringify!(#ident).as_bytes())?,
+                        #name::#ident => #encode_value,
                     }
                 })
                 .collect();
@@ -61,8 +180,18 @@ pub fn derive_encode(input: TokenStream) -> TokenStream {
         syn::Data::Union(_) => panic!("Can not derive Encode for union."),
     };
 
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let mut where_clause = where_clause
+        .cloned()
+        .unwrap_or_else(|| syn::parse_quote! { where });
+    where_clause
+        .predicates
+        .extend(field_bounds.iter().map(|ty| -> syn::WherePredicate {
+            syn::parse_quote! { #ty: prometheus_client::encoding::text::Encode }
+        }));
+
     let gen = quote! {
-        impl prometheus_client::encoding::text::Encode for #name {
+        impl #impl_generics prometheus_client::encoding::text::Encode for #name #ty_generics #where_clause {
             fn encode(&self, writer: &mut dyn std::io::Write) -> std::result::Result<(), std::io::Error> {
                 #body
 
@@ -73,6 +202,88 @@ pub fn derive_encode(input: TokenStream) -> TokenStream {
     gen.into()
 }
 
+/// Whether the given tokens, e.g. the type of a field, mention any of the given
+/// type parameters.
+fn mentions_type_param(tokens: TokenStream2, type_params: &[syn::Ident]) -> bool {
+    tokens.into_iter().any(|token| match token {
+        proc_macro2::TokenTree::Ident(ident) => type_params.contains(&ident),
+        proc_macro2::TokenTree::Group(group) => mentions_type_param(group.stream(), type_params),
+        _ => false,
+    })
+}
+
+/// Whether the given constant label name is valid, i.e. matches
+/// `[a-zA-Z_][a-zA-Z0-9_]*`.
+fn is_valid_label_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Escape the given label value, i.e. of a constant label or enum variant, at
+/// expansion time, the same way `encode_label_value` does at runtime.
+fn escape_label_value(value: &syn::LitStr) -> syn::LitStr {
+    let escaped = value
+        .value()
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    syn::LitStr::new(&escaped, value.span())
+}
+
+/// Parse all `#[prometheus(...)]` attributes.
+fn parse_attributes(attrs: &[syn::Attribute]) -> Vec<PrometheusAttribute> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("prometheus"))
+        .flat_map(|attr| {
+            attr.parse_args_with(
+                Punctuated::<PrometheusAttribute, syn::Token![,]>::parse_terminated,
+            )
+            .unwrap_or_else(|e| panic!("Invalid `prometheus` attribute: {}", e))
+        })
+        .collect()
+}
+
+enum PrometheusAttribute {
+    /// A constant label declared on a struct via
+    /// `#[prometheus(const_label("key" = "value"))]`.
+    ConstLabel(ConstLabel),
+    /// The value to encode an enum as. Either declared on the enum via
+    /// `#[prometheus(value = "discriminant")]` or on a variant via
+    /// `#[prometheus(value = "...")]`.
+    Value(syn::LitStr),
+}
+
+struct ConstLabel {
+    key: syn::LitStr,
+    value: syn::LitStr,
+}
+
+impl Parse for PrometheusAttribute {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident: syn::Ident = input.parse()?;
+        if ident == "const_label" {
+            let content;
+            syn::parenthesized!(content in input);
+            let key = content.parse()?;
+            content.parse::<syn::Token![=]>()?;
+            let value = content.parse()?;
+
+            Ok(PrometheusAttribute::ConstLabel(ConstLabel { key, value }))
+        } else if ident == "value" {
+            input.parse::<syn::Token![=]>()?;
+
+            Ok(PrometheusAttribute::Value(input.parse()?))
+        } else {
+            Err(syn::Error::new(
+                ident.span(),
+                "expected `const_label(\"key\" = \"value\")` or `value = \"...\"`",
+            ))
+        }
+    }
+}
+
 // Copied from https://github.com/djc/askama (MIT and APACHE licensed) and
 // modified.
 static KEYWORD_IDENTIFIERS: [(&str, &str); 48] = [
@@ -65,3 +65,174 @@ fn remap_keyword_identifiers() {
         String::from_utf8(buffer).unwrap()
     );
 }
+
+#[test]
+fn const_labels() {
+    #[derive(Encode, Hash, Clone, Eq, PartialEq)]
+    #[prometheus(const_label("schema" = "v2"), const_label("team" = "core"))]
+    #[prometheus(const_label("region" = "eu"))]
+    struct Labels {
+        method: String,
+    }
+
+    let labels = Labels {
+        method: "GET".to_string(),
+    };
+
+    let mut buffer = vec![];
+
+    labels.encode(&mut buffer).unwrap();
+
+    assert_eq!(
+        "method=\"GET\",schema=\"v2\",team=\"core\",region=\"eu\"".to_string(),
+        String::from_utf8(buffer).unwrap()
+    );
+}
+
+#[test]
+fn const_labels_escaped() {
+    #[derive(Encode, Hash, Clone, Eq, PartialEq)]
+    #[prometheus(const_label("note" = "a\"b\\c\nd"))]
+    struct Labels {
+        method: String,
+    }
+
+    #[derive(Encode, Hash, Clone, Eq, PartialEq)]
+    enum Quoted {
+        #[prometheus(value = "\"quoted\"")]
+        Quoted,
+    }
+
+    let mut buffer = vec![];
+    Labels {
+        method: "GET".to_string(),
+    }
+    .encode(&mut buffer)
+    .unwrap();
+    assert_eq!(
+        "method=\"GET\",note=\"a\\\"b\\\\c\\nd\"",
+        String::from_utf8(buffer).unwrap()
+    );
+
+    let mut buffer = vec![];
+    Quoted::Quoted.encode(&mut buffer).unwrap();
+    assert_eq!("\\\"quoted\\\"", String::from_utf8(buffer).unwrap());
+}
+
+#[test]
+fn enum_values() {
+    #[derive(Encode, Hash, Clone, Eq, PartialEq)]
+    #[prometheus(value = "discriminant")]
+    enum Status {
+        Ok = 200,
+        Created,
+        NotFound = 404,
+        #[prometheus(value = "5xx")]
+        ServerError = 500,
+    }
+
+    #[derive(Encode, Hash, Clone, Eq, PartialEq)]
+    #[prometheus(value = "discriminant")]
+    enum Implicit {
+        Zero,
+        One,
+    }
+
+    #[derive(Encode, Hash, Clone, Eq, PartialEq)]
+    enum Method {
+        Get,
+        #[prometheus(value = "PUT")]
+        Put,
+    }
+
+    let encode = |value: &dyn Encode| {
+        let mut buffer = vec![];
+        value.encode(&mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    };
+
+    assert_eq!("200", encode(&Status::Ok));
+    assert_eq!("201", encode(&Status::Created));
+    assert_eq!("404", encode(&Status::NotFound));
+    assert_eq!("5xx", encode(&Status::ServerError));
+    assert_eq!("0", encode(&Implicit::Zero));
+    assert_eq!("1", encode(&Implicit::One));
+    assert_eq!("Get", encode(&Method::Get));
+    assert_eq!("PUT", encode(&Method::Put));
+}
+
+#[test]
+fn generic_struct() {
+    #[derive(Encode, Hash, Clone, Eq, PartialEq)]
+    struct Labels<T> {
+        method: String,
+        status: T,
+    }
+
+    #[derive(Encode, Hash, Clone, Eq, PartialEq)]
+    struct BoundedLabels<T: Encode>
+    where
+        T: Clone,
+    {
+        status: Option<T>,
+    }
+
+    #[derive(Encode, Hash, Clone, Eq, PartialEq)]
+    enum Status {
+        Ok,
+    }
+
+    let mut registry = Registry::default();
+    let family = Family::<Labels<Status>, Counter>::default();
+    registry.register("my_counter", "This is my counter", family.clone());
+
+    family
+        .get_or_create(&Labels {
+            method: "GET".to_string(),
+            status: Status::Ok,
+        })
+        .inc();
+
+    let mut buffer = vec![];
+    encode(&mut buffer, &registry).unwrap();
+
+    let expected = "# HELP my_counter This is my counter.\n".to_owned()
+        + "# TYPE my_counter counter\n"
+        + "my_counter_total{method=\"GET\",status=\"Ok\"} 1\n"
+        + "# EOF\n";
+    assert_eq!(expected, String::from_utf8(buffer).unwrap());
+
+    let mut buffer = vec![];
+    BoundedLabels {
+        status: Some(200u64),
+    }
+    .encode(&mut buffer)
+    .unwrap();
+    assert_eq!("status=\"200\"", String::from_utf8(buffer).unwrap());
+}
+
+#[test]
+fn newtype_struct() {
+    #[derive(Encode, Hash, Clone, Eq, PartialEq)]
+    struct Shard(u64);
+
+    #[derive(Encode, Hash, Clone, Eq, PartialEq)]
+    struct Labels {
+        shard: Shard,
+    }
+
+    let mut registry = Registry::default();
+    let family = Family::<Labels, Counter>::default();
+    registry.register("my_counter", "This is my counter", family.clone());
+
+    family.get_or_create(&Labels { shard: Shard(7) }).inc();
+
+    let mut buffer = vec![];
+    encode(&mut buffer, &registry).unwrap();
+
+    let expected = "# HELP my_counter This is my counter.\n".to_owned()
+        + "# TYPE my_counter counter\n"
+        + "my_counter_total{shard=\"7\"} 1\n"
+        + "# EOF\n";
+    assert_eq!(expected, String::from_utf8(buffer).unwrap());
+}
@@ -1,7 +1,7 @@
 use std::sync::Mutex;
 
 use actix_web::{web, App, HttpResponse, HttpServer, Responder, Result};
-use prometheus_client::encoding::text::{encode, Encode};
+use prometheus_client::encoding::text::{encode, Encode, CONTENT_TYPE};
 use prometheus_client::metrics::counter::Counter;
 use prometheus_client::metrics::family::Family;
 use prometheus_client::registry::Registry;
@@ -36,9 +36,7 @@ pub async fn metrics_handler(state: web::Data<Mutex<AppState>>) -> Result<HttpRe
     let mut buf = Vec::new();
     encode(&mut buf, &state.registry)?;
     let body = std::str::from_utf8(buf.as_slice()).unwrap().to_string();
-    Ok(HttpResponse::Ok()
-        .content_type("application/openmetrics-text; version=1.0.0; charset=utf-8")
-        .body(body))
+    Ok(HttpResponse::Ok().content_type(CONTENT_TYPE).body(body))
 }
 
 pub async fn some_handler(metrics: web::Data<Metrics>) -> impl Responder {
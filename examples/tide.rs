@@ -1,4 +1,4 @@
-use prometheus_client::encoding::text::{encode, Encode};
+use prometheus_client::encoding::text::{encode, Encode, CONTENT_TYPE};
 use prometheus_client::metrics::counter::Counter;
 use prometheus_client::metrics::family::Family;
 use prometheus_client::registry::Registry;
@@ -34,7 +34,7 @@ async fn main() -> std::result::Result<(), std::io::Error> {
             encode(&mut encoded, &req.state().registry).unwrap();
             let response = tide::Response::builder(200)
                 .body(encoded)
-                .content_type("application/openmetrics-text; version=1.0.0; charset=utf-8")
+                .content_type(CONTENT_TYPE)
                 .build();
             Ok(response)
         });
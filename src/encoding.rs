@@ -1,3 +1,27 @@
 //! Exposition format implementations.
 
+#[cfg(feature = "graphite")]
+pub mod graphite;
+#[cfg(feature = "influx")]
+pub mod influx;
 pub mod text;
+
+/// Encode the metrics of the [`Registry`](crate::registry::Registry) in the
+/// Open Metrics text format without exemplars and parse the result, being the
+/// common traversal of the metrics for the non-text encodings.
+#[cfg(any(feature = "graphite", feature = "influx"))]
+fn encode_parsed<M: text::EncodeMetric>(
+    registry: &crate::registry::Registry<M>,
+) -> Result<Vec<text::ParsedFamily>, std::io::Error> {
+    use std::io::{Error, ErrorKind};
+
+    let options = registry
+        .encode_options()
+        .clone()
+        .format(text::Format::OpenMetrics)
+        .exemplars(false);
+    let mut buffer = Vec::new();
+    text::encode_with_options(&mut buffer, registry, &options)?;
+    let buffer = String::from_utf8(buffer).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    text::parse(&buffer).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
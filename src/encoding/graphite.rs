@@ -0,0 +1,178 @@
+//! Encoding of a [`Registry`] in the [Graphite plaintext
+//! protocol](https://graphite.readthedocs.io/en/latest/feeding-carbon.html#the-plaintext-protocol),
+//! e.g. to keep feeding dashboards still reading from Graphite.
+//!
+//! Requires the `graphite` feature. See [`encode`] for details.
+
+use crate::encoding::text::EncodeMetric;
+use crate::registry::Registry;
+use std::io::{Error, Write};
+
+/// How labels are mapped onto the Graphite metric path, see [`encode`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LabelScheme {
+    /// Append each label as a [Graphite
+    /// tag](https://graphite.readthedocs.io/en/latest/tags.html), e.g.
+    /// `http_requests_total;method=GET;status=200`.
+    ///
+    /// `;` and whitespace in label names and values are replaced by `_`, as
+    /// are `!`, `^` and `=` in label names and a leading `~` in label values.
+    #[default]
+    Tagged,
+    /// Append each label name and value as path segments, e.g.
+    /// `http_requests_total.method.GET.status.200`.
+    ///
+    /// `.`, `;`, `/` and whitespace in label names and values are replaced by
+    /// `_`, e.g. the histogram bucket label `le="0.5"` becomes `le.0_5`.
+    Dotted,
+}
+
+/// Encode the metrics of the [`Registry`] as Graphite plaintext protocol lines
+/// `<path> <value> <timestamp>`.
+///
+/// Each sample of the Open Metrics text encoding, see
+/// [`encode`](crate::encoding::text::encode), becomes one line. The path is
+/// the sample name, including suffixes like `_total` or `_bucket`, followed by
+/// the labels of the sample as per the [`LabelScheme`]. Labels with an empty
+/// value are omitted, same as in Prometheus. Samples with a non-finite value
+/// are skipped, as Graphite does not support them. Samples without a
+/// timestamp of their own use `timestamp`, in seconds since the Unix epoch.
+///
+/// ```
+/// # use prometheus_client::encoding::graphite::{encode, LabelScheme};
+/// # use prometheus_client::metrics::counter::Counter;
+/// # use prometheus_client::metrics::family::Family;
+/// # use prometheus_client::registry::Registry;
+/// #
+/// let mut registry = Registry::default();
+/// let requests = Family::<Vec<(String, String)>, Counter>::default();
+/// registry.register("http_requests", "Number of HTTP requests", requests.clone());
+/// requests.get_or_create(&vec![("method".to_owned(), "GET".to_owned())]).inc();
+///
+/// let mut buffer = vec![];
+/// encode(&mut buffer, &registry, LabelScheme::Tagged, 1_600_000_000).unwrap();
+/// assert_eq!(
+///     "http_requests_total;method=GET 1 1600000000\n",
+///     String::from_utf8(buffer).unwrap(),
+/// );
+/// ```
+pub fn encode<W, M>(
+    writer: &mut W,
+    registry: &Registry<M>,
+    scheme: LabelScheme,
+    timestamp: u64,
+) -> Result<(), Error>
+where
+    W: Write,
+    M: EncodeMetric,
+{
+    let families = super::encode_parsed(registry)?;
+
+    for sample in families.iter().flat_map(|family| family.samples.iter()) {
+        if !sample.value.is_finite() {
+            continue;
+        }
+
+        writer.write_all(sample.name.as_bytes())?;
+        for (name, value) in sample.labels.iter().filter(|(_, value)| !value.is_empty()) {
+            match scheme {
+                LabelScheme::Tagged => write!(
+                    writer,
+                    ";{}={}",
+                    sanitize(name, |c| matches!(c, ';' | '!' | '^' | '=')),
+                    sanitize_tag_value(value),
+                )?,
+                LabelScheme::Dotted => write!(
+                    writer,
+                    ".{}.{}",
+                    sanitize(name, is_reserved_in_path),
+                    sanitize(value, is_reserved_in_path),
+                )?,
+            }
+        }
+        let timestamp = sample.timestamp.map_or(timestamp, |t| t as u64);
+        writeln!(writer, " {} {}", sample.value, timestamp)?;
+    }
+
+    writer.flush()
+}
+
+/// Replaces whitespace and `reserved` characters with `_`.
+fn sanitize(s: &str, reserved: impl Fn(char) -> bool) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_whitespace() || reserved(c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Whether `c` separates path segments (`.`), tags (`;`) or, once stored by
+/// Carbon, directories (`/`).
+fn is_reserved_in_path(c: char) -> bool {
+    matches!(c, '.' | ';' | '/')
+}
+
+/// Like [`sanitize`], additionally replacing a leading `~`, which Graphite
+/// does not allow in tag values.
+fn sanitize_tag_value(value: &str) -> String {
+    let mut value = sanitize(value, |c| c == ';');
+    if value.starts_with('~') {
+        value.replace_range(..1, "_");
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::counter::Counter;
+    use crate::metrics::family::Family;
+    use crate::metrics::gauge::Gauge;
+    use crate::metrics::histogram::Histogram;
+    use std::sync::atomic::AtomicU64;
+
+    fn encode_to_string(registry: &Registry, scheme: LabelScheme) -> String {
+        let mut buffer = vec![];
+        encode(&mut buffer, registry, scheme, 42).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn encode_tagged_and_dotted() {
+        let mut registry = <Registry>::default();
+        let requests = Family::<Vec<(String, String)>, Counter>::default();
+        registry.register("requests", "Requests", Box::new(requests.clone()));
+        let histogram = Histogram::new([0.5].into_iter());
+        registry.register("latency", "Latency", Box::new(histogram.clone()));
+        let gauge = Gauge::<f64, AtomicU64>::default();
+        registry.register("temperature", "Temperature", Box::new(gauge.clone()));
+
+        requests
+            .get_or_create(&vec![
+                ("path".to_owned(), "/a b;c".to_owned()),
+                ("user".to_owned(), "~me".to_owned()),
+                ("empty".to_owned(), "".to_owned()),
+            ])
+            .inc();
+        histogram.observe(0.25);
+        gauge.set(f64::NAN);
+
+        let expected = "requests_total;path=/a_b_c;user=_me 1 42\n".to_owned()
+            + "latency_sum 0.25 42\n"
+            + "latency_count 1 42\n"
+            + "latency_bucket;le=0.5 1 42\n"
+            + "latency_bucket;le=+Inf 1 42\n";
+        assert_eq!(expected, encode_to_string(&registry, LabelScheme::Tagged));
+
+        let expected = "requests_total.path._a_b_c.user.~me 1 42\n".to_owned()
+            + "latency_sum 0.25 42\n"
+            + "latency_count 1 42\n"
+            + "latency_bucket.le.0_5 1 42\n"
+            + "latency_bucket.le.+Inf 1 42\n";
+        assert_eq!(expected, encode_to_string(&registry, LabelScheme::Dotted));
+    }
+}
@@ -0,0 +1,181 @@
+//! Encoding of a [`Registry`] in the [InfluxDB line
+//! protocol](https://docs.influxdata.com/influxdb/v2/reference/syntax/line-protocol/),
+//! e.g. to reuse the same instrumentation with an InfluxDB backend.
+//!
+//! Requires the `influx` feature. See [`encode`] for details.
+
+use crate::encoding::text::{EncodeMetric, ParsedFamily};
+use crate::registry::Registry;
+use std::io::{Error, Write};
+
+/// Encode the metrics of the [`Registry`] as InfluxDB line protocol lines
+/// `<measurement>[,<tag>=<value>...] <field>=<value>[,...] [<timestamp>]`.
+///
+/// Each metric family becomes a measurement named like the family, with one
+/// line per label set, the labels becoming tags. The samples of a label set
+/// become fields, named:
+///
+/// - `value` for samples without suffix, e.g. of a gauge,
+/// - the suffix without the leading `_` otherwise, e.g. `total` for a counter
+///   and `sum` and `count` for a histogram,
+/// - the upper bound for histogram buckets, e.g. `0.5` and `+Inf`, and the
+///   quantile for summaries, i.e. the value of the `le` respectively
+///   `quantile` label, which is thus not a tag.
+///
+/// Labels with an empty value are omitted, as InfluxDB does not support empty
+/// tag values. Samples with a non-finite value are skipped for the same
+/// reason. Lines use the timestamp of their first sample with a timestamp of
+/// its own, falling back to `timestamp`, both in nanoseconds since the Unix
+/// epoch. Without either, the line has no timestamp and InfluxDB uses the
+/// time of ingestion.
+///
+/// ```
+/// # use prometheus_client::encoding::influx::encode;
+/// # use prometheus_client::metrics::counter::Counter;
+/// # use prometheus_client::metrics::family::Family;
+/// # use prometheus_client::registry::Registry;
+/// #
+/// let mut registry = Registry::default();
+/// let requests = Family::<Vec<(String, String)>, Counter>::default();
+/// registry.register("http_requests", "Number of HTTP requests", requests.clone());
+/// requests.get_or_create(&vec![("method".to_owned(), "GET".to_owned())]).inc();
+///
+/// let mut buffer = vec![];
+/// encode(&mut buffer, &registry, None).unwrap();
+/// assert_eq!(
+///     "http_requests,method=GET total=1\n",
+///     String::from_utf8(buffer).unwrap(),
+/// );
+/// ```
+pub fn encode<W, M>(
+    writer: &mut W,
+    registry: &Registry<M>,
+    timestamp: Option<u64>,
+) -> Result<(), Error>
+where
+    W: Write,
+    M: EncodeMetric,
+{
+    for family in super::encode_parsed(registry)? {
+        for line in lines(&family) {
+            if line.fields.is_empty() {
+                continue;
+            }
+
+            write_escaped(writer, &family.name, &[',', ' '])?;
+            for (name, value) in line.tags.iter().filter(|(_, value)| !value.is_empty()) {
+                writer.write_all(b",")?;
+                write_escaped(writer, name, &[',', '=', ' '])?;
+                writer.write_all(b"=")?;
+                write_escaped(writer, value, &[',', '=', ' '])?;
+            }
+            for (i, (name, value)) in line.fields.iter().enumerate() {
+                writer.write_all(if i == 0 { b" " } else { b"," })?;
+                write_escaped(writer, name, &[',', '=', ' '])?;
+                write!(writer, "={}", value)?;
+            }
+            match line.timestamp.or(timestamp) {
+                Some(timestamp) => writeln!(writer, " {}", timestamp)?,
+                None => writeln!(writer)?,
+            }
+        }
+    }
+
+    writer.flush()
+}
+
+/// A single line protocol line, i.e. the samples of one label set.
+#[derive(Debug, Default)]
+struct Line<'a> {
+    tags: Vec<(&'a str, &'a str)>,
+    fields: Vec<(&'a str, f64)>,
+    timestamp: Option<u64>,
+}
+
+/// Group the samples of the family by their labels, excluding `le` and
+/// `quantile`, in the order of their first sample.
+fn lines(family: &ParsedFamily) -> Vec<Line<'_>> {
+    let mut lines: Vec<Line> = Vec::new();
+    for sample in &family.samples {
+        let mut field = match sample.name.strip_prefix(family.name.as_str()) {
+            Some("") | None => "value",
+            Some(suffix) => suffix.trim_start_matches('_'),
+        };
+        let mut tags = Vec::with_capacity(sample.labels.len());
+        for (name, value) in &sample.labels {
+            match name.as_str() {
+                "le" | "quantile" => field = value,
+                _ => tags.push((name.as_str(), value.as_str())),
+            }
+        }
+
+        let line = match lines.iter().position(|line| line.tags == tags) {
+            Some(i) => &mut lines[i],
+            None => {
+                lines.push(Line {
+                    tags,
+                    ..Default::default()
+                });
+                lines.last_mut().expect("lines not to be empty.")
+            }
+        };
+        if sample.value.is_finite() {
+            line.fields.push((field, sample.value));
+        }
+        if line.timestamp.is_none() {
+            line.timestamp = sample.timestamp.map(|t| (t * 1e9) as u64);
+        }
+    }
+    lines
+}
+
+fn write_escaped(writer: &mut dyn Write, s: &str, special: &[char]) -> Result<(), Error> {
+    for c in s.chars() {
+        if c == '\\' || special.contains(&c) {
+            writer.write_all(b"\\")?;
+        }
+        write!(writer, "{}", c)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::counter::Counter;
+    use crate::metrics::family::Family;
+    use crate::metrics::gauge::Gauge;
+    use crate::metrics::histogram::Histogram;
+    use std::sync::atomic::AtomicU64;
+
+    #[test]
+    fn encode_families() {
+        let mut registry = <Registry>::default();
+        let requests = Family::<Vec<(String, String)>, Counter>::default();
+        registry.register("requests", "Requests", Box::new(requests.clone()));
+        let latency = Family::<Vec<(String, String)>, Histogram>::new_with_constructor(|| {
+            Histogram::new([0.5].into_iter())
+        });
+        registry.register("latency", "Latency", Box::new(latency.clone()));
+        let gauge = Gauge::<f64, AtomicU64>::default();
+        registry.register("temperature", "Temperature", Box::new(gauge.clone()));
+
+        requests
+            .get_or_create(&vec![
+                ("path".to_owned(), "/a b,c".to_owned()),
+                ("empty".to_owned(), "".to_owned()),
+            ])
+            .inc();
+        latency
+            .get_or_create(&vec![("method".to_owned(), "GET".to_owned())])
+            .observe(0.25);
+        gauge.set(f64::NAN);
+
+        let mut buffer = vec![];
+        encode(&mut buffer, &registry, Some(42)).unwrap();
+
+        let expected = "requests,path=/a\\ b\\,c total=1 42\n".to_owned()
+            + "latency,method=GET sum=0.25,count=1,0.5=1,+Inf=1 42\n";
+        assert_eq!(expected, String::from_utf8(buffer).unwrap());
+    }
+}
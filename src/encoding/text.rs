@@ -24,624 +24,3499 @@
 //! assert_eq!(expected, String::from_utf8(buffer).unwrap());
 //! ```
 
-use crate::metrics::counter::{self, Counter};
+use crate::metrics::counter::{self, Counter, LabeledCounter};
 use crate::metrics::exemplar::{CounterWithExemplar, Exemplar, HistogramWithExemplars};
-use crate::metrics::family::{Family, MetricConstructor};
-use crate::metrics::gauge::{self, Gauge};
-use crate::metrics::histogram::Histogram;
+use crate::metrics::family::{Family, LabeledMetricConstructor};
+use crate::metrics::gauge::{self, Gauge, GaugeMax, GaugeWithMax, LabeledGauge, SumGauge};
+use crate::metrics::histogram::{ConstHistogram, Histogram, IntHistogram};
 use crate::metrics::info::Info;
 use crate::metrics::{MetricType, TypedMetric};
-use crate::registry::{Registry, Unit};
+use crate::registry::{Descriptor, Registry, Unit};
+#[cfg(feature = "smallvec")]
+use smallvec::SmallVec;
 
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
 use std::io::Write;
-use std::ops::Deref;
+use std::ops::{Add, Deref, Sub};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
 
 pub use prometheus_client_derive_text_encode::*;
 
+mod parse;
+
+pub use parse::{parse, ParseError, ParsedExemplar, ParsedFamily, ParsedSample};
+
+/// Encode the metrics of the [`Registry`] with its
+/// [`Registry::encode_options`].
+///
+/// Metric families are encoded in the order of [`Registry::iter`], i.e. in
+/// registration order, unless sorted by name via
+/// [`EncodeOptions::sort_by_name`]. The order of the series within a
+/// [`Family`] is unspecified.
+///
+/// The `writer` is flushed after the final `# EOF` line, thus one can pass a
+/// buffered writer like [`std::io::BufWriter`] without flushing it manually.
 pub fn encode<W, M>(writer: &mut W, registry: &Registry<M>) -> Result<(), std::io::Error>
 where
     W: Write,
     M: EncodeMetric,
 {
-    for (desc, metric) in registry.iter() {
-        writer.write_all(b"# HELP ")?;
-        writer.write_all(desc.name().as_bytes())?;
-        if let Some(unit) = desc.unit() {
-            writer.write_all(b"_")?;
-            unit.encode(writer)?;
-        }
-        writer.write_all(b" ")?;
-        writer.write_all(desc.help().as_bytes())?;
-        writer.write_all(b"\n")?;
+    encode_with_options(writer, registry, registry.encode_options())
+}
 
-        writer.write_all(b"# TYPE ")?;
-        writer.write_all(desc.name().as_bytes())?;
-        if let Some(unit) = desc.unit() {
-            writer.write_all(b"_")?;
-            unit.encode(writer)?;
-        }
-        writer.write_all(b" ")?;
-        metric.metric_type().encode(writer)?;
-        writer.write_all(b"\n")?;
+/// Like [`encode`] but allows customizing the output via [`EncodeOptions`].
+///
+/// ```
+/// # use prometheus_client::encoding::text::{encode_with_options, EncodeOptions};
+/// # use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+/// # use prometheus_client::registry::Registry;
+/// #
+/// let mut registry = Registry::default();
+/// let histogram = Histogram::new(exponential_buckets(1.0, 2.0, 10));
+/// registry.register("my_histogram", "This is my histogram", histogram.clone());
+/// histogram.observe(3.0);
+///
+/// let options = EncodeOptions::default().histograms_as_summaries(vec![0.5]);
+/// let mut buffer = vec![];
+/// encode_with_options(&mut buffer, &registry, &options).unwrap();
+///
+/// let expected = "# HELP my_histogram This is my histogram.\n".to_owned() +
+///                "# TYPE my_histogram summary\n" +
+///                "my_histogram_sum 3.0\n" +
+///                "my_histogram_count 1\n" +
+///                "my_histogram{quantile=\"0.5\"} 3.0\n" +
+///                "# EOF\n";
+/// assert_eq!(expected, String::from_utf8(buffer).unwrap());
+/// ```
+pub fn encode_with_options<W, M>(
+    writer: &mut W,
+    registry: &Registry<M>,
+    options: &EncodeOptions,
+) -> Result<(), std::io::Error>
+where
+    W: Write,
+    M: EncodeMetric,
+{
+    registry.run_pre_scrape_hooks();
 
-        if let Some(unit) = desc.unit() {
-            writer.write_all(b"# UNIT ")?;
-            writer.write_all(desc.name().as_bytes())?;
-            writer.write_all(b"_")?;
-            unit.encode(writer)?;
-            writer.write_all(b" ")?;
-            unit.encode(writer)?;
-            writer.write_all(b"\n")?;
-        }
+    encode_registry_filtered(writer, registry, options, &|_| true, true)
+}
 
-        let encoder = Encoder {
-            writer,
-            name: desc.name(),
-            unit: desc.unit(),
-            const_labels: desc.labels(),
-            labels: None,
+/// Like [`encode`], but only encodes the metric families for whose
+/// [`Descriptor`] `filter` returns `true`, e.g. to omit metrics registered via
+/// [`Registry::register_sensitive`] for unauthorized scrapes.
+///
+/// ```
+/// # use prometheus_client::encoding::text::encode_with_filter;
+/// # use prometheus_client::metrics::counter::Counter;
+/// # use prometheus_client::registry::Registry;
+/// #
+/// let mut registry: Registry<Counter> = Registry::default();
+/// registry.register("requests", "Number of requests", Counter::default());
+/// registry.register("errors", "Number of errors", Counter::default());
+///
+/// let mut buffer = vec![];
+/// encode_with_filter(&mut buffer, &registry, |desc| desc.name() != "errors").unwrap();
+///
+/// let expected = "# HELP requests Number of requests.\n".to_owned() +
+///                "# TYPE requests counter\n" +
+///                "requests_total 0\n" +
+///                "# EOF\n";
+/// assert_eq!(expected, String::from_utf8(buffer).unwrap());
+/// ```
+pub fn encode_with_filter<W, M, F>(
+    writer: &mut W,
+    registry: &Registry<M>,
+    filter: F,
+) -> Result<(), std::io::Error>
+where
+    W: Write,
+    M: EncodeMetric,
+    F: Fn(&Descriptor) -> bool,
+{
+    registry.run_pre_scrape_hooks();
+
+    encode_registry_filtered(writer, registry, registry.encode_options(), &filter, true)
+}
+
+/// Encode the metric families passing `filter`, resetting histograms in
+/// reset-on-read mode if `reset_on_read` is set, see
+/// [`Histogram::with_reset_on_read`].
+fn encode_registry_filtered<M: EncodeMetric>(
+    writer: &mut dyn Write,
+    registry: &Registry<M>,
+    options: &EncodeOptions,
+    filter: &dyn Fn(&Descriptor) -> bool,
+    reset_on_read: bool,
+) -> Result<(), std::io::Error> {
+    let families: Vec<_> = families(registry, options)
+        .into_iter()
+        .filter(|(_, desc, _)| filter(desc))
+        .collect();
+
+    // Check upfront, not to write a partial exposition.
+    let mut family_names = HashMap::new();
+    let mut metadata = Vec::with_capacity(families.len());
+    for (name, desc, metric) in &families {
+        metadata.push(insert_family_name(
+            &mut family_names,
+            name,
+            desc,
+            *metric,
+            options,
+        )?);
+    }
+
+    for ((name, desc, metric), metadata) in families.into_iter().zip(metadata) {
+        encode_metric(writer, name, desc, metric, options, metadata, reset_on_read)?;
+    }
+
+    if options.format == Format::OpenMetrics {
+        writer.write_all(b"# EOF\n")?;
+    }
+
+    writer.flush()
+}
+
+/// Like [`encode`], but continues past metric families failing to encode,
+/// returning an [`EncodeReport`] of the failed families.
+///
+/// Where [`encode`] fails the whole exposition on the first failing family,
+/// e.g. a custom [`Encode`] implementation returning an error,
+/// [`encode_lenient`] omits the failing family from the output, leaving the
+/// remaining families intact. Errors writing to `writer` itself still abort
+/// the encoding.
+///
+/// ```
+/// # use prometheus_client::encoding::text::{encode_lenient, Encode};
+/// # use prometheus_client::metrics::counter::Counter;
+/// # use prometheus_client::metrics::family::Family;
+/// # use prometheus_client::registry::Registry;
+/// # use std::io::{Error, ErrorKind, Write};
+/// #
+/// #[derive(Clone, Hash, PartialEq, Eq)]
+/// struct Broken;
+///
+/// impl Encode for Broken {
+///     fn encode(&self, _writer: &mut dyn Write) -> Result<(), Error> {
+///         Err(Error::new(ErrorKind::InvalidData, "broken"))
+///     }
+/// }
+///
+/// let mut registry = <Registry>::default();
+/// let family = Family::<Broken, Counter>::default();
+/// family.get_or_create(&Broken).inc();
+/// registry.register("broken", "Broken", Box::new(family));
+/// registry.register("my_counter", "My counter", Box::new(Counter::<u64>::default()));
+///
+/// let mut buffer = vec![];
+/// let report = encode_lenient(&mut buffer, &registry).unwrap();
+///
+/// assert_eq!("broken", report.failures()[0].0);
+/// assert_eq!(
+///     "# HELP my_counter My counter.\n".to_owned() +
+///     "# TYPE my_counter counter\n" +
+///     "my_counter_total 0\n" +
+///     "# EOF\n",
+///     String::from_utf8(buffer).unwrap(),
+/// );
+/// ```
+pub fn encode_lenient<W, M>(
+    writer: &mut W,
+    registry: &Registry<M>,
+) -> Result<EncodeReport, std::io::Error>
+where
+    W: Write,
+    M: EncodeMetric,
+{
+    registry.run_pre_scrape_hooks();
+
+    let options = registry.encode_options();
+    let mut report = EncodeReport::default();
+    let mut buffer = Vec::new();
+    let mut family_names = HashMap::new();
+    for (name, desc, metric) in families(registry, options) {
+        let metadata = match insert_family_name(&mut family_names, name, desc, metric, options) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                let mut family_name = Vec::new();
+                write_family_name(&mut family_name, name, desc, metric.metric_type())?;
+                report
+                    .failures
+                    .push((String::from_utf8_lossy(&family_name).into_owned(), e));
+                continue;
+            }
         };
 
-        metric.encode(encoder)?;
+        buffer.clear();
+        match encode_metric(&mut buffer, name, desc, metric, options, metadata, false) {
+            Ok(()) => writer.write_all(&buffer)?,
+            Err(e) => {
+                let mut family_name = Vec::new();
+                write_family_name(&mut family_name, name, desc, metric.metric_type())?;
+                report
+                    .failures
+                    .push((String::from_utf8_lossy(&family_name).into_owned(), e));
+            }
+        }
     }
 
-    writer.write_all(b"# EOF\n")?;
+    if options.format == Format::OpenMetrics {
+        writer.write_all(b"# EOF\n")?;
+    }
+    writer.flush()?;
 
-    Ok(())
+    Ok(report)
 }
 
-pub trait Encode {
-    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error>;
+/// Report of [`encode_lenient`] on the metric families that failed to encode.
+#[derive(Debug, Default)]
+pub struct EncodeReport {
+    failures: Vec<(String, std::io::Error)>,
 }
 
-impl Encode for f64 {
-    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
-        writer.write_all(dtoa::Buffer::new().format(*self).as_bytes())?;
-        Ok(())
+impl EncodeReport {
+    /// Whether all metric families have been encoded successfully.
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
     }
-}
 
-impl Encode for u64 {
-    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
-        writer.write_all(itoa::Buffer::new().format(*self).as_bytes())?;
-        Ok(())
+    /// The name of each metric family that failed to encode, including its
+    /// unit, and the error it failed with, in encoding order.
+    pub fn failures(&self) -> &[(String, std::io::Error)] {
+        &self.failures
     }
 }
 
-impl Encode for u32 {
-    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
-        writer.write_all(itoa::Buffer::new().format(*self).as_bytes())?;
-        Ok(())
+/// Like [`encode`], but stops before the first metric family that would
+/// exceed `max_bytes`, e.g. to protect a constrained transport from a scrape
+/// blown up by a cardinality bug.
+///
+/// The output only ever ends at a family boundary, followed by the final
+/// `# EOF` line, thus it is a valid, if incomplete, exposition. `max_bytes`
+/// includes the `# EOF` line, which is written even if it alone exceeds
+/// `max_bytes`.
+///
+/// ```
+/// # use prometheus_client::encoding::text::encode_with_limit;
+/// # use prometheus_client::metrics::counter::Counter;
+/// # use prometheus_client::registry::Registry;
+/// #
+/// let mut registry: Registry<Counter> = Registry::default();
+/// registry.register("a", "A", Counter::default());
+/// registry.register("b", "B", Counter::default());
+///
+/// let mut buffer = vec![];
+/// let report = encode_with_limit(&mut buffer, &registry, 64).unwrap();
+/// assert!(report.truncated());
+/// assert_eq!(buffer.len(), report.bytes_written());
+/// assert_eq!(
+///     "# HELP a A.\n# TYPE a counter\na_total 0\n# EOF\n",
+///     String::from_utf8(buffer).unwrap(),
+/// );
+/// ```
+pub fn encode_with_limit<W, M>(
+    writer: &mut W,
+    registry: &Registry<M>,
+    max_bytes: usize,
+) -> Result<EncodeLimitReport, std::io::Error>
+where
+    W: Write,
+    M: EncodeMetric,
+{
+    registry.run_pre_scrape_hooks();
+
+    let options = registry.encode_options();
+    let families = families(registry, options);
+    let mut family_names = HashMap::new();
+    let mut metadata = Vec::with_capacity(families.len());
+    for (name, desc, metric) in &families {
+        metadata.push(insert_family_name(
+            &mut family_names,
+            name,
+            desc,
+            *metric,
+            options,
+        )?);
     }
-}
 
-impl<T: Encode> Encode for &[T] {
-    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
-        if self.is_empty() {
-            return Ok(());
+    let eof: &[u8] = match options.format {
+        Format::OpenMetrics => b"# EOF\n",
+        Format::PrometheusText => b"",
+    };
+    let mut report = EncodeLimitReport::default();
+    let mut buffer = Vec::new();
+    for ((name, desc, metric), metadata) in families.into_iter().zip(metadata) {
+        buffer.clear();
+        encode_metric(&mut buffer, name, desc, metric, options, metadata, false)?;
+        if report.bytes_written + buffer.len() + eof.len() > max_bytes {
+            report.truncated = true;
+            break;
         }
+        writer.write_all(&buffer)?;
+        report.bytes_written += buffer.len();
+    }
 
-        let mut iter = self.iter().peekable();
-        while let Some(x) = iter.next() {
-            x.encode(writer)?;
+    writer.write_all(eof)?;
+    report.bytes_written += eof.len();
+    writer.flush()?;
 
-            if iter.peek().is_some() {
-                writer.write_all(b",")?;
-            }
-        }
+    Ok(report)
+}
 
-        Ok(())
-    }
+/// Report of [`encode_with_limit`].
+#[derive(Debug, Default)]
+pub struct EncodeLimitReport {
+    bytes_written: usize,
+    truncated: bool,
 }
 
-impl<T: Encode> Encode for Vec<T> {
-    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
-        self.as_slice().encode(writer)
+impl EncodeLimitReport {
+    /// The number of bytes written, including the `# EOF` line.
+    pub fn bytes_written(&self) -> usize {
+        self.bytes_written
+    }
+
+    /// Whether metric families have been omitted due to the limit.
+    pub fn truncated(&self) -> bool {
+        self.truncated
     }
 }
 
-impl<K: Encode, V: Encode> Encode for (K, V) {
-    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
-        let (key, value) = self;
+/// Encode each metric family of the [`Registry`] separately, calling `f` with
+/// the name of the family, including its unit, and its encoding, e.g. to route
+/// each family to a different shard.
+///
+/// Each encoding includes its own `# HELP` and `# TYPE` lines and, in the Open
+/// Metrics format, is terminated by `# EOF`, thus is a valid exposition on its
+/// own.
+///
+/// ```
+/// # use prometheus_client::encoding::text::encode_by_family;
+/// # use prometheus_client::metrics::counter::Counter;
+/// # use prometheus_client::metrics::gauge::Gauge;
+/// # use prometheus_client::registry::Registry;
+/// #
+/// let mut registry = <Registry>::default();
+/// registry.register("my_counter", "This is my counter", Box::new(Counter::<u64>::default()));
+/// registry.register("my_gauge", "This is my gauge", Box::new(Gauge::<u64>::default()));
+///
+/// let mut families = vec![];
+/// encode_by_family(&registry, |name, encoded| {
+///     families.push((name.to_owned(), String::from_utf8(encoded.to_vec()).unwrap()));
+/// })
+/// .unwrap();
+///
+/// assert_eq!("my_gauge", families[1].0);
+/// assert_eq!(
+///     "# HELP my_gauge This is my gauge.\n".to_owned() +
+///     "# TYPE my_gauge gauge\n" +
+///     "my_gauge 0\n" +
+///     "# EOF\n",
+///     families[1].1,
+/// );
+/// ```
+pub fn encode_by_family<M, F>(registry: &Registry<M>, mut f: F) -> Result<(), std::io::Error>
+where
+    M: EncodeMetric,
+    F: FnMut(&str, &[u8]),
+{
+    registry.run_pre_scrape_hooks();
+
+    let options = registry.encode_options();
+    let mut family_name = Vec::new();
+    let mut buffer = Vec::new();
+    for (name, desc, metric) in families(registry, options) {
+        family_name.clear();
+        write_family_name(&mut family_name, name, desc, metric.metric_type())?;
+        let family_name = std::str::from_utf8(&family_name)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+        buffer.clear();
+        encode_metric(&mut buffer, name, desc, metric, options, true, false)?;
+        if options.format == Format::OpenMetrics {
+            buffer.extend_from_slice(b"# EOF\n");
+        }
 
-        key.encode(writer)?;
-        writer.write_all(b"=\"")?;
+        f(family_name, &buffer);
+    }
 
-        value.encode(writer)?;
-        writer.write_all(b"\"")?;
+    Ok(())
+}
 
-        Ok(())
+/// Encode the single metric family with the given name, e.g. for a per-metric
+/// scrape endpoint, returning `false` if no such family exists.
+///
+/// The name is the full name of the family as encoded, i.e. including the
+/// prefixes of the sub-registries and the unit. The encoding includes the
+/// `# HELP`, `# TYPE` and `# UNIT` lines and is terminated by `# EOF`. Runs the
+/// pre-scrape hooks of the whole [`Registry`].
+///
+/// ```
+/// # use prometheus_client::encoding::text::encode_family;
+/// # use prometheus_client::metrics::counter::Counter;
+/// # use prometheus_client::registry::{Registry, Unit};
+/// #
+/// let mut registry = Registry::default();
+/// let counter: Counter = Counter::default();
+/// registry
+///     .sub_registry_with_prefix("my_subsystem")
+///     .register_with_unit("my_counter", "This is my counter", Unit::Seconds, counter.clone());
+///
+/// let mut buffer = vec![];
+/// assert!(encode_family(&mut buffer, &registry, "my_subsystem_my_counter_seconds").unwrap());
+///
+/// let expected = "# HELP my_subsystem_my_counter_seconds This is my counter.\n".to_owned() +
+///                "# TYPE my_subsystem_my_counter_seconds counter\n" +
+///                "# UNIT my_subsystem_my_counter_seconds seconds\n" +
+///                "my_subsystem_my_counter_seconds_total 0\n" +
+///                "# EOF\n";
+/// assert_eq!(expected, String::from_utf8(buffer).unwrap());
+///
+/// assert!(!encode_family(&mut vec![], &registry, "my_counter").unwrap());
+/// ```
+pub fn encode_family<W, M>(
+    writer: &mut W,
+    registry: &Registry<M>,
+    name: &str,
+) -> Result<bool, std::io::Error>
+where
+    W: Write,
+    M: EncodeMetric,
+{
+    registry.run_pre_scrape_hooks();
+
+    let options = registry.encode_options();
+    let mut family_name = Vec::new();
+    for (registered_name, desc, metric) in families(registry, options) {
+        family_name.clear();
+        write_family_name(
+            &mut family_name,
+            registered_name,
+            desc,
+            metric.metric_type(),
+        )?;
+        if family_name == name.as_bytes() {
+            encode_metric(writer, registered_name, desc, metric, options, true, false)?;
+            if options.format == Format::OpenMetrics {
+                writer.write_all(b"# EOF\n")?;
+            }
+            writer.flush()?;
+            return Ok(true);
+        }
     }
+
+    Ok(false)
 }
 
-impl Encode for &str {
-    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
-        // TODO: Can we do better?
-        writer.write_all(self.as_bytes())?;
-        Ok(())
+/// Writes the name of a metric family as encoded, i.e. with the unit and
+/// without the suffix of its [`MetricType`].
+pub(crate) fn write_family_name(
+    writer: &mut dyn Write,
+    name: &str,
+    desc: &Descriptor,
+    metric_type: MetricType,
+) -> Result<(), std::io::Error> {
+    writer.write_all(family_name(name, desc, metric_type).as_bytes())?;
+    if let Some(unit) = desc.unit() {
+        writer.write_all(b"_")?;
+        unit.encode(writer)?;
     }
+
+    Ok(())
 }
 
-impl Encode for String {
-    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
-        self.as_str().encode(writer)
+/// Insert the name of the metric family into `family_names`, returning
+/// whether it is a new family, or `false` if it continues a family of the
+/// same name and type merged via [`EncodeOptions::merge_duplicate_families`].
+/// Fails on any other duplicate.
+fn insert_family_name<M: EncodeMetric>(
+    family_names: &mut HashMap<String, &'static str>,
+    name: &str,
+    desc: &Descriptor,
+    metric: &M,
+    options: &EncodeOptions,
+) -> Result<bool, std::io::Error> {
+    let mut family_name = Vec::new();
+    write_family_name(&mut family_name, name, desc, metric.metric_type())?;
+    let family_name = String::from_utf8_lossy(&family_name).into_owned();
+    let metric_type = metric.metric_type().as_str();
+    match family_names.get(&family_name) {
+        Some(t) if options.merge_duplicate_families && *t == metric_type => Ok(false),
+        Some(_) => Err(duplicate_family_error(&family_name)),
+        None => {
+            family_names.insert(family_name, metric_type);
+            Ok(true)
+        }
     }
 }
 
-impl<'a> Encode for Cow<'a, str> {
-    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
-        self.as_ref().encode(writer)
-    }
+/// Prometheus rejects an exposition with two metric families of the same name,
+/// e.g. registered by two independent code paths.
+fn duplicate_family_error(family_name: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("metric family `{}` registered more than once", family_name),
+    )
 }
 
-impl Encode for MetricType {
-    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
-        let t = match self {
-            MetricType::Counter => "counter",
-            MetricType::Gauge => "gauge",
-            MetricType::Histogram => "histogram",
-            MetricType::Info => "info",
-            MetricType::Unknown => "unknown",
-        };
+/// The metric families of the [`Registry`] in encoding order, each alias being
+/// a family of its own. With [`EncodeOptions::merge_duplicate_families`],
+/// families of the same name are adjacent.
+fn families<'a, M: EncodeMetric>(
+    registry: &'a Registry<M>,
+    options: &EncodeOptions,
+) -> Vec<(&'a str, &'a Descriptor, &'a M)> {
+    let mut families: Vec<_> = registry
+        .iter()
+        .flat_map(|(desc, metric)| {
+            std::iter::once(desc.name())
+                .chain(desc.aliases().iter().map(String::as_str))
+                .map(move |name| (name, desc, metric))
+        })
+        .collect();
 
-        writer.write_all(t.as_bytes())?;
-        Ok(())
+    if options.sort_by_name {
+        families.sort_by_key(|(name, _, _)| *name);
     }
-}
 
-impl Encode for Unit {
-    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
-        let u = match self {
-            Unit::Amperes => "amperes",
-            Unit::Bytes => "bytes",
-            Unit::Celsius => "celsius",
-            Unit::Grams => "grams",
-            Unit::Joules => "joules",
-            Unit::Meters => "meters",
-            Unit::Ratios => "ratios",
-            Unit::Seconds => "seconds",
-            Unit::Volts => "volts",
-            Unit::Other(other) => other.as_str(),
-        };
+    if options.sort_by_cardinality {
+        families.sort_by_key(|(_, _, metric)| std::cmp::Reverse(metric.series_count()));
+    }
 
-        writer.write_all(u.as_bytes())?;
-        Ok(())
+    if options.merge_duplicate_families {
+        // Move duplicates right behind the first family of the same name.
+        let mut first_positions = HashMap::new();
+        let mut positioned: Vec<_> = families
+            .into_iter()
+            .enumerate()
+            .map(|(i, (name, desc, metric))| {
+                let mut family_name = Vec::new();
+                write_family_name(&mut family_name, name, desc, metric.metric_type())
+                    .expect("writing to a Vec not to fail.");
+                let position = *first_positions.entry(family_name).or_insert(i);
+                (position, (name, desc, metric))
+            })
+            .collect();
+        positioned.sort_by_key(|(position, _)| *position);
+        families = positioned.into_iter().map(|(_, family)| family).collect();
     }
+
+    families
 }
 
-impl Encode for () {
-    fn encode(&self, _writer: &mut dyn Write) -> Result<(), std::io::Error> {
-        Ok(())
-    }
+/// Strips the suffix of the [`MetricType`] from a metric name, e.g. `_total`
+/// from the name of a counter, as the encoder appends the suffix to each
+/// sample itself, unless registered via
+/// [`Registry::register_without_type_suffix`].
+fn family_name<'a>(name: &'a str, desc: &Descriptor, metric_type: MetricType) -> &'a str {
+    metric_type
+        .suffix()
+        .filter(|_| desc.type_suffix())
+        .and_then(|suffix| name.strip_suffix(suffix))
+        .and_then(|name| name.strip_suffix('_'))
+        .filter(|name| !name.is_empty())
+        .unwrap_or(name)
 }
 
-/// Helper type for [`EncodeMetric`], see [`EncodeMetric::encode`].
+/// Encode the metric family, omitting the `# HELP`, `# TYPE` and `# UNIT`
+/// lines unless `metadata` is set, e.g. when continuing a family merged via
+/// [`EncodeOptions::merge_duplicate_families`].
 ///
-// `Encoder` does not take a trait parameter for `writer` and `labels` because
-// `EncodeMetric` which uses `Encoder` needs to be usable as a trait object in
-// order to be able to register different metric types with a `Registry`. Trait
-// objects can not use type parameters.
-//
-// TODO: Alternative solutions to the above are very much appreciated.
-#[allow(missing_debug_implementations)]
-pub struct Encoder<'a, 'b> {
-    writer: &'a mut dyn Write,
-    name: &'a str,
-    unit: &'a Option<Unit>,
-    const_labels: &'a [(Cow<'static, str>, Cow<'static, str>)],
-    labels: Option<&'b dyn Encode>,
-}
+/// Histograms in reset-on-read mode are only reset if `reset_on_read` is
+/// set, i.e. by entry points encoding each family exactly once per scrape.
+fn encode_metric<M: EncodeMetric>(
+    writer: &mut dyn Write,
+    name: &str,
+    desc: &Descriptor,
+    metric: &M,
+    options: &EncodeOptions,
+    metadata: bool,
+    reset_on_read: bool,
+) -> Result<(), std::io::Error> {
+    let name = family_name(name, desc, metric.metric_type());
+    if metadata {
+        encode_metadata(writer, name, desc, metric, options)?;
+    }
 
-impl<'a, 'b> Encoder<'a, 'b> {
-    /// Encode a metric suffix, e.g. in the case of [`Counter`] the suffic `_total`.
-    pub fn encode_suffix(&mut self, suffix: &'static str) -> Result<BucketEncoder, std::io::Error> {
-        self.write_name_and_unit()?;
+    let encoder = Encoder {
+        writer,
+        name,
+        unit: desc.unit(),
+        const_labels: desc.labels(),
+        labels: None,
+        type_suffix: desc.type_suffix(),
+        reset_on_read,
+        options,
+    };
+
+    metric.encode(encoder)
+}
 
-        self.writer.write_all(b"_")?;
-        self.writer.write_all(suffix.as_bytes()).map(|_| ())?;
+fn encode_metadata<M: EncodeMetric>(
+    writer: &mut dyn Write,
+    name: &str,
+    desc: &Descriptor,
+    metric: &M,
+    options: &EncodeOptions,
+) -> Result<(), std::io::Error> {
+    writer.write_all(b"# HELP ")?;
+    write_metadata_name(writer, name, desc, metric.metric_type(), options)?;
+    writer.write_all(b" ")?;
+    match options
+        .help_resolver
+        .as_ref()
+        .and_then(|resolver| (resolver.0)(desc))
+    {
+        Some(help) => write_help(writer, &help)?,
+        None => write_help(writer, desc.help())?,
+    }
+    writer.write_all(b"\n")?;
+
+    writer.write_all(b"# TYPE ")?;
+    write_metadata_name(writer, name, desc, metric.metric_type(), options)?;
+    writer.write_all(b" ")?;
+    match (metric.metric_type(), options.format) {
+        (MetricType::Histogram, _)
+            if options.summary_quantiles.is_some() && metric.encodes_as_summary() =>
+        {
+            writer.write_all(b"summary")?
+        }
+        (MetricType::Info, Format::PrometheusText) => writer.write_all(b"gauge")?,
+        (MetricType::Unknown, Format::PrometheusText) => writer.write_all(b"untyped")?,
+        (metric_type, _) => metric_type.encode(writer)?,
+    }
+    writer.write_all(b"\n")?;
 
-        self.encode_labels()
+    if let (Some(unit), Format::OpenMetrics) = (desc.unit(), options.format) {
+        writer.write_all(b"# UNIT ")?;
+        writer.write_all(name.as_bytes())?;
+        writer.write_all(b"_")?;
+        unit.encode(writer)?;
+        writer.write_all(b" ")?;
+        unit.encode(writer)?;
+        writer.write_all(b"\n")?;
     }
 
-    /// Signal that the metric has no suffix.
-    pub fn no_suffix(&mut self) -> Result<BucketEncoder, std::io::Error> {
-        self.write_name_and_unit()?;
+    Ok(())
+}
 
-        self.encode_labels()
+/// Writes the text of the `# HELP` line, escaping `\` and newlines, the latter
+/// otherwise ending the line.
+fn write_help(writer: &mut dyn Write, help: &str) -> Result<(), std::io::Error> {
+    let mut start = 0;
+    for (i, byte) in help.bytes().enumerate() {
+        let escaped: &[u8] = match byte {
+            b'\\' => b"\\\\",
+            b'\n' => b"\\n",
+            _ => continue,
+        };
+        writer.write_all(&help.as_bytes()[start..i])?;
+        writer.write_all(escaped)?;
+        start = i + 1;
     }
+    writer.write_all(&help.as_bytes()[start..])
+}
 
-    fn write_name_and_unit(&mut self) -> Result<(), std::io::Error> {
-        self.writer.write_all(self.name.as_bytes())?;
-        if let Some(unit) = self.unit {
-            self.writer.write_all(b"_")?;
-            unit.encode(self.writer)?;
+/// Writes the name of the metric family in the `# HELP` and `# TYPE` lines.
+///
+/// Open Metrics refers to the family, e.g. `requests`. The Prometheus text
+/// format has no notion of families, its parsers match the metadata against
+/// the sample names exactly, thus it includes the suffix of counters and
+/// info metrics, e.g. `requests_total`.
+fn write_metadata_name(
+    writer: &mut dyn Write,
+    name: &str,
+    desc: &Descriptor,
+    metric_type: MetricType,
+    options: &EncodeOptions,
+) -> Result<(), std::io::Error> {
+    writer.write_all(name.as_bytes())?;
+    if let Some(unit) = desc.unit() {
+        writer.write_all(b"_")?;
+        unit.encode(writer)?;
+    }
+    if options.format == Format::PrometheusText && desc.type_suffix() {
+        if let Some(suffix) = metric_type.suffix() {
+            writer.write_all(b"_")?;
+            writer.write_all(suffix.as_bytes())?;
         }
-
-        Ok(())
     }
 
-    // TODO: Consider caching the encoded labels for Histograms as they stay the
-    // same but are currently encoded multiple times.
-    fn encode_labels(&mut self) -> Result<BucketEncoder, std::io::Error> {
-        let mut opened_curly_brackets = false;
+    Ok(())
+}
 
-        if !self.const_labels.is_empty() {
-            self.writer.write_all(b"{")?;
-            opened_curly_brackets = true;
+/// Encoder writing the text encoding of a [`Registry`] into fixed size
+/// buffers, one chunk at a time, e.g. to stream metrics over a transport with a
+/// fixed MTU.
+///
+/// The [`ChunkEncoder`] encodes one metric family at a time into an internal
+/// buffer, reused across chunks and scrapes, and resumes with the next family
+/// once the current one has been written. Thus each family is encoded once per
+/// scrape and the internal buffer grows to the size of the largest family
+/// only. Metrics should not be registered between the chunks of a single
+/// scrape, otherwise the chunks might not add up to a valid encoding.
+///
+/// Like [`encode`], it uses the [`Registry::encode_options`], unless created
+/// via [`ChunkEncoder::with_options`].
+///
+/// ```
+/// # use prometheus_client::encoding::text::{encode, ChunkEncoder, EncodeChunkError};
+/// # use prometheus_client::metrics::counter::Counter;
+/// # use prometheus_client::registry::Registry;
+/// #
+/// # let mut registry = Registry::default();
+/// # let counter: Counter = Counter::default();
+/// # registry.register("my_counter", "This is my counter", counter.clone());
+/// let mut encoder = ChunkEncoder::default();
+/// let mut buffer = [0u8; 16];
+/// let mut encoded = vec![];
+///
+/// loop {
+///     match encoder.encode_chunk(&registry, &mut buffer) {
+///         Ok(written) => {
+///             encoded.extend_from_slice(&buffer[..written]);
+///             break;
+///         }
+///         Err(EncodeChunkError::BufferFull { written }) => {
+///             encoded.extend_from_slice(&buffer[..written]);
+///         }
+///         Err(EncodeChunkError::Io(e)) => panic!("{}", e),
+///     }
+/// }
+/// #
+/// # let mut expected = vec![];
+/// # encode(&mut expected, &registry).unwrap();
+/// # assert_eq!(expected, encoded);
+/// ```
+#[derive(Debug, Default)]
+pub struct ChunkEncoder {
+    /// Overrides the [`Registry::encode_options`] if set.
+    options: Option<EncodeOptions>,
+    /// Whether to write the `# HELP`, `# TYPE` and `# UNIT` lines of each
+    /// family of the current scrape, `None` in between scrapes.
+    metadata: Option<Vec<bool>>,
+    /// Index of the next family to encode.
+    next_family: usize,
+    /// Encoding of the current family, written up to `offset`.
+    pending: Vec<u8>,
+    offset: usize,
+    /// Whether `pending` holds the end of the encoding.
+    last: bool,
+}
 
-            self.const_labels.encode(self.writer)?;
+impl ChunkEncoder {
+    /// Create a [`ChunkEncoder`] customizing the output via [`EncodeOptions`],
+    /// instead of the [`Registry::encode_options`] used by default.
+    pub fn with_options(options: EncodeOptions) -> Self {
+        Self {
+            options: Some(options),
+            ..Default::default()
         }
+    }
 
-        if let Some(labels) = &self.labels {
-            if opened_curly_brackets {
-                self.writer.write_all(b",")?;
-            } else {
-                opened_curly_brackets = true;
-                self.writer.write_all(b"{")?;
-            }
-            labels.encode(self.writer)?;
+    /// Encode the next chunk of the [`Registry`] into `buffer`.
+    ///
+    /// Returns the number of bytes written to `buffer` once the last chunk has
+    /// been written, after which the [`ChunkEncoder`] starts from the beginning
+    /// again. Returns [`EncodeChunkError::BufferFull`] if `buffer` has been
+    /// filled up and more chunks follow. `buffer` must not be empty.
+    pub fn encode_chunk<M: EncodeMetric>(
+        &mut self,
+        registry: &Registry<M>,
+        buffer: &mut [u8],
+    ) -> Result<usize, EncodeChunkError> {
+        let options = self
+            .options
+            .as_ref()
+            .unwrap_or_else(|| registry.encode_options());
+
+        if self.metadata.is_none() {
+            registry.run_pre_scrape_hooks();
+
+            // Check upfront, not to write a partial exposition.
+            let mut family_names = HashMap::new();
+            let metadata = families(registry, options)
+                .into_iter()
+                .map(|(name, desc, metric)| {
+                    insert_family_name(&mut family_names, name, desc, metric, options)
+                })
+                .collect::<Result<_, _>>()
+                .map_err(EncodeChunkError::Io)?;
+            self.metadata = Some(metadata);
         }
 
-        Ok(BucketEncoder {
-            opened_curly_brackets,
-            writer: self.writer,
-        })
+        let mut written = 0;
+        let mut families_of_registry = None;
+        loop {
+            let n = (self.pending.len() - self.offset).min(buffer.len() - written);
+            buffer[written..written + n]
+                .copy_from_slice(&self.pending[self.offset..self.offset + n]);
+            self.offset += n;
+            written += n;
+
+            if self.offset < self.pending.len() {
+                return Err(EncodeChunkError::BufferFull { written });
+            }
+            if self.last {
+                self.reset();
+                return Ok(written);
+            }
+
+            self.pending.clear();
+            self.offset = 0;
+
+            let families_of_registry =
+                families_of_registry.get_or_insert_with(|| families(registry, options));
+            let metadata = self.metadata.as_deref().unwrap_or_default();
+            match (
+                families_of_registry.get(self.next_family),
+                metadata.get(self.next_family),
+            ) {
+                (Some((name, desc, metric)), Some(metadata)) => {
+                    if let Err(e) = encode_metric(
+                        &mut self.pending,
+                        name,
+                        desc,
+                        *metric,
+                        options,
+                        *metadata,
+                        true,
+                    ) {
+                        self.reset();
+                        return Err(EncodeChunkError::Io(e));
+                    }
+                    self.next_family += 1;
+                }
+                _ => {
+                    if options.format == Format::OpenMetrics {
+                        self.pending.extend_from_slice(b"# EOF\n");
+                    }
+                    self.last = true;
+                }
+            }
+        }
     }
 
-    /// Encode a set of labels. Used by wrapper metric types like [`Family`].
-    pub fn with_label_set<'c, 'd>(&'c mut self, label_set: &'d dyn Encode) -> Encoder<'c, 'd> {
-        debug_assert!(self.labels.is_none());
-
-        Encoder {
-            writer: self.writer,
-            name: self.name,
-            unit: self.unit,
-            const_labels: self.const_labels,
-            labels: Some(label_set),
-        }
+    /// Start the next call to [`ChunkEncoder::encode_chunk`] from the
+    /// beginning, keeping the allocated buffer.
+    fn reset(&mut self) {
+        self.metadata = None;
+        self.next_family = 0;
+        self.pending.clear();
+        self.offset = 0;
+        self.last = false;
     }
 }
 
-#[allow(missing_debug_implementations)]
-#[must_use]
-pub struct BucketEncoder<'a> {
-    writer: &'a mut dyn Write,
-    opened_curly_brackets: bool,
+/// Error returned by [`ChunkEncoder::encode_chunk`].
+#[derive(Debug)]
+pub enum EncodeChunkError {
+    /// The buffer has been filled up with the given number of bytes and more
+    /// chunks follow.
+    BufferFull { written: usize },
+    /// Encoding a metric failed.
+    Io(std::io::Error),
 }
 
-impl<'a> BucketEncoder<'a> {
-    /// Encode a bucket. Used for the [`Histogram`] metric type.
-    pub fn encode_bucket(&mut self, upper_bound: f64) -> Result<ValueEncoder, std::io::Error> {
-        if self.opened_curly_brackets {
-            self.writer.write_all(b",")?;
-        } else {
-            self.writer.write_all(b"{")?;
-        }
-
-        self.writer.write_all(b"le=\"")?;
-        if upper_bound == f64::MAX {
-            self.writer.write_all(b"+Inf")?;
-        } else {
-            upper_bound.encode(self.writer)?;
-        }
-        self.writer.write_all(b"\"}")?;
-
-        Ok(ValueEncoder {
-            writer: self.writer,
-        })
-    }
-
-    /// Signal that the metric type has no bucket.
-    pub fn no_bucket(&mut self) -> Result<ValueEncoder, std::io::Error> {
-        if self.opened_curly_brackets {
-            self.writer.write_all(b"}")?;
+impl std::fmt::Display for EncodeChunkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodeChunkError::BufferFull { written } => {
+                write!(f, "buffer full after writing {} bytes", written)
+            }
+            EncodeChunkError::Io(e) => write!(f, "failed to encode metric: {}", e),
         }
-        Ok(ValueEncoder {
-            writer: self.writer,
-        })
     }
 }
 
-#[allow(missing_debug_implementations)]
-#[must_use]
-pub struct ValueEncoder<'a> {
-    writer: &'a mut dyn Write,
-}
+impl std::error::Error for EncodeChunkError {}
 
-impl<'a> ValueEncoder<'a> {
-    /// Encode the metric value. E.g. in the case of [`Counter`] the
-    /// monotonically increasing counter value.
-    pub fn encode_value<V: Encode>(&mut self, v: V) -> Result<ExemplarEncoder, std::io::Error> {
-        self.writer.write_all(b" ")?;
-        v.encode(self.writer)?;
-        Ok(ExemplarEncoder {
-            writer: self.writer,
-        })
+/// Encode a label set to a standalone string, the same way it is rendered as
+/// part of a metric, e.g. for logging.
+///
+/// An empty label set results in an empty string.
+///
+/// ```
+/// # use prometheus_client::encoding::text::encode_label_set;
+/// let labels = vec![("method", "GET"), ("path", "/metrics")];
+/// assert_eq!(
+///     "{method=\"GET\",path=\"/metrics\"}",
+///     encode_label_set(&labels).unwrap(),
+/// );
+/// ```
+pub fn encode_label_set(label_set: &dyn Encode) -> Result<String, std::io::Error> {
+    let mut buffer = vec![b'{'];
+    label_set.encode(&mut buffer)?;
+    if buffer.len() == 1 {
+        return Ok(String::new());
     }
-}
+    buffer.push(b'}');
 
-#[allow(missing_debug_implementations)]
-#[must_use]
-pub struct ExemplarEncoder<'a> {
-    writer: &'a mut dyn Write,
+    String::from_utf8(buffer).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
 }
 
-impl<'a> ExemplarEncoder<'a> {
-    /// Encode an exemplar for the given metric.
-    pub fn encode_exemplar<S: Encode, V: Encode>(
-        &mut self,
-        exemplar: &Exemplar<S, V>,
-    ) -> Result<(), std::io::Error> {
-        self.writer.write_all(b" # {")?;
-        exemplar.label_set.encode(self.writer)?;
-        self.writer.write_all(b"} ")?;
-        exemplar.value.encode(self.writer)?;
-        self.writer.write_all(b"\n")?;
-        Ok(())
-    }
+/// Build a label set from `key = value` pairs, with the identifiers as label
+/// names, failing to compile on duplicate label names.
+///
+/// Expands to an array of `(&str, value)` pairs, thus all values need to be of
+/// the same type.
+///
+/// ```
+/// # use prometheus_client::encoding::text::encode_label_set;
+/// # use prometheus_client::labels;
+/// # use prometheus_client::metrics::counter::Counter;
+/// # use prometheus_client::metrics::family::Family;
+/// #
+/// let family = Family::<[(&str, &str); 2], Counter>::default();
+/// family.get_or_create(&labels! { method = "GET", status = "200" }).inc();
+///
+/// assert_eq!(
+///     "{method=\"GET\",status=\"200\"}",
+///     encode_label_set(&labels! { method = "GET", status = "200" }).unwrap(),
+/// );
+/// ```
+///
+/// ```compile_fail
+/// # use prometheus_client::labels;
+/// let _ = labels! { method = "GET", method = "PUT" };
+/// ```
+#[macro_export]
+macro_rules! labels {
+    ($($key:ident = $value:expr),* $(,)?) => {{
+        // Fails to compile on duplicate label names, i.e. duplicate fields.
+        #[allow(dead_code, non_snake_case)]
+        struct DuplicateLabelNames {
+            $($key: (),)*
+        }
 
-    /// Signal that the metric type has no exemplar.
-    pub fn no_exemplar(&mut self) -> Result<(), std::io::Error> {
-        self.writer.write_all(b"\n")?;
-        Ok(())
-    }
+        [$((stringify!($key), $value)),*]
+    }};
 }
 
-/// Trait implemented by each metric type, e.g. [`Counter`], to implement its encoding.
-pub trait EncodeMetric {
-    fn encode(&self, encoder: Encoder) -> Result<(), std::io::Error>;
-
-    // One can not use [`TypedMetric`] directly, as associated constants are not
-    // object safe and thus can not be used with dynamic dispatching.
-    fn metric_type(&self) -> MetricType;
+/// Options to customize the output of [`encode_with_options`].
+///
+/// [`EncodeOptions::default`] produces the same output as [`encode`].
+#[derive(Clone, Debug, Default)]
+pub struct EncodeOptions {
+    format: Format,
+    sort_by_name: bool,
+    sort_by_cardinality: bool,
+    summary_quantiles: Option<Vec<f64>>,
+    float_precision: Option<usize>,
+    whole_floats_as_integers: bool,
+    skip_exemplars: bool,
+    help_resolver: Option<HelpResolver>,
+    merge_duplicate_families: bool,
 }
 
-impl EncodeMetric for Box<dyn EncodeMetric> {
-    fn encode(&self, encoder: Encoder) -> Result<(), std::io::Error> {
-        self.deref().encode(encoder)
-    }
+/// Resolver set via [`EncodeOptions::help_resolver`].
+#[derive(Clone)]
+struct HelpResolver(Arc<ResolveHelp>);
 
-    fn metric_type(&self) -> MetricType {
-        self.deref().metric_type()
+type ResolveHelp = dyn Fn(&Descriptor) -> Option<Cow<'static, str>> + Send + Sync;
+
+impl std::fmt::Debug for HelpResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("HelpResolver").finish()
     }
 }
 
-pub trait SendSyncEncodeMetric: EncodeMetric + Send + Sync {}
+impl EncodeOptions {
+    /// Set the text [`Format`] to encode to. Defaults to
+    /// [`Format::OpenMetrics`].
+    ///
+    /// ```
+    /// # use prometheus_client::encoding::text::{encode_with_options, EncodeOptions, Format};
+    /// # use prometheus_client::metrics::counter::Counter;
+    /// # use prometheus_client::registry::{Registry, Unit};
+    /// #
+    /// let mut registry = Registry::default();
+    /// let counter: Counter = Counter::default();
+    /// registry.register_with_unit("my_counter", "This is my counter", Unit::Seconds, counter.clone());
+    ///
+    /// let options = EncodeOptions::default().format(Format::PrometheusText);
+    /// let mut buffer = vec![];
+    /// encode_with_options(&mut buffer, &registry, &options).unwrap();
+    ///
+    /// let expected = "# HELP my_counter_seconds_total This is my counter.\n".to_owned() +
+    ///                "# TYPE my_counter_seconds_total counter\n" +
+    ///                "my_counter_seconds_total 0\n";
+    /// assert_eq!(expected, String::from_utf8(buffer).unwrap());
+    /// ```
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
 
-impl<T: EncodeMetric + Send + Sync> SendSyncEncodeMetric for T {}
+    /// Encode each [`Histogram`] as an Open Metrics summary with the given
+    /// quantiles instead of as a histogram.
+    ///
+    /// This is meant as a migration aid for consumers that only accept
+    /// summaries. The quantiles are derived from the histogram buckets at
+    /// encode time, assuming observations to be distributed linearly within
+    /// each bucket, the same way Prometheus' `histogram_quantile` does. Thus
+    /// the result is only as accurate as the bucket layout allows:
+    ///
+    /// - A quantile can not be more precise than the width of the bucket it
+    ///   falls into.
+    ///
+    /// - The lower bound of the first bucket is assumed to be `0` (if its upper
+    ///   bound is positive).
+    ///
+    /// - A quantile falling into the `+Inf` bucket is reported as the upper
+    ///   bound of the highest finite bucket.
+    ///
+    /// - A histogram without any observations reports `NaN` for every
+    ///   quantile.
+    ///
+    /// Custom [`EncodeMetric`] implementations keep their declared type, see
+    /// [`EncodeMetric::encodes_as_summary`].
+    pub fn histograms_as_summaries(mut self, quantiles: Vec<f64>) -> Self {
+        self.summary_quantiles = Some(quantiles);
+        self
+    }
 
-impl EncodeMetric for Box<dyn SendSyncEncodeMetric> {
-    fn encode(&self, encoder: Encoder) -> Result<(), std::io::Error> {
-        self.deref().encode(encoder)
+    /// Encode floating point metric values, e.g. of a `Gauge<f64, AtomicU64>`
+    /// or the sum of a [`Histogram`], with a fixed number of decimal places
+    /// instead of the shortest representation that round-trips.
+    ///
+    /// ```
+    /// # use prometheus_client::encoding::text::{encode_with_options, EncodeOptions};
+    /// # use prometheus_client::metrics::gauge::Gauge;
+    /// # use prometheus_client::registry::Registry;
+    /// # use std::sync::atomic::AtomicU64;
+    /// #
+    /// let mut registry = Registry::default();
+    /// let gauge = Gauge::<f64, AtomicU64>::default();
+    /// registry.register("my_gauge", "This is my gauge", gauge.clone());
+    /// gauge.set(1.0 / 3.0);
+    ///
+    /// let options = EncodeOptions::default().float_precision(3);
+    /// let mut buffer = vec![];
+    /// encode_with_options(&mut buffer, &registry, &options).unwrap();
+    ///
+    /// let expected = "# HELP my_gauge This is my gauge.\n".to_owned() +
+    ///                "# TYPE my_gauge gauge\n" +
+    ///                "my_gauge 0.333\n" +
+    ///                "# EOF\n";
+    /// assert_eq!(expected, String::from_utf8(buffer).unwrap());
+    /// ```
+    pub fn float_precision(mut self, precision: usize) -> Self {
+        self.float_precision = Some(precision);
+        self
     }
 
-    fn metric_type(&self) -> MetricType {
-        self.deref().metric_type()
+    /// Encode floating point metric values that are whole numbers without a
+    /// fractional part, e.g. `5` instead of `5.0`, both being valid Open
+    /// Metrics numbers. Takes precedence over
+    /// [`EncodeOptions::float_precision`] for whole numbers.
+    ///
+    /// ```
+    /// # use prometheus_client::encoding::text::{encode_with_options, EncodeOptions};
+    /// # use prometheus_client::metrics::gauge::Gauge;
+    /// # use prometheus_client::registry::Registry;
+    /// # use std::sync::atomic::AtomicU64;
+    /// #
+    /// let mut registry = Registry::default();
+    /// let gauge = Gauge::<f64, AtomicU64>::default();
+    /// registry.register("my_gauge", "This is my gauge", gauge.clone());
+    /// gauge.set(5.0);
+    ///
+    /// let options = EncodeOptions::default().whole_floats_as_integers();
+    /// let mut buffer = vec![];
+    /// encode_with_options(&mut buffer, &registry, &options).unwrap();
+    ///
+    /// let expected = "# HELP my_gauge This is my gauge.\n".to_owned() +
+    ///                "# TYPE my_gauge gauge\n" +
+    ///                "my_gauge 5\n" +
+    ///                "# EOF\n";
+    /// assert_eq!(expected, String::from_utf8(buffer).unwrap());
+    /// ```
+    pub fn whole_floats_as_integers(mut self) -> Self {
+        self.whole_floats_as_integers = true;
+        self
     }
-}
 
-/////////////////////////////////////////////////////////////////////////////////
-// Counter
+    /// Whether to encode exemplars. Defaults to `true`.
+    ///
+    /// When disabled, exemplars are omitted regardless of what the metrics
+    /// recorded, e.g. to strip exemplars in environments without tracing
+    /// without changing every metric definition. See also
+    /// [`RegistryBuilder::with_exemplars`](crate::registry::RegistryBuilder::with_exemplars).
+    pub fn exemplars(mut self, enabled: bool) -> Self {
+        self.skip_exemplars = !enabled;
+        self
+    }
 
-impl<N, A> EncodeMetric for Counter<N, A>
-where
-    N: Encode,
-    A: counter::Atomic<N>,
-{
-    fn encode(&self, encoder: Encoder) -> Result<(), std::io::Error> {
-        // TODO: Would be better to use never type instead of `()`.
-        encode_counter_with_maybe_exemplar::<(), _>(self.get(), None, encoder)
+    /// Encode metric families sorted by name instead of in registration
+    /// order, e.g. for a stable output independent of the registration order
+    /// across sub-registries.
+    pub fn sort_by_name(mut self) -> Self {
+        self.sort_by_name = true;
+        self
     }
 
-    fn metric_type(&self) -> MetricType {
-        Self::TYPE
+    /// Encode metric families sorted by their number of series, highest
+    /// first, as listed by [`Registry::cardinality_report`], e.g. to spot the
+    /// family blowing up the size of a scrape at the top of the output.
+    ///
+    /// Intended for diagnostics, as the order is neither stable across scrapes
+    /// nor of interest to Prometheus. Families with the same number of series
+    /// keep their order, e.g. sorted by name via [`EncodeOptions::sort_by_name`].
+    ///
+    /// ```
+    /// # use prometheus_client::encoding::text::{encode_with_options, EncodeOptions};
+    /// # use prometheus_client::metrics::counter::Counter;
+    /// # use prometheus_client::metrics::family::Family;
+    /// # use prometheus_client::registry::Registry;
+    /// #
+    /// let mut registry = <Registry>::default();
+    /// registry.register("requests", "Number of requests", Box::new(Counter::<u64>::default()));
+    /// let errors = Family::<Vec<(String, String)>, Counter>::default();
+    /// registry.register("errors", "Number of errors", Box::new(errors.clone()));
+    /// for code in ["400", "500"] {
+    ///     errors.get_or_create(&vec![("code".to_owned(), code.to_owned())]).inc();
+    /// }
+    ///
+    /// let options = EncodeOptions::default().sort_by_cardinality();
+    /// let mut buffer = vec![];
+    /// encode_with_options(&mut buffer, &registry, &options).unwrap();
+    /// assert!(String::from_utf8(buffer).unwrap().starts_with("# HELP errors"));
+    /// ```
+    pub fn sort_by_cardinality(mut self) -> Self {
+        self.sort_by_cardinality = true;
+        self
     }
-}
 
-// TODO: S, V, N, A are hard to grasp.
-impl<S, N, A> EncodeMetric for CounterWithExemplar<S, N, A>
-where
-    S: Encode,
-    N: Encode + Clone,
-    A: counter::Atomic<N>,
-{
-    fn encode(&self, encoder: Encoder) -> Result<(), std::io::Error> {
-        let (value, exemplar) = self.get();
-        encode_counter_with_maybe_exemplar(value, exemplar.as_ref().as_ref(), encoder)
+    /// Merge metric families of the same name and type into a single family,
+    /// e.g. a metric registered with multiple sub-registries differing in
+    /// their labels only, instead of failing the encoding.
+    ///
+    /// The `# HELP`, `# TYPE` and `# UNIT` lines are written once, taken from
+    /// the first family, followed by the series of all families of the name,
+    /// placed where the first family would be. The families need to differ in
+    /// their labels, otherwise the exposition contains duplicate series.
+    /// Families of the same name but a different type still fail the
+    /// encoding. Not applied by [`encode_by_family`] and [`encode_family`].
+    ///
+    /// ```
+    /// # use prometheus_client::encoding::text::{encode_with_options, EncodeOptions};
+    /// # use prometheus_client::metrics::counter::Counter;
+    /// # use prometheus_client::registry::Registry;
+    /// # use std::borrow::Cow;
+    /// #
+    /// let mut registry: Registry<Counter> = Registry::default();
+    /// for shard in ["0", "1"] {
+    ///     registry
+    ///         .sub_registry_with_label((Cow::Borrowed("shard"), Cow::Borrowed(shard)))
+    ///         .register("requests", "Number of requests", Counter::default());
+    /// }
+    ///
+    /// let options = EncodeOptions::default().merge_duplicate_families();
+    /// let mut buffer = vec![];
+    /// encode_with_options(&mut buffer, &registry, &options).unwrap();
+    ///
+    /// let expected = "# HELP requests Number of requests.\n".to_owned() +
+    ///                "# TYPE requests counter\n" +
+    ///                "requests_total{shard=\"0\"} 0\n" +
+    ///                "requests_total{shard=\"1\"} 0\n" +
+    ///                "# EOF\n";
+    /// assert_eq!(expected, String::from_utf8(buffer).unwrap());
+    /// ```
+    pub fn merge_duplicate_families(mut self) -> Self {
+        self.merge_duplicate_families = true;
+        self
     }
 
-    fn metric_type(&self) -> MetricType {
-        Counter::<N, A>::TYPE
+    /// Resolve the `# HELP` text of each metric family at encode time, e.g. to
+    /// localize it per scrape, falling back to the help text the metric has
+    /// been registered with where the resolver returns `None`.
+    ///
+    /// The resolved text is written without appending a `.`, escaping `\` and
+    /// newlines the same way as the registered help text.
+    ///
+    /// ```
+    /// # use prometheus_client::encoding::text::{encode_with_options, EncodeOptions};
+    /// # use prometheus_client::metrics::counter::Counter;
+    /// # use prometheus_client::registry::Registry;
+    /// # use std::borrow::Cow;
+    /// #
+    /// let mut registry = Registry::default();
+    /// let counter: Counter = Counter::default();
+    /// registry.register("my_counter", "This is my counter", counter.clone());
+    ///
+    /// let locale = "de";
+    /// let options = EncodeOptions::default().help_resolver(move |desc| {
+    ///     match (locale, desc.name()) {
+    ///         ("de", "my_counter") => Some(Cow::Borrowed("Das ist mein Zähler.")),
+    ///         _ => None,
+    ///     }
+    /// });
+    /// let mut buffer = vec![];
+    /// encode_with_options(&mut buffer, &registry, &options).unwrap();
+    ///
+    /// let expected = "# HELP my_counter Das ist mein Zähler.\n".to_owned() +
+    ///                "# TYPE my_counter counter\n" +
+    ///                "my_counter_total 0\n" +
+    ///                "# EOF\n";
+    /// assert_eq!(expected, String::from_utf8(buffer).unwrap());
+    /// ```
+    pub fn help_resolver<F>(mut self, resolver: F) -> Self
+    where
+        F: Fn(&Descriptor) -> Option<Cow<'static, str>> + Send + Sync + 'static,
+    {
+        self.help_resolver = Some(HelpResolver(Arc::new(resolver)));
+        self
     }
 }
 
-fn encode_counter_with_maybe_exemplar<S, N>(
-    value: N,
-    exemplar: Option<&Exemplar<S, N>>,
-    mut encoder: Encoder,
-) -> Result<(), std::io::Error>
-where
-    S: Encode,
-    N: Encode,
-{
-    let mut bucket_encoder = encoder.encode_suffix("total")?;
-    let mut value_encoder = bucket_encoder.no_bucket()?;
-    let mut exemplar_encoder = value_encoder.encode_value(value)?;
+/// The text format to encode to, see [`EncodeOptions::format`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// The [Open Metrics text
+    /// format](https://github.com/OpenObservability/OpenMetrics/blob/main/specification/OpenMetrics.md).
+    #[default]
+    OpenMetrics,
+    /// The [Prometheus text
+    /// format](https://prometheus.io/docs/instrumenting/exposition_formats/#text-based-format)
+    /// predating Open Metrics, for consumers not (yet) supporting the latter.
+    ///
+    /// Compared to [`Format::OpenMetrics`] the output:
+    ///
+    /// - does not end with `# EOF`,
+    ///
+    /// - does not contain `# UNIT` lines, though the unit remains part of the
+    ///   metric name,
+    ///
+    /// - uses the `untyped` type instead of `unknown` and the `gauge` type
+    ///   instead of `info`,
+    ///
+    /// - names counters and info metrics in the `# HELP` and `# TYPE` lines
+    ///   like their samples, i.e. including the `_total` respectively `_info`
+    ///   suffix,
+    ///
+    /// - does not contain exemplars.
+    PrometheusText,
+}
 
-    match exemplar {
-        Some(exemplar) => exemplar_encoder.encode_exemplar(exemplar)?,
-        None => exemplar_encoder.no_exemplar()?,
+impl Format {
+    /// The HTTP `Content-Type` of the [`Format`], i.e. [`CONTENT_TYPE`] or
+    /// [`PROMETHEUS_TEXT_CONTENT_TYPE`].
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::OpenMetrics => CONTENT_TYPE,
+            Format::PrometheusText => PROMETHEUS_TEXT_CONTENT_TYPE,
+        }
     }
-
-    Ok(())
 }
 
-/////////////////////////////////////////////////////////////////////////////////
-// Gauge
+/// The HTTP `Content-Type` of the Open Metrics text format, e.g. as produced
+/// by [`encode`].
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
 
-impl<N, A> EncodeMetric for Gauge<N, A>
-where
-    N: Encode,
-    A: gauge::Atomic<N>,
-{
-    fn encode(&self, mut encoder: Encoder) -> Result<(), std::io::Error> {
-        encoder
-            .no_suffix()?
-            .no_bucket()?
-            .encode_value(self.get())?
-            .no_exemplar()?;
+/// The HTTP `Content-Type` of the Prometheus text format, see
+/// [`Format::PrometheusText`].
+pub const PROMETHEUS_TEXT_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
 
-        Ok(())
+/// Trait implemented by label sets, label values and metric values to encode
+/// them in the text format.
+///
+/// Prefer the [`Encode`] derive macro for label sets. When implementing
+/// [`Encode`] for a label set by hand, encode each label as a `(key, value)`
+/// pair, which takes care of the `key="value"` syntax, and separate the pairs
+/// with a comma.
+///
+/// ```
+/// # use prometheus_client::encoding::text::{encode, Encode};
+/// # use prometheus_client::metrics::counter::Counter;
+/// # use prometheus_client::metrics::family::Family;
+/// # use prometheus_client::registry::Registry;
+/// # use std::io::Write;
+/// #
+/// #[derive(Clone, Hash, PartialEq, Eq)]
+/// struct Labels {
+///     method: String,
+///     status: u64,
+/// }
+///
+/// impl Encode for Labels {
+///     fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+///         ("method", self.method.as_str()).encode(writer)?;
+///         writer.write_all(b",")?;
+///         ("status", self.status).encode(writer)
+///     }
+/// }
+/// #
+/// # let mut registry = Registry::default();
+/// # let family = Family::<Labels, Counter>::default();
+/// # registry.register("my_counter", "This is my counter", family.clone());
+/// # family.get_or_create(&Labels { method: "GET".to_string(), status: 200 }).inc();
+/// #
+/// # let mut buffer = vec![];
+/// # encode(&mut buffer, &registry).unwrap();
+/// #
+/// # let expected = "# HELP my_counter This is my counter.\n".to_owned() +
+/// #                "# TYPE my_counter counter\n" +
+/// #                "my_counter_total{method=\"GET\",status=\"200\"} 1\n" +
+/// #                "# EOF\n";
+/// # assert_eq!(expected, String::from_utf8(buffer).unwrap());
+/// ```
+pub trait Encode {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error>;
+
+    /// Encode `self` as a metric value, honoring the value related
+    /// [`EncodeOptions`]. Defaults to [`Encode::encode`].
+    fn encode_value(
+        &self,
+        writer: &mut dyn Write,
+        _options: &EncodeOptions,
+    ) -> Result<(), std::io::Error> {
+        self.encode(writer)
     }
-    fn metric_type(&self) -> MetricType {
-        Self::TYPE
+
+    /// Whether `self` is a finite metric value, i.e. neither `NaN` nor
+    /// infinite. Exemplars with a non-finite value are skipped when encoding.
+    /// Defaults to `true`.
+    fn is_finite_value(&self) -> bool {
+        true
     }
 }
 
-/////////////////////////////////////////////////////////////////////////////////
-// Family
-
-impl<S, M, C> EncodeMetric for Family<S, M, C>
-where
-    S: Clone + std::hash::Hash + Eq + Encode,
-    M: EncodeMetric + TypedMetric,
-    C: MetricConstructor<M>,
-{
-    fn encode(&self, mut encoder: Encoder) -> Result<(), std::io::Error> {
-        let guard = self.read();
-        for (label_set, m) in guard.iter() {
-            let encoder = encoder.with_label_set(label_set);
-            m.encode(encoder)?;
+impl Encode for f64 {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        // `dtoa` follows `std::fmt` for non-finite values, e.g. `inf`, whereas
+        // Open Metrics requires `+Inf`, `-Inf` and `NaN`.
+        if self.is_nan() {
+            writer.write_all(b"NaN")?;
+        } else if self.is_infinite() {
+            if self.is_sign_positive() {
+                writer.write_all(b"+Inf")?;
+            } else {
+                writer.write_all(b"-Inf")?;
+            }
+        } else {
+            writer.write_all(dtoa::Buffer::new().format_finite(*self).as_bytes())?;
         }
         Ok(())
     }
 
-    fn metric_type(&self) -> MetricType {
-        M::TYPE
+    fn encode_value(
+        &self,
+        writer: &mut dyn Write,
+        options: &EncodeOptions,
+    ) -> Result<(), std::io::Error> {
+        if options.whole_floats_as_integers && self.is_finite() && self.fract() == 0.0 {
+            return write!(writer, "{:.0}", self);
+        }
+
+        match options.float_precision {
+            Some(precision) if self.is_finite() => write!(writer, "{:.*}", precision, self),
+            _ => self.encode(writer),
+        }
     }
-}
 
-/////////////////////////////////////////////////////////////////////////////////
-// Histogram
+    fn is_finite_value(&self) -> bool {
+        self.is_finite()
+    }
+}
 
-impl EncodeMetric for Histogram {
-    fn encode(&self, encoder: Encoder) -> Result<(), std::io::Error> {
-        let (sum, count, buckets) = self.get();
-        // TODO: Would be better to use never type instead of `()`.
-        encode_histogram_with_maybe_exemplars::<()>(sum, count, &buckets, None, encoder)
+impl Encode for u64 {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        writer.write_all(itoa::Buffer::new().format(*self).as_bytes())?;
+        Ok(())
     }
+}
 
-    fn metric_type(&self) -> MetricType {
-        Self::TYPE
+impl Encode for u128 {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        writer.write_all(itoa::Buffer::new().format(*self).as_bytes())?;
+        Ok(())
     }
 }
 
-impl<S: Encode> EncodeMetric for HistogramWithExemplars<S> {
-    fn encode(&self, encoder: Encoder) -> Result<(), std::io::Error> {
-        let inner = self.inner();
-        let (sum, count, buckets) = inner.histogram.get();
-        encode_histogram_with_maybe_exemplars(sum, count, &buckets, Some(&inner.exemplars), encoder)
+impl Encode for u32 {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        writer.write_all(itoa::Buffer::new().format(*self).as_bytes())?;
+        Ok(())
     }
+}
 
-    fn metric_type(&self) -> MetricType {
-        Histogram::TYPE
+impl<T: Encode> Encode for &[T] {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let mut iter = self.iter().peekable();
+        while let Some(x) = iter.next() {
+            x.encode(writer)?;
+
+            if iter.peek().is_some() {
+                writer.write_all(b",")?;
+            }
+        }
+
+        Ok(())
     }
 }
 
-fn encode_histogram_with_maybe_exemplars<S: Encode>(
-    sum: f64,
-    count: u64,
-    buckets: &[(f64, u64)],
-    exemplars: Option<&HashMap<usize, Exemplar<S, f64>>>,
-    mut encoder: Encoder,
+impl<T: Encode, const N: usize> Encode for [T; N] {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        self.as_slice().encode(writer)
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        self.as_slice().encode(writer)
+    }
+}
+
+impl<K: Encode, V: Encode> Encode for (K, V) {
+    /// Encodes the pair as `key="value"`, failing with
+    /// [`std::io::ErrorKind::InvalidData`] if `key` encodes to an empty string,
+    /// as Open Metrics does not allow empty label names. An empty `value` is
+    /// valid.
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        let (key, value) = self;
+
+        let mut key_writer = CountingWriter { writer, count: 0 };
+        key.encode(&mut key_writer)?;
+        if key_writer.count == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "label name must not be empty",
+            ));
+        }
+        writer.write_all(b"=\"")?;
+
+        encode_label_value(value, writer)?;
+        writer.write_all(b"\"")?;
+
+        Ok(())
+    }
+}
+
+/// Encode a label value, escaping `\`, `"` and line feeds as required by Open
+/// Metrics, e.g. for a trace id of an exemplar containing a quote.
+///
+/// Used by the [`Encode`] implementation of `(K, V)` label pairs and the
+/// derived [`Encode`] implementations. Use it in custom [`Encode`]
+/// implementations of label sets, not to break the exposition.
+///
+/// ```
+/// # use prometheus_client::encoding::text::encode_label_value;
+/// let mut buffer = vec![];
+/// encode_label_value(&"a\"b\\c", &mut buffer).unwrap();
+/// assert_eq!("a\\\"b\\\\c", String::from_utf8(buffer).unwrap());
+/// ```
+pub fn encode_label_value(
+    value: &dyn Encode,
+    writer: &mut dyn Write,
 ) -> Result<(), std::io::Error> {
-    encoder
-        .encode_suffix("sum")?
-        .no_bucket()?
-        .encode_value(sum)?
-        .no_exemplar()?;
-    encoder
-        .encode_suffix("count")?
-        .no_bucket()?
-        .encode_value(count)?
-        .no_exemplar()?;
+    value.encode(&mut EscapingWriter { writer })
+}
 
-    let mut cummulative = 0;
-    for (i, (upper_bound, count)) in buckets.iter().enumerate() {
-        cummulative += count;
-        let mut bucket_encoder = encoder.encode_suffix("bucket")?;
-        let mut value_encoder = bucket_encoder.encode_bucket(*upper_bound)?;
-        let mut exemplar_encoder = value_encoder.encode_value(cummulative)?;
+/// [`Write`] implementation escaping label values, see [`encode_label_value`].
+struct EscapingWriter<'a> {
+    writer: &'a mut dyn Write,
+}
+
+impl<'a> Write for EscapingWriter<'a> {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        let mut start = 0;
+        for (i, byte) in data.iter().enumerate() {
+            let escaped: &[u8] = match byte {
+                b'\\' => b"\\\\",
+                b'"' => b"\\\"",
+                b'\n' => b"\\n",
+                _ => continue,
+            };
+            self.writer.write_all(&data[start..i])?;
+            self.writer.write_all(escaped)?;
+            start = i + 1;
+        }
+        self.writer.write_all(&data[start..])?;
+
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// [`Write`] implementation writing `prefix` before the first non-empty write.
+struct PrefixWriter<'a> {
+    writer: &'a mut dyn Write,
+    prefix: Option<&'static [u8]>,
+}
+
+impl<'a> Write for PrefixWriter<'a> {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+        if let Some(prefix) = self.prefix.take() {
+            self.writer.write_all(prefix)?;
+        }
+        self.writer.write(data)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// [`Write`] implementation counting the bytes written to the inner writer.
+struct CountingWriter<'a> {
+    writer: &'a mut dyn Write,
+    count: usize,
+}
+
+impl<'a> Write for CountingWriter<'a> {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        let n = self.writer.write(data)?;
+        self.count += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl Encode for &str {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        // TODO: Can we do better?
+        writer.write_all(self.as_bytes())?;
+        Ok(())
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        self.as_str().encode(writer)
+    }
+}
+
+impl<'a> Encode for Cow<'a, str> {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        self.as_ref().encode(writer)
+    }
+}
+
+impl Encode for &String {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        self.as_str().encode(writer)
+    }
+}
+
+impl Encode for Box<str> {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        self.as_ref().encode(writer)
+    }
+}
+
+impl Encode for Arc<str> {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        self.as_ref().encode(writer)
+    }
+}
+
+/// Encodes the path lossily, replacing invalid UTF-8 with `U+FFFD`, see
+/// [`Path::to_string_lossy`].
+impl Encode for &Path {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        self.as_os_str().encode(writer)
+    }
+}
+
+/// Encodes the path lossily, see [`Path::to_string_lossy`].
+impl Encode for PathBuf {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        self.as_path().encode(writer)
+    }
+}
+
+/// Encodes the string lossily, replacing invalid UTF-8 with `U+FFFD`, see
+/// [`OsStr::to_string_lossy`].
+impl Encode for &OsStr {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        writer.write_all(self.to_string_lossy().as_bytes())
+    }
+}
+
+/// Encodes the string lossily, see [`OsStr::to_string_lossy`].
+impl Encode for OsString {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        self.as_os_str().encode(writer)
+    }
+}
+
+impl Encode for MetricType {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        writer.write_all(self.as_str().as_bytes())?;
+        Ok(())
+    }
+}
+
+impl Encode for Unit {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        let u = match self {
+            Unit::Amperes => "amperes",
+            Unit::Bytes => "bytes",
+            Unit::Celsius => "celsius",
+            Unit::Grams => "grams",
+            Unit::Joules => "joules",
+            Unit::Meters => "meters",
+            Unit::Ratios => "ratios",
+            Unit::Seconds => "seconds",
+            Unit::Volts => "volts",
+            Unit::Other(other) => other.as_str(),
+        };
+
+        writer.write_all(u.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Encodes nothing for `None`, e.g. to make a whole label set optional.
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        match self {
+            Some(t) => t.encode(writer),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Encode for () {
+    fn encode(&self, _writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+}
+
+/// Label set encoding the labels of `A` followed by the labels of `B`, e.g. to
+/// combine a per-request with a per-connection label set without defining a
+/// merged type.
+///
+/// Label names present in both `A` and `B` are not detected, they are encoded
+/// twice, which Open Metrics does not allow. Make sure the two sides are
+/// disjoint.
+///
+/// ```
+/// # use prometheus_client::encoding::text::{encode_label_set, Concat};
+/// # use prometheus_client::metrics::counter::Counter;
+/// # use prometheus_client::metrics::family::Family;
+/// #
+/// let connection = vec![("peer", "10.0.0.1")];
+/// let family = Family::<Concat<[(&str, &str); 1], Vec<(&str, &str)>>, Counter>::default();
+/// family.get_or_create(&Concat([("method", "GET")], connection.clone())).inc();
+///
+/// assert_eq!(
+///     "{method=\"GET\",peer=\"10.0.0.1\"}",
+///     encode_label_set(&Concat([("method", "GET")], connection)).unwrap(),
+/// );
+/// ```
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct Concat<A, B>(pub A, pub B);
+
+impl<A: Encode, B: Encode> Encode for Concat<A, B> {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        let mut first_writer = CountingWriter { writer, count: 0 };
+        self.0.encode(&mut first_writer)?;
+
+        let prefix: &'static [u8] = b",";
+        let mut second_writer = PrefixWriter {
+            prefix: (first_writer.count > 0).then_some(prefix),
+            writer,
+        };
+        self.1.encode(&mut second_writer)
+    }
+}
+
+/// Label set of up to `N` labels stored inline, spilling to the heap only
+/// beyond `N` labels, e.g. as the key of a [`Family`] with a dynamic yet
+/// small label set, without a heap allocation per lookup as with a [`Vec`].
+///
+/// Labels default to pairs of [`Cow<'static, str>`](Cow), thus static label
+/// names and values do not allocate either.
+///
+/// ```
+/// # use prometheus_client::encoding::text::{encode_label_set, SmallLabelSet};
+/// # use prometheus_client::metrics::counter::Counter;
+/// # use prometheus_client::metrics::family::Family;
+/// # use std::borrow::Cow;
+/// #
+/// let family = Family::<SmallLabelSet<2>, Counter>::default();
+/// let mut label_set = SmallLabelSet::new();
+/// label_set.push((Cow::Borrowed("method"), Cow::Borrowed("GET")));
+/// label_set.push((Cow::Borrowed("status"), Cow::Owned(200.to_string())));
+/// family.get_or_create(&label_set).inc();
+///
+/// assert!(!label_set.spilled());
+/// assert_eq!(
+///     "{method=\"GET\",status=\"200\"}",
+///     encode_label_set(&label_set).unwrap(),
+/// );
+/// ```
+#[cfg(feature = "smallvec")]
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct SmallLabelSet<const N: usize, T = (Cow<'static, str>, Cow<'static, str>)>(
+    SmallVec<[T; N]>,
+);
+
+#[cfg(feature = "smallvec")]
+impl<const N: usize, T> SmallLabelSet<N, T> {
+    /// Create an empty label set, not allocating until more than `N` labels
+    /// are pushed.
+    pub fn new() -> Self {
+        SmallLabelSet(SmallVec::new())
+    }
+
+    /// Append a label, moving all labels to the heap if there are more than
+    /// `N`.
+    pub fn push(&mut self, label: T) {
+        self.0.push(label)
+    }
+
+    /// Whether the labels have been moved to the heap.
+    pub fn spilled(&self) -> bool {
+        self.0.spilled()
+    }
+
+    /// The labels in the order they have been pushed.
+    pub fn as_slice(&self) -> &[T] {
+        &self.0
+    }
+}
+
+#[cfg(feature = "smallvec")]
+impl<const N: usize, T> FromIterator<T> for SmallLabelSet<N, T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        SmallLabelSet(iter.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "smallvec")]
+impl<const N: usize, T: Encode> Encode for SmallLabelSet<N, T> {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        self.as_slice().encode(writer)
+    }
+}
+
+/// Label value truncated to at most `N` characters, e.g. to bound the size of
+/// series labeled with untrusted input like a user agent.
+///
+/// The value is truncated on construction, not when encoding, thus a
+/// [`Family`] keyed by [`Truncated`] values neither stores the full values nor
+/// creates distinct series for values differing only after the first `N`
+/// characters. Truncation never splits a multi-byte character.
+///
+/// ```
+/// # use prometheus_client::encoding::text::Truncated;
+/// assert_eq!("curl", Truncated::<4>::new("curl/7.79.1").as_str());
+/// assert_eq!("cur…", Truncated::<4>::with_ellipsis("curl/7.79.1").as_str());
+/// assert_eq!("curl", Truncated::<4>::with_ellipsis("curl").as_str());
+/// ```
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Truncated<const N: usize>(String);
+
+impl<const N: usize> Truncated<N> {
+    /// Truncate `value` to its first `N` characters.
+    pub fn new(value: &str) -> Self {
+        Truncated(value.chars().take(N).collect())
+    }
+
+    /// Like [`Truncated::new`], but marks a truncated value by replacing its
+    /// last character with `…`, keeping it at most `N` characters.
+    pub fn with_ellipsis(value: &str) -> Self {
+        if N == 0 || value.chars().nth(N).is_none() {
+            return Self::new(value);
+        }
+
+        let mut truncated: String = value.chars().take(N - 1).collect();
+        truncated.push('…');
+        Truncated(truncated)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<const N: usize> Encode for Truncated<N> {
+    fn encode(&self, writer: &mut dyn Write) -> Result<(), std::io::Error> {
+        self.0.encode(writer)
+    }
+}
+
+/// Helper type for [`EncodeMetric`], see [`EncodeMetric::encode`].
+///
+// `Encoder` does not take a trait parameter for `writer` and `labels` because
+// `EncodeMetric` which uses `Encoder` needs to be usable as a trait object in
+// order to be able to register different metric types with a `Registry`. Trait
+// objects can not use type parameters.
+//
+// TODO: Alternative solutions to the above are very much appreciated.
+#[allow(missing_debug_implementations)]
+pub struct Encoder<'a, 'b> {
+    writer: &'a mut dyn Write,
+    name: &'a str,
+    unit: &'a Option<Unit>,
+    const_labels: &'a [(Cow<'static, str>, Cow<'static, str>)],
+    labels: Option<&'b dyn Encode>,
+    type_suffix: bool,
+    reset_on_read: bool,
+    options: &'a EncodeOptions,
+}
+
+impl<'a, 'b> Encoder<'a, 'b> {
+    /// Encode a metric suffix, e.g. in the case of [`Counter`] the suffic `_total`.
+    pub fn encode_suffix(&mut self, suffix: &'static str) -> Result<BucketEncoder, std::io::Error> {
+        self.write_name_and_unit()?;
+
+        self.writer.write_all(b"_")?;
+        self.writer.write_all(suffix.as_bytes()).map(|_| ())?;
+
+        self.encode_labels()
+    }
+
+    /// Encode the suffix of the given [`MetricType`], if any, see
+    /// [`MetricType::suffix`].
+    pub fn encode_type_suffix(
+        &mut self,
+        metric_type: MetricType,
+    ) -> Result<BucketEncoder, std::io::Error> {
+        match metric_type.suffix().filter(|_| self.type_suffix) {
+            Some(suffix) => self.encode_suffix(suffix),
+            None => self.no_suffix(),
+        }
+    }
+
+    /// Signal that the metric has no suffix.
+    pub fn no_suffix(&mut self) -> Result<BucketEncoder, std::io::Error> {
+        self.write_name_and_unit()?;
+
+        self.encode_labels()
+    }
+
+    fn write_name_and_unit(&mut self) -> Result<(), std::io::Error> {
+        self.writer.write_all(self.name.as_bytes())?;
+        if let Some(unit) = self.unit {
+            self.writer.write_all(b"_")?;
+            unit.encode(self.writer)?;
+        }
+
+        Ok(())
+    }
+
+    // TODO: Consider caching the encoded labels for Histograms as they stay the
+    // same but are currently encoded multiple times.
+    fn encode_labels(&mut self) -> Result<BucketEncoder, std::io::Error> {
+        let mut opened_curly_brackets = false;
+
+        if !self.const_labels.is_empty() {
+            self.writer.write_all(b"{")?;
+            opened_curly_brackets = true;
+
+            self.const_labels.encode(self.writer)?;
+        }
+
+        if let Some(labels) = &self.labels {
+            // Only open the curly brackets, or separate from the constant
+            // labels, if the label set is not empty, e.g. not `None`.
+            let mut writer = PrefixWriter {
+                writer: self.writer,
+                prefix: Some(if opened_curly_brackets { b"," } else { b"{" }),
+            };
+            labels.encode(&mut writer)?;
+            opened_curly_brackets |= writer.prefix.is_none();
+        }
+
+        Ok(BucketEncoder {
+            opened_curly_brackets,
+            writer: self.writer,
+            options: self.options,
+        })
+    }
+
+    /// Encode a set of labels. Used by wrapper metric types like [`Family`].
+    pub fn with_label_set<'c, 'd>(&'c mut self, label_set: &'d dyn Encode) -> Encoder<'c, 'd> {
+        debug_assert!(self.labels.is_none());
+
+        Encoder {
+            writer: self.writer,
+            name: self.name,
+            unit: self.unit,
+            const_labels: self.const_labels,
+            labels: Some(label_set),
+            type_suffix: self.type_suffix,
+            reset_on_read: self.reset_on_read,
+            options: self.options,
+        }
+    }
+
+    /// Whether histograms in reset-on-read mode are to be reset, see
+    /// [`Histogram::with_reset_on_read`].
+    pub(crate) fn reset_on_read(&self) -> bool {
+        self.reset_on_read
+    }
+}
+
+#[allow(missing_debug_implementations)]
+#[must_use]
+pub struct BucketEncoder<'a> {
+    writer: &'a mut dyn Write,
+    opened_curly_brackets: bool,
+    options: &'a EncodeOptions,
+}
+
+impl<'a> BucketEncoder<'a> {
+    /// Encode a bucket. Used for the [`Histogram`] metric type.
+    pub fn encode_bucket(&mut self, upper_bound: f64) -> Result<ValueEncoder, std::io::Error> {
+        if self.opened_curly_brackets {
+            self.writer.write_all(b",")?;
+        } else {
+            self.writer.write_all(b"{")?;
+        }
+
+        self.writer.write_all(b"le=\"")?;
+        if upper_bound == f64::MAX {
+            self.writer.write_all(b"+Inf")?;
+        } else {
+            upper_bound.encode(self.writer)?;
+        }
+        self.writer.write_all(b"\"}")?;
+
+        Ok(ValueEncoder {
+            writer: self.writer,
+            options: self.options,
+        })
+    }
+
+    /// Encode a quantile. Used for the summary metric type.
+    pub fn encode_quantile(&mut self, quantile: f64) -> Result<ValueEncoder, std::io::Error> {
+        if self.opened_curly_brackets {
+            self.writer.write_all(b",")?;
+        } else {
+            self.writer.write_all(b"{")?;
+        }
+
+        self.writer.write_all(b"quantile=\"")?;
+        quantile.encode(self.writer)?;
+        self.writer.write_all(b"\"}")?;
+
+        Ok(ValueEncoder {
+            writer: self.writer,
+            options: self.options,
+        })
+    }
+
+    /// Signal that the metric type has no bucket.
+    pub fn no_bucket(&mut self) -> Result<ValueEncoder, std::io::Error> {
+        if self.opened_curly_brackets {
+            self.writer.write_all(b"}")?;
+        }
+        Ok(ValueEncoder {
+            writer: self.writer,
+            options: self.options,
+        })
+    }
+}
+
+#[allow(missing_debug_implementations)]
+#[must_use]
+pub struct ValueEncoder<'a> {
+    writer: &'a mut dyn Write,
+    options: &'a EncodeOptions,
+}
+
+impl<'a> ValueEncoder<'a> {
+    /// Encode the metric value. E.g. in the case of [`Counter`] the
+    /// monotonically increasing counter value.
+    pub fn encode_value<V: Encode>(&mut self, v: V) -> Result<ExemplarEncoder, std::io::Error> {
+        self.writer.write_all(b" ")?;
+        v.encode_value(self.writer, self.options)?;
+        Ok(ExemplarEncoder {
+            writer: self.writer,
+            options: self.options,
+        })
+    }
+}
+
+#[allow(missing_debug_implementations)]
+#[must_use]
+pub struct ExemplarEncoder<'a> {
+    writer: &'a mut dyn Write,
+    options: &'a EncodeOptions,
+}
+
+impl<'a> ExemplarEncoder<'a> {
+    /// Encode an exemplar for the given metric.
+    ///
+    /// Open Metrics does not allow `NaN` or infinite exemplar values, thus an
+    /// exemplar with such a value is skipped instead of failing the whole
+    /// scrape.
+    pub fn encode_exemplar<S: Encode, V: Encode>(
+        &mut self,
+        exemplar: &Exemplar<S, V>,
+    ) -> Result<(), std::io::Error> {
+        if self.options.format == Format::PrometheusText
+            || self.options.skip_exemplars
+            || !exemplar.value.is_finite_value()
+        {
+            return self.no_exemplar();
+        }
+
+        self.writer.write_all(b" # {")?;
+        exemplar.label_set.encode(self.writer)?;
+        self.writer.write_all(b"} ")?;
+        exemplar.value.encode(self.writer)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Signal that the metric type has no exemplar.
+    pub fn no_exemplar(&mut self) -> Result<(), std::io::Error> {
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+}
+
+/// Trait implemented by each metric type, e.g. [`Counter`], to implement its encoding.
+pub trait EncodeMetric {
+    fn encode(&self, encoder: Encoder) -> Result<(), std::io::Error>;
+
+    // One can not use [`TypedMetric`] directly, as associated constants are not
+    // object safe and thus can not be used with dynamic dispatching.
+    fn metric_type(&self) -> MetricType;
+
+    /// The number of series, i.e. label sets, the metric currently consists
+    /// of, see [`Registry::cardinality_report`].
+    ///
+    /// Defaults to 1, for metrics without labels of their own.
+    fn series_count(&self) -> usize {
+        1
+    }
+
+    /// Whether the metric is encoded as a summary, with quantiles derived from
+    /// its buckets, given [`EncodeOptions::histograms_as_summaries`], thus
+    /// declared as `summary` instead of its [`EncodeMetric::metric_type`].
+    ///
+    /// Defaults to `false`, e.g. for custom histograms emitting their buckets
+    /// regardless. The built-in histograms return `true`.
+    fn encodes_as_summary(&self) -> bool {
+        false
+    }
+}
+
+impl EncodeMetric for Box<dyn EncodeMetric> {
+    fn encode(&self, encoder: Encoder) -> Result<(), std::io::Error> {
+        self.deref().encode(encoder)
+    }
+
+    fn metric_type(&self) -> MetricType {
+        self.deref().metric_type()
+    }
+
+    fn series_count(&self) -> usize {
+        self.deref().series_count()
+    }
+
+    fn encodes_as_summary(&self) -> bool {
+        self.deref().encodes_as_summary()
+    }
+}
+
+pub trait SendSyncEncodeMetric: EncodeMetric + Send + Sync {}
+
+impl<T: EncodeMetric + Send + Sync> SendSyncEncodeMetric for T {}
+
+impl EncodeMetric for Box<dyn SendSyncEncodeMetric> {
+    fn encode(&self, encoder: Encoder) -> Result<(), std::io::Error> {
+        self.deref().encode(encoder)
+    }
+
+    fn metric_type(&self) -> MetricType {
+        self.deref().metric_type()
+    }
+
+    fn series_count(&self) -> usize {
+        self.deref().series_count()
+    }
+
+    fn encodes_as_summary(&self) -> bool {
+        self.deref().encodes_as_summary()
+    }
+}
+
+impl<M: EncodeMetric + ?Sized> EncodeMetric for Arc<M> {
+    fn encode(&self, encoder: Encoder) -> Result<(), std::io::Error> {
+        self.deref().encode(encoder)
+    }
+
+    fn metric_type(&self) -> MetricType {
+        self.deref().metric_type()
+    }
+
+    fn series_count(&self) -> usize {
+        self.deref().series_count()
+    }
+
+    fn encodes_as_summary(&self) -> bool {
+        self.deref().encodes_as_summary()
+    }
+}
+
+impl<M: EncodeMetric + ?Sized> EncodeMetric for Rc<M> {
+    fn encode(&self, encoder: Encoder) -> Result<(), std::io::Error> {
+        self.deref().encode(encoder)
+    }
+
+    fn metric_type(&self) -> MetricType {
+        self.deref().metric_type()
+    }
+
+    fn series_count(&self) -> usize {
+        self.deref().series_count()
+    }
+
+    fn encodes_as_summary(&self) -> bool {
+        self.deref().encodes_as_summary()
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+// Counter
+
+impl<N, A> EncodeMetric for Counter<N, A>
+where
+    N: Encode,
+    A: counter::Atomic<N>,
+{
+    fn encode(&self, encoder: Encoder) -> Result<(), std::io::Error> {
+        // TODO: Would be better to use never type instead of `()`.
+        encode_counter_with_maybe_exemplar::<(), _>(self.get(), None, encoder)
+    }
+
+    fn metric_type(&self) -> MetricType {
+        Self::TYPE
+    }
+}
+
+impl<S, N, A> EncodeMetric for LabeledCounter<S, N, A>
+where
+    S: Encode,
+    N: Encode,
+    A: counter::Atomic<N>,
+{
+    fn encode(&self, mut encoder: Encoder) -> Result<(), std::io::Error> {
+        self.counter()
+            .encode(encoder.with_label_set(self.label_set()))
+    }
+
+    fn metric_type(&self) -> MetricType {
+        Self::TYPE
+    }
+}
+
+// TODO: S, V, N, A are hard to grasp.
+impl<S, N, A> EncodeMetric for CounterWithExemplar<S, N, A>
+where
+    S: Encode,
+    N: Encode + Clone,
+    A: counter::Atomic<N>,
+{
+    fn encode(&self, encoder: Encoder) -> Result<(), std::io::Error> {
+        let (value, exemplar) = self.get();
+        encode_counter_with_maybe_exemplar(value, exemplar.as_ref().as_ref(), encoder)
+    }
+
+    fn metric_type(&self) -> MetricType {
+        Counter::<N, A>::TYPE
+    }
+}
+
+fn encode_counter_with_maybe_exemplar<S, N>(
+    value: N,
+    exemplar: Option<&Exemplar<S, N>>,
+    mut encoder: Encoder,
+) -> Result<(), std::io::Error>
+where
+    S: Encode,
+    N: Encode,
+{
+    let mut bucket_encoder = encoder.encode_type_suffix(MetricType::Counter)?;
+    let mut value_encoder = bucket_encoder.no_bucket()?;
+    let mut exemplar_encoder = value_encoder.encode_value(value)?;
+
+    match exemplar {
+        Some(exemplar) => exemplar_encoder.encode_exemplar(exemplar)?,
+        None => exemplar_encoder.no_exemplar()?,
+    }
+
+    Ok(())
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+// Gauge
+
+impl<N, A> EncodeMetric for Gauge<N, A>
+where
+    N: Encode,
+    A: gauge::Atomic<N>,
+{
+    fn encode(&self, mut encoder: Encoder) -> Result<(), std::io::Error> {
+        encoder
+            .encode_type_suffix(Self::TYPE)?
+            .no_bucket()?
+            .encode_value(self.get())?
+            .no_exemplar()?;
+
+        Ok(())
+    }
+    fn metric_type(&self) -> MetricType {
+        Self::TYPE
+    }
+}
+
+impl<S, N, A> EncodeMetric for LabeledGauge<S, N, A>
+where
+    S: Encode,
+    N: Encode,
+    A: gauge::Atomic<N>,
+{
+    fn encode(&self, mut encoder: Encoder) -> Result<(), std::io::Error> {
+        self.gauge()
+            .encode(encoder.with_label_set(self.label_set()))
+    }
+
+    fn metric_type(&self) -> MetricType {
+        Self::TYPE
+    }
+}
+
+impl<S, N, A, C> EncodeMetric for SumGauge<S, N, A, C>
+where
+    S: Clone + std::hash::Hash + Eq,
+    N: Encode + std::iter::Sum,
+    A: gauge::Atomic<N>,
+    C: LabeledMetricConstructor<S, Gauge<N, A>>,
+{
+    fn encode(&self, mut encoder: Encoder) -> Result<(), std::io::Error> {
+        encoder
+            .encode_type_suffix(Self::TYPE)?
+            .no_bucket()?
+            .encode_value(self.get())?
+            .no_exemplar()?;
+
+        Ok(())
+    }
+    fn metric_type(&self) -> MetricType {
+        Self::TYPE
+    }
+}
+
+impl<N> EncodeMetric for GaugeWithMax<N>
+where
+    N: Encode + Copy + PartialOrd + From<u8> + Add<Output = N> + Sub<Output = N>,
+{
+    fn encode(&self, mut encoder: Encoder) -> Result<(), std::io::Error> {
+        encoder
+            .encode_type_suffix(Self::TYPE)?
+            .no_bucket()?
+            .encode_value(self.get())?
+            .no_exemplar()?;
+
+        Ok(())
+    }
+    fn metric_type(&self) -> MetricType {
+        Self::TYPE
+    }
+}
+
+impl<N> EncodeMetric for GaugeMax<N>
+where
+    N: Encode + Copy + PartialOrd + From<u8> + Add<Output = N> + Sub<Output = N>,
+{
+    fn encode(&self, mut encoder: Encoder) -> Result<(), std::io::Error> {
+        encoder
+            .encode_type_suffix(Self::TYPE)?
+            .no_bucket()?
+            .encode_value(self.get())?
+            .no_exemplar()?;
+
+        Ok(())
+    }
+    fn metric_type(&self) -> MetricType {
+        Self::TYPE
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+// Family
+
+impl<S, M, C> EncodeMetric for Family<S, M, C>
+where
+    S: Clone + std::hash::Hash + Eq + Encode,
+    M: EncodeMetric + TypedMetric,
+    C: LabeledMetricConstructor<S, M>,
+{
+    fn encode(&self, mut encoder: Encoder) -> Result<(), std::io::Error> {
+        let guard = self.read();
+        for (label_set, m) in guard.iter() {
+            let encoder = encoder.with_label_set(label_set);
+            m.encode(encoder)?;
+        }
+        Ok(())
+    }
+
+    fn metric_type(&self) -> MetricType {
+        M::TYPE
+    }
+
+    fn series_count(&self) -> usize {
+        self.read().values().map(EncodeMetric::series_count).sum()
+    }
+
+    fn encodes_as_summary(&self) -> bool {
+        M::ENCODES_AS_SUMMARY
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+// Histogram
+
+impl EncodeMetric for Histogram {
+    fn encode(&self, encoder: Encoder) -> Result<(), std::io::Error> {
+        if let Some((sum, count, buckets)) = encoder
+            .reset_on_read()
+            .then(|| self.take_if_reset_on_read())
+            .flatten()
+        {
+            return encode_histogram_with_maybe_exemplars::<()>(
+                sum, count, &buckets, None, encoder,
+            );
+        }
+
+        let (sum, count, buckets) = self.get();
+        // TODO: Would be better to use never type instead of `()`.
+        encode_histogram_with_maybe_exemplars::<()>(sum, count, &buckets, None, encoder)
+    }
+
+    fn metric_type(&self) -> MetricType {
+        Self::TYPE
+    }
+
+    fn encodes_as_summary(&self) -> bool {
+        Self::ENCODES_AS_SUMMARY
+    }
+}
+
+impl EncodeMetric for ConstHistogram {
+    fn encode(&self, encoder: Encoder) -> Result<(), std::io::Error> {
+        let (sum, count, buckets) = self.get();
+        encode_histogram_with_maybe_exemplars::<()>(sum, count, buckets, None, encoder)
+    }
+
+    fn metric_type(&self) -> MetricType {
+        Self::TYPE
+    }
+
+    fn encodes_as_summary(&self) -> bool {
+        Self::ENCODES_AS_SUMMARY
+    }
+}
+
+impl EncodeMetric for IntHistogram {
+    fn encode(&self, encoder: Encoder) -> Result<(), std::io::Error> {
+        let (sum, count, buckets) = self.get();
+        encode_histogram_with_maybe_exemplars::<()>(sum, count, &buckets, None, encoder)
+    }
+
+    fn metric_type(&self) -> MetricType {
+        Self::TYPE
+    }
+
+    fn encodes_as_summary(&self) -> bool {
+        Self::ENCODES_AS_SUMMARY
+    }
+}
+
+impl<S: Encode> EncodeMetric for HistogramWithExemplars<S> {
+    fn encode(&self, encoder: Encoder) -> Result<(), std::io::Error> {
+        let inner = self.inner();
+        let (sum, count, buckets) = inner.histogram.get();
+        encode_histogram_with_maybe_exemplars(sum, count, &buckets, Some(&inner.exemplars), encoder)
+    }
+
+    fn metric_type(&self) -> MetricType {
+        Histogram::TYPE
+    }
+
+    fn encodes_as_summary(&self) -> bool {
+        true
+    }
+}
+
+fn encode_histogram_with_maybe_exemplars<S: Encode>(
+    sum: impl Encode,
+    count: u64,
+    buckets: &[(f64, u64)],
+    exemplars: Option<&HashMap<usize, Exemplar<S, f64>>>,
+    mut encoder: Encoder,
+) -> Result<(), std::io::Error> {
+    // Exemplars are keyed by the index of their bucket, the last one being the
+    // `+Inf` bucket. Exemplars with any other key would silently be dropped.
+    debug_assert!(
+        exemplars
+            .into_iter()
+            .flat_map(HashMap::keys)
+            .all(|i| *i < buckets.len()),
+        "Exemplar bucket index out of range, expected index < {}.",
+        buckets.len()
+    );
+    // Summing the per-bucket counts yields non-decreasing cumulative counts,
+    // unless they overflow. The last, `+Inf`, bucket has to equal `count`, a
+    // mismatch hinting at e.g. a reset racing with an observation.
+    debug_assert_eq!(
+        buckets
+            .iter()
+            .try_fold(0u64, |cumulative, (_, count)| cumulative
+                .checked_add(*count)),
+        Some(count),
+        "Histogram bucket counts to be cumulative with the `+Inf` bucket equal to count."
+    );
+
+    if let Some(quantiles) = &encoder.options.summary_quantiles {
+        return encode_histogram_as_summary(sum, count, buckets, quantiles, encoder);
+    }
+
+    encoder
+        .encode_suffix("sum")?
+        .no_bucket()?
+        .encode_value(sum)?
+        .no_exemplar()?;
+    encoder
+        .encode_suffix("count")?
+        .no_bucket()?
+        .encode_value(count)?
+        .no_exemplar()?;
+
+    let mut cumulative = 0;
+    for (i, (upper_bound, count)) in buckets.iter().enumerate() {
+        cumulative += count;
+        let mut bucket_encoder = encoder.encode_suffix("bucket")?;
+        let mut value_encoder = bucket_encoder.encode_bucket(*upper_bound)?;
+        let mut exemplar_encoder = value_encoder.encode_value(cumulative)?;
+
+        match exemplars.and_then(|es| es.get(&i)) {
+            Some(exemplar) => exemplar_encoder.encode_exemplar(exemplar)?,
+            None => exemplar_encoder.no_exemplar()?,
+        }
+    }
+
+    Ok(())
+}
+
+fn encode_histogram_as_summary(
+    sum: impl Encode,
+    count: u64,
+    buckets: &[(f64, u64)],
+    quantiles: &[f64],
+    mut encoder: Encoder,
+) -> Result<(), std::io::Error> {
+    encoder
+        .encode_suffix("sum")?
+        .no_bucket()?
+        .encode_value(sum)?
+        .no_exemplar()?;
+    encoder
+        .encode_suffix("count")?
+        .no_bucket()?
+        .encode_value(count)?
+        .no_exemplar()?;
+
+    for quantile in quantiles {
+        encoder
+            .no_suffix()?
+            .encode_quantile(*quantile)?
+            .encode_value(quantile_from_buckets(*quantile, count, buckets))?
+            .no_exemplar()?;
+    }
+
+    Ok(())
+}
+
+/// Estimates the given quantile from non-cumulative bucket counts, see
+/// [`EncodeOptions::histograms_as_summaries`] for the limitations.
+fn quantile_from_buckets(quantile: f64, count: u64, buckets: &[(f64, u64)]) -> f64 {
+    if count == 0 {
+        return f64::NAN;
+    }
+
+    let rank = quantile * count as f64;
+    let mut cumulative = 0;
+    for (i, (upper_bound, bucket_count)) in buckets.iter().enumerate() {
+        let previous = cumulative;
+        cumulative += bucket_count;
+        if (cumulative as f64) < rank || *bucket_count == 0 {
+            continue;
+        }
+
+        // The last bucket is the `+Inf` bucket.
+        if i == buckets.len() - 1 {
+            return i.checked_sub(1).map(|i| buckets[i].0).unwrap_or(f64::NAN);
+        }
+
+        let lower_bound = match i {
+            0 if *upper_bound > 0.0 => 0.0,
+            0 => return *upper_bound,
+            _ => buckets[i - 1].0,
+        };
+
+        return lower_bound
+            + (upper_bound - lower_bound) * (rank - previous as f64) / *bucket_count as f64;
+    }
+
+    buckets
+        .iter()
+        .rev()
+        .nth(1)
+        .map(|(upper_bound, _)| *upper_bound)
+        .unwrap_or(f64::NAN)
+}
+
+/////////////////////////////////////////////////////////////////////////////////
+// Info
+
+impl<S> EncodeMetric for Info<S>
+where
+    S: Clone + std::hash::Hash + Eq + Encode,
+{
+    fn encode(&self, mut encoder: Encoder) -> Result<(), std::io::Error> {
+        encoder
+            .with_label_set(&self.0)
+            .encode_type_suffix(Self::TYPE)?
+            .no_bucket()?
+            .encode_value(1u32)?
+            .no_exemplar()?;
+
+        Ok(())
+    }
+
+    fn metric_type(&self) -> MetricType {
+        Self::TYPE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::counter::{Counter, LockedU128};
+    use crate::metrics::gauge::Gauge;
+    use crate::metrics::histogram::exponential_buckets;
+    use pyo3::{prelude::*, types::PyModule};
+    use std::borrow::Cow;
+    use std::sync::atomic::AtomicU64;
+
+    #[test]
+    fn encode_counter() {
+        let counter: Counter = Counter::default();
+        let mut registry = Registry::default();
+        registry.register("my_counter", "My counter", counter);
+
+        let mut encoded = Vec::new();
+
+        encode(&mut encoded, &registry).unwrap();
+
+        parse_with_python_client(String::from_utf8(encoded).unwrap());
+    }
+
+    #[test]
+    fn encode_counter_with_unit() {
+        let mut registry = Registry::default();
+        let counter: Counter = Counter::default();
+        registry.register_with_unit("my_counter", "My counter", Unit::Seconds, counter);
+
+        let mut encoded = Vec::new();
+        encode(&mut encoded, &registry).unwrap();
+
+        let expected = "# HELP my_counter_seconds My counter.\n".to_owned()
+            + "# TYPE my_counter_seconds counter\n"
+            + "# UNIT my_counter_seconds seconds\n"
+            + "my_counter_seconds_total 0\n"
+            + "# EOF\n";
+        assert_eq!(expected, String::from_utf8(encoded.clone()).unwrap());
+
+        parse_with_python_client(String::from_utf8(encoded).unwrap());
+    }
+
+    #[test]
+    fn encode_counter_with_exemplar() {
+        let mut registry = Registry::default();
+
+        let counter_with_exemplar: CounterWithExemplar<(String, u64)> =
+            CounterWithExemplar::default();
+        registry.register_with_unit(
+            "my_counter_with_exemplar",
+            "My counter with exemplar",
+            Unit::Seconds,
+            counter_with_exemplar.clone(),
+        );
+
+        counter_with_exemplar.inc_by(1, Some(("user_id".to_string(), 42)));
+
+        let mut encoded = Vec::new();
+        encode(&mut encoded, &registry).unwrap();
+
+        let expected = "# HELP my_counter_with_exemplar_seconds My counter with exemplar.\n"
+            .to_owned()
+            + "# TYPE my_counter_with_exemplar_seconds counter\n"
+            + "# UNIT my_counter_with_exemplar_seconds seconds\n"
+            + "my_counter_with_exemplar_seconds_total 1 # {user_id=\"42\"} 1\n"
+            + "# EOF\n";
+        assert_eq!(expected, String::from_utf8(encoded.clone()).unwrap());
+
+        parse_with_python_client(String::from_utf8(encoded).unwrap());
+    }
+
+    #[test]
+    fn encode_counter_with_non_finite_exemplar() {
+        for value in [f64::NAN, f64::INFINITY] {
+            let mut registry = Registry::default();
+            let counter: CounterWithExemplar<Vec<(String, String)>, f64, AtomicU64> =
+                CounterWithExemplar::default();
+            registry.register("my_counter", "My counter", counter.clone());
+
+            counter.inc_by(value, Some(vec![("trace_id".to_string(), "1".to_string())]));
+
+            let mut encoded = Vec::new();
+            encode(&mut encoded, &registry).unwrap();
+            let encoded = String::from_utf8(encoded).unwrap();
+            let sample = encoded
+                .lines()
+                .find(|l| l.starts_with("my_counter_total"))
+                .unwrap();
+            assert!(!sample.contains('#'), "{}", sample);
+        }
+    }
+
+    #[test]
+    fn encode_exemplar_with_escaped_label_value() {
+        let mut registry = Registry::default();
+        let counter: CounterWithExemplar<Vec<(String, String)>> = CounterWithExemplar::default();
+        registry.register("my_counter", "My counter", counter.clone());
+
+        let trace_id = "a\"b\\c\nd".to_string();
+        counter.inc_by(1, Some(vec![("trace_id".to_string(), trace_id.clone())]));
+
+        let mut encoded = Vec::new();
+        encode(&mut encoded, &registry).unwrap();
+        let encoded = String::from_utf8(encoded).unwrap();
+
+        assert!(encoded.contains("my_counter_total 1 # {trace_id=\"a\\\"b\\\\c\\nd\"} 1\n"));
+        let families = parse(&encoded).unwrap();
+        let exemplar = families[0].samples[0].exemplar.as_ref().unwrap();
+        assert_eq!(vec![("trace_id".to_string(), trace_id)], exemplar.labels);
+    }
+
+    #[test]
+    fn encode_without_exemplars() {
+        let counter: CounterWithExemplar<(String, u64)> = CounterWithExemplar::default();
+        let histogram = HistogramWithExemplars::new(exponential_buckets(1.0, 2.0, 2));
+        counter.inc_by(1, Some(("user_id".to_string(), 42)));
+        histogram.observe(1.0, Some(("user_id".to_string(), 42u64)));
+
+        let encode_registry = |exemplars: bool| {
+            let mut registry: Registry = Registry::builder().with_exemplars(exemplars).build();
+            registry.register("my_counter", "My counter", Box::new(counter.clone()));
+            registry.register("my_histogram", "My histogram", Box::new(histogram.clone()));
+            let mut encoded = Vec::new();
+            encode(&mut encoded, &registry).unwrap();
+            String::from_utf8(encoded).unwrap()
+        };
+
+        let with_exemplars = encode_registry(true);
+        assert!(with_exemplars.contains("my_counter_total 1 # {user_id=\"42\"} 1\n"));
+        assert!(
+            with_exemplars.contains("my_histogram_bucket{le=\"1.0\"} 1 # {user_id=\"42\"} 1.0\n")
+        );
+
+        let without_exemplars = encode_registry(false);
+        assert!(!without_exemplars.contains("user_id"));
+        assert!(without_exemplars.contains("my_counter_total 1\n"));
+        assert!(without_exemplars.contains("my_histogram_bucket{le=\"1.0\"} 1\n"));
+
+        parse_with_python_client(without_exemplars);
+    }
+
+    #[test]
+    fn encode_u128_counter() {
+        let mut registry = Registry::default();
+        let counter = Counter::<u128, LockedU128>::default();
+        registry.register("my_counter", "My counter", counter.clone());
+        counter.inc_by(u128::MAX);
+
+        let mut encoded = Vec::new();
+        encode(&mut encoded, &registry).unwrap();
+
+        let expected = "# HELP my_counter My counter.\n".to_owned()
+            + "# TYPE my_counter counter\n"
+            + "my_counter_total 340282366920938463463374607431768211455\n"
+            + "# EOF\n";
+        assert_eq!(expected, String::from_utf8(encoded).unwrap());
+    }
+
+    #[test]
+    fn encode_gauge() {
+        let mut registry = Registry::default();
+        let gauge: Gauge = Gauge::default();
+        registry.register("my_gauge", "My gauge", gauge);
+
+        let mut encoded = Vec::new();
+
+        encode(&mut encoded, &registry).unwrap();
+
+        parse_with_python_client(String::from_utf8(encoded).unwrap());
+    }
+
+    #[test]
+    fn encode_arc_and_rc() {
+        let counter: Arc<Counter> = Arc::new(Counter::default());
+        let mut registry = Registry::default();
+        registry.register("my_counter", "My counter", counter.clone());
+        counter.inc();
+
+        let mut encoded = Vec::new();
+        encode(&mut encoded, &registry).unwrap();
+
+        let expected = "# HELP my_counter My counter.\n".to_owned()
+            + "# TYPE my_counter counter\n"
+            + "my_counter_total 1\n"
+            + "# EOF\n";
+        assert_eq!(expected, String::from_utf8(encoded).unwrap());
+
+        let gauge: Rc<Gauge> = Rc::new(Gauge::default());
+        let mut registry: Registry<Rc<Gauge>> = Registry::default();
+        registry.register("my_gauge", "My gauge", gauge.clone());
+        gauge.set(42);
+
+        let mut encoded = Vec::new();
+        encode(&mut encoded, &registry).unwrap();
+
+        let expected = "# HELP my_gauge My gauge.\n".to_owned()
+            + "# TYPE my_gauge gauge\n"
+            + "my_gauge 42\n"
+            + "# EOF\n";
+        assert_eq!(expected, String::from_utf8(encoded).unwrap());
+    }
+
+    #[test]
+    fn encode_label_set_to_string() {
+        assert_eq!("", encode_label_set(&()).unwrap());
+        assert_eq!(
+            "{method=\"GET\"}",
+            encode_label_set(&vec![("method".to_string(), "GET".to_string())]).unwrap()
+        );
+    }
+
+    #[test]
+    fn encode_float_precision() {
+        let mut registry = <Registry>::default();
+        let gauge = Gauge::<f64, AtomicU64>::default();
+        registry.register("my_gauge", "My gauge", Box::new(gauge.clone()));
+        let counter = Counter::<f64, AtomicU64>::default();
+        registry.register("my_counter", "My counter", Box::new(counter.clone()));
+        gauge.set(2.0 / 3.0);
+        counter.inc_by(1.5);
+
+        let mut encoded = Vec::new();
+        encode(&mut encoded, &registry).unwrap();
+
+        let expected = "# HELP my_gauge My gauge.\n".to_owned()
+            + "# TYPE my_gauge gauge\n"
+            + "my_gauge 0.6666666666666666\n"
+            + "# HELP my_counter My counter.\n"
+            + "# TYPE my_counter counter\n"
+            + "my_counter_total 1.5\n"
+            + "# EOF\n";
+        assert_eq!(expected, String::from_utf8(encoded).unwrap());
+
+        let mut encoded = Vec::new();
+        let options = EncodeOptions::default().float_precision(2);
+        encode_with_options(&mut encoded, &registry, &options).unwrap();
+
+        let expected = "# HELP my_gauge My gauge.\n".to_owned()
+            + "# TYPE my_gauge gauge\n"
+            + "my_gauge 0.67\n"
+            + "# HELP my_counter My counter.\n"
+            + "# TYPE my_counter counter\n"
+            + "my_counter_total 1.50\n"
+            + "# EOF\n";
+        assert_eq!(expected, String::from_utf8(encoded.clone()).unwrap());
+
+        parse_with_python_client(String::from_utf8(encoded).unwrap());
+    }
+
+    #[test]
+    fn encode_whole_floats_as_integers() {
+        let mut registry = <Registry>::default();
+        let gauge = Gauge::<f64, AtomicU64>::default();
+        registry.register("my_gauge", "My gauge", Box::new(gauge.clone()));
+
+        let encode_value = |value: f64, options: &EncodeOptions| {
+            gauge.set(value);
+            let mut encoded = Vec::new();
+            encode_with_options(&mut encoded, &registry, options).unwrap();
+            let encoded = String::from_utf8(encoded).unwrap();
+            encoded
+                .lines()
+                .find_map(|line| line.strip_prefix("my_gauge "))
+                .unwrap()
+                .to_string()
+        };
+
+        let default = EncodeOptions::default();
+        let integers = EncodeOptions::default().whole_floats_as_integers();
+        assert_eq!("5.0", encode_value(5.0, &default));
+        assert_eq!("5", encode_value(5.0, &integers));
+        assert_eq!("-3", encode_value(-3.0, &integers));
+        assert_eq!("100000000000000000000", encode_value(1e20, &integers));
+        assert_eq!("5.5", encode_value(5.5, &integers));
+        assert_eq!("NaN", encode_value(f64::NAN, &integers));
+        assert_eq!("+Inf", encode_value(f64::INFINITY, &integers));
+        assert_eq!(
+            "5",
+            encode_value(
+                5.0,
+                &EncodeOptions::default()
+                    .float_precision(2)
+                    .whole_floats_as_integers()
+            )
+        );
+    }
+
+    #[test]
+    fn encode_with_help_resolver() {
+        let mut registry = <Registry>::default();
+        registry.register("a", "A", Box::new(Counter::<u64>::default()));
+        registry.register("b", "B", Box::new(Gauge::<u64>::default()));
+
+        let encode_help = |locale: &'static str| {
+            let options = EncodeOptions::default().help_resolver(move |desc| {
+                (desc.name() == "a").then(|| Cow::Owned(format!("A ({}).", locale)))
+            });
+            let mut encoded = vec![];
+            encode_with_options(&mut encoded, &registry, &options).unwrap();
+            String::from_utf8(encoded)
+                .unwrap()
+                .lines()
+                .filter(|line| line.starts_with("# HELP"))
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec!["# HELP a A (en).", "# HELP b B."], encode_help("en"));
+        assert_eq!(vec!["# HELP a A (fr).", "# HELP b B."], encode_help("fr"));
+    }
+
+    #[test]
+    fn encode_escaped_help() {
+        let mut registry = <Registry>::default();
+        registry.register("a", "A\\\nA", Box::new(Counter::<u64>::default()));
+        registry.register("b", "B", Box::new(Counter::<u64>::default()));
+
+        let options = EncodeOptions::default()
+            .help_resolver(|desc| (desc.name() == "b").then_some(Cow::Borrowed("B\nB\\")));
+        let mut encoded = vec![];
+        encode_with_options(&mut encoded, &registry, &options).unwrap();
+
+        let expected = "# HELP a A\\\\\\nA.\n".to_owned()
+            + "# TYPE a counter\n"
+            + "a_total 0\n"
+            + "# HELP b B\\nB\\\\\n"
+            + "# TYPE b counter\n"
+            + "b_total 0\n"
+            + "# EOF\n";
+        assert_eq!(expected, String::from_utf8(encoded).unwrap());
+    }
+
+    #[test]
+    fn encode_prometheus_text_format() {
+        let mut registry = <Registry>::default();
+        let counter: CounterWithExemplar<(String, u64)> = CounterWithExemplar::default();
+        registry.register_with_unit(
+            "my_counter",
+            "My counter",
+            Unit::Seconds,
+            Box::new(counter.clone()),
+        );
+        counter.inc_by(1, Some(("user_id".to_string(), 42)));
+        let info = Info::new(vec![("os".to_string(), "GNU/linux".to_string())]);
+        registry.register("my_info_metric", "My info metric", Box::new(info));
+
+        let mut encoded = Vec::new();
+        let options = EncodeOptions::default().format(Format::PrometheusText);
+        encode_with_options(&mut encoded, &registry, &options).unwrap();
+
+        let expected = "# HELP my_counter_seconds_total My counter.\n".to_owned()
+            + "# TYPE my_counter_seconds_total counter\n"
+            + "my_counter_seconds_total 1\n"
+            + "# HELP my_info_metric_info My info metric.\n"
+            + "# TYPE my_info_metric_info gauge\n"
+            + "my_info_metric_info{os=\"GNU/linux\"} 1\n";
+        assert_eq!(expected, String::from_utf8(encoded).unwrap());
+    }
+
+    #[test]
+    fn encode_empty_label_name_and_value() {
+        let mut registry = Registry::default();
+        let family = Family::<Vec<(String, String)>, Counter>::default();
+        registry.register("my_counter", "My counter", family.clone());
+
+        family
+            .get_or_create(&vec![("method".to_string(), "".to_string())])
+            .inc();
+        let mut encoded = Vec::new();
+        encode(&mut encoded, &registry).unwrap();
+        assert!(String::from_utf8(encoded)
+            .unwrap()
+            .contains("my_counter_total{method=\"\"} 1\n"));
+
+        family
+            .get_or_create(&vec![("".to_string(), "GET".to_string())])
+            .inc();
+        let err = encode(&mut Vec::new(), &registry).unwrap_err();
+        assert_eq!(std::io::ErrorKind::InvalidData, err.kind());
+    }
+
+    #[test]
+    fn encode_optional_label_set() {
+        let mut registry = Registry::default();
+        let family = Family::<Option<Vec<(String, String)>>, Counter>::default();
+        let sub_registry =
+            registry.sub_registry_with_label((Cow::Borrowed("my_key"), Cow::Borrowed("my_value")));
+        sub_registry.register("my_counter", "My counter", family.clone());
+
+        family.get_or_create(&None).inc();
+        family
+            .get_or_create(&Some(vec![("method".to_string(), "GET".to_string())]))
+            .inc();
+
+        let mut encoded = Vec::new();
+        encode(&mut encoded, &registry).unwrap();
+        let encoded = String::from_utf8(encoded).unwrap();
+
+        assert!(encoded.contains("my_counter_total{my_key=\"my_value\"} 1\n"));
+        assert!(encoded.contains("my_counter_total{my_key=\"my_value\",method=\"GET\"} 1\n"));
+
+        parse_with_python_client(encoded);
+    }
+
+    #[test]
+    fn encode_by_family_matches_encode() {
+        let mut registry = <Registry>::default();
+        let counter: Counter = Counter::default();
+        registry.register_with_unit(
+            "my_counter",
+            "My counter",
+            Unit::Seconds,
+            Box::new(counter.clone()),
+        );
+        let sub_registry = registry.sub_registry_with_prefix("my_prefix");
+        let family = Family::<Vec<(String, String)>, Gauge>::default();
+        sub_registry.register("my_gauge", "My gauge", Box::new(family.clone()));
+        family
+            .get_or_create(&vec![("method".to_string(), "GET".to_string())])
+            .inc();
+
+        let mut families = vec![];
+        encode_by_family(&registry, |name, encoded| {
+            families.push((
+                name.to_string(),
+                String::from_utf8(encoded.to_vec()).unwrap(),
+            ));
+        })
+        .unwrap();
+
+        let names: Vec<_> = families.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(vec!["my_counter_seconds", "my_prefix_my_gauge"], names);
+        let concatenated = families
+            .iter()
+            .map(|(_, encoded)| encoded.strip_suffix("# EOF\n").unwrap())
+            .collect::<String>()
+            + "# EOF\n";
+        let mut encoded = Vec::new();
+        encode(&mut encoded, &registry).unwrap();
+        assert_eq!(String::from_utf8(encoded).unwrap(), concatenated);
+
+        for (_, encoded) in families {
+            parse_with_python_client(encoded);
+        }
+    }
+
+    #[test]
+    fn encode_by_family_prometheus_text_format() {
+        let mut registry: Registry = Registry::builder()
+            .with_encode_options(EncodeOptions::default().format(Format::PrometheusText))
+            .build();
+        registry.register("a", "A", Box::new(Counter::<u64>::default()));
+        registry.register("b", "B", Box::new(Gauge::<u64>::default()));
+
+        let mut families = vec![];
+        encode_by_family(&registry, |_, encoded| {
+            families.push(String::from_utf8(encoded.to_vec()).unwrap());
+        })
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                "# HELP a_total A.\n# TYPE a_total counter\na_total 0\n",
+                "# HELP b B.\n# TYPE b gauge\nb 0\n",
+            ],
+            families
+        );
+    }
+
+    #[test]
+    fn encode_suffixes() {
+        let mut registry = <Registry>::default();
+        registry.register("a", "A", Box::new(Counter::<u64>::default()));
+        registry.register("b_total", "B", Box::new(Counter::<u64>::default()));
+        registry.register("c_total", "C", Box::new(Gauge::<u64>::default()));
+        registry.register(
+            "d_count",
+            "D",
+            Box::new(Histogram::new(vec![1.0].into_iter())),
+        );
+        registry.register("e_info", "E", Box::new(Info::new(vec![("k", "v")])));
+        registry.register_with_unit(
+            "f_total",
+            "F",
+            Unit::Seconds,
+            Box::new(Counter::<u64>::default()),
+        );
+        registry.register("total", "Total", Box::new(Counter::<u64>::default()));
+
+        let mut encoded = Vec::new();
+        encode(&mut encoded, &registry).unwrap();
+
+        let expected = "# HELP a A.\n".to_owned()
+            + "# TYPE a counter\n"
+            + "a_total 0\n"
+            + "# HELP b B.\n"
+            + "# TYPE b counter\n"
+            + "b_total 0\n"
+            + "# HELP c_total C.\n"
+            + "# TYPE c_total gauge\n"
+            + "c_total 0\n"
+            + "# HELP d_count D.\n"
+            + "# TYPE d_count histogram\n"
+            + "d_count_sum 0.0\n"
+            + "d_count_count 0\n"
+            + "d_count_bucket{le=\"1.0\"} 0\n"
+            + "d_count_bucket{le=\"+Inf\"} 0\n"
+            + "# HELP e E.\n"
+            + "# TYPE e info\n"
+            + "e_info{k=\"v\"} 1\n"
+            + "# HELP f_seconds F.\n"
+            + "# TYPE f_seconds counter\n"
+            + "# UNIT f_seconds seconds\n"
+            + "f_seconds_total 0\n"
+            + "# HELP total Total.\n"
+            + "# TYPE total counter\n"
+            + "total_total 0\n"
+            + "# EOF\n";
+        assert_eq!(expected, String::from_utf8(encoded.clone()).unwrap());
+
+        parse_with_python_client(String::from_utf8(encoded).unwrap());
+    }
+
+    #[test]
+    fn encode_concat() {
+        let mut registry = Registry::default();
+        let family = Family::<Concat<Vec<(&str, &str)>, Vec<(&str, &str)>>, Counter>::default();
+        registry.register("my_counter", "My counter", family.clone());
+
+        family
+            .get_or_create(&Concat(vec![("a", "1")], vec![("b", "2"), ("c", "3")]))
+            .inc();
+        family
+            .get_or_create(&Concat(vec![], vec![("b", "2")]))
+            .inc();
+        family
+            .get_or_create(&Concat(vec![("a", "1")], vec![]))
+            .inc();
+        family.get_or_create(&Concat(vec![], vec![])).inc();
+
+        for (label_set, expected) in [
+            (
+                Concat(vec![("a", "1")], vec![("b", "2"), ("c", "3")]),
+                "{a=\"1\",b=\"2\",c=\"3\"}",
+            ),
+            (Concat(vec![], vec![("b", "2")]), "{b=\"2\"}"),
+            (Concat(vec![("a", "1")], vec![]), "{a=\"1\"}"),
+            (Concat(vec![], vec![]), ""),
+        ] {
+            assert_eq!(expected, encode_label_set(&label_set).unwrap());
+        }
+
+        let mut encoded = Vec::new();
+        encode(&mut encoded, &registry).unwrap();
+
+        parse_with_python_client(String::from_utf8(encoded).unwrap());
+    }
+
+    #[test]
+    fn encode_truncated() {
+        let value = "ünïcödé🦀🦀🦀";
+        assert_eq!("ünïcödé🦀", Truncated::<8>::new(value).as_str());
+        assert_eq!("ünïcödé…", Truncated::<8>::with_ellipsis(value).as_str());
+        assert_eq!(value, Truncated::<10>::new(value).as_str());
+        assert_eq!(value, Truncated::<10>::with_ellipsis(value).as_str());
+        assert_eq!("", Truncated::<0>::with_ellipsis(value).as_str());
+        assert_eq!("…", Truncated::<1>::with_ellipsis(value).as_str());
+
+        let family = Family::<[(&str, Truncated<4>); 1], Counter>::default();
+        family
+            .get_or_create(&[("user_agent", Truncated::new("curl/7.79.1"))])
+            .inc();
+        family
+            .get_or_create(&[("user_agent", Truncated::new("curl/8.0.0"))])
+            .inc();
+        assert_eq!(1, family.read().len());
+
+        let mut registry = Registry::default();
+        registry.register("my_counter", "My counter", family);
+        let mut encoded = Vec::new();
+        encode(&mut encoded, &registry).unwrap();
+        assert!(String::from_utf8(encoded)
+            .unwrap()
+            .contains("my_counter_total{user_agent=\"curl\"} 2\n"));
+    }
+
+    #[test]
+    fn encode_path_and_os_str() {
+        let path = PathBuf::from("/var/log/syslog");
+        assert_eq!(
+            "{path=\"/var/log/syslog\"}",
+            encode_label_set(&vec![("path", path.as_path())]).unwrap()
+        );
+        assert_eq!(
+            "{path=\"/var/log/syslog\"}",
+            encode_label_set(&vec![("path", path.into_os_string())]).unwrap()
+        );
 
-        match exemplars.and_then(|es| es.get(&i)) {
-            Some(exemplar) => exemplar_encoder.encode_exemplar(exemplar)?,
-            None => exemplar_encoder.no_exemplar()?,
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+
+            let invalid = OsStr::from_bytes(b"/tmp/\xFF");
+            assert_eq!(
+                "{path=\"/tmp/\u{FFFD}\"}",
+                encode_label_set(&vec![("path", Path::new(invalid))]).unwrap()
+            );
         }
     }
 
-    Ok(())
-}
+    #[test]
+    fn encode_labels_macro() {
+        assert_eq!(
+            "{method=\"GET\",status=\"200\"}",
+            encode_label_set(&crate::labels! { method = "GET", status = "200", }).unwrap()
+        );
+        assert_eq!(
+            "{code=\"404\"}",
+            encode_label_set(&crate::labels! { code = 404u64 }).unwrap()
+        );
+        let empty: [(&str, &str); 0] = crate::labels! {};
+        assert_eq!("", encode_label_set(&empty).unwrap());
+    }
 
-/////////////////////////////////////////////////////////////////////////////////
-// Info
+    #[test]
+    #[deny(non_snake_case)]
+    fn encode_labels_macro_with_non_snake_case_key() {
+        assert_eq!(
+            "{HTTP_method=\"GET\"}",
+            encode_label_set(&crate::labels! { HTTP_method = "GET" }).unwrap()
+        );
+    }
 
-impl<S> EncodeMetric for Info<S>
-where
-    S: Clone + std::hash::Hash + Eq + Encode,
-{
-    fn encode(&self, mut encoder: Encoder) -> Result<(), std::io::Error> {
-        encoder
-            .with_label_set(&self.0)
-            .encode_suffix("info")?
-            .no_bucket()?
-            .encode_value(1u32)?
-            .no_exemplar()?;
+    #[test]
+    fn encode_in_registration_order_or_sorted_by_name() {
+        let mut registry = <Registry>::default();
+        registry.register("c", "C", Box::new(Gauge::<u64>::default()));
+        let sub_registry = registry.sub_registry_with_prefix("a");
+        sub_registry.register("x", "X", Box::new(Gauge::<u64>::default()));
+        registry.register("b", "B", Box::new(Gauge::<u64>::default()));
+        registry.register_alias("b", "d");
+
+        let names = |options: &EncodeOptions| {
+            let mut encoded = Vec::new();
+            encode_with_options(&mut encoded, &registry, options).unwrap();
+            String::from_utf8(encoded)
+                .unwrap()
+                .lines()
+                .filter_map(|l| l.strip_prefix("# TYPE "))
+                .map(|l| l.split(' ').next().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
 
-        Ok(())
+        assert_eq!(vec!["c", "b", "d", "a_x"], names(&EncodeOptions::default()));
+        assert_eq!(
+            vec!["a_x", "b", "c", "d"],
+            names(&EncodeOptions::default().sort_by_name())
+        );
     }
 
-    fn metric_type(&self) -> MetricType {
-        Self::TYPE
+    #[test]
+    fn encode_sorted_by_cardinality() {
+        let mut registry = <Registry>::default();
+        for (name, series) in [("a", 0), ("b", 1), ("c", 3), ("d", 1)] {
+            let family = Family::<Vec<(String, String)>, Gauge>::default();
+            for i in 0..series {
+                family.get_or_create(&vec![("i".to_string(), i.to_string())]);
+            }
+            registry.register(name, name, Box::new(family));
+        }
+        registry.register("e", "E", Box::new(Gauge::<u64>::default()));
+
+        let mut encoded = Vec::new();
+        let options = EncodeOptions::default().sort_by_cardinality();
+        encode_with_options(&mut encoded, &registry, &options).unwrap();
+        let names = String::from_utf8(encoded)
+            .unwrap()
+            .lines()
+            .filter_map(|l| l.strip_prefix("# TYPE "))
+            .map(|l| l.split(' ').next().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["c", "b", "d", "e", "a"], names);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::metrics::counter::Counter;
-    use crate::metrics::gauge::Gauge;
-    use crate::metrics::histogram::exponential_buckets;
-    use pyo3::{prelude::*, types::PyModule};
-    use std::borrow::Cow;
+    #[cfg(feature = "smallvec")]
+    #[test]
+    fn small_label_set() {
+        let inline: SmallLabelSet<2, (&str, &str)> = [("a", "1"), ("b", "2")].into_iter().collect();
+        assert!(!inline.spilled());
+        let mut spilled = inline.clone();
+        spilled.push(("c", "3"));
+        assert!(spilled.spilled());
+        assert_eq!(
+            "{a=\"1\",b=\"2\",c=\"3\"}",
+            encode_label_set(&spilled).unwrap()
+        );
+
+        let family = Family::<SmallLabelSet<2, (&str, &str)>, Counter>::default();
+        family.get_or_create(&inline).inc();
+        family.get_or_create(&spilled).inc();
+        family
+            .get_or_create(&[("a", "1"), ("b", "2")].into_iter().collect())
+            .inc();
+        assert_eq!(2, family.get_or_create(&inline).get());
+    }
 
     #[test]
-    fn encode_counter() {
-        let counter: Counter = Counter::default();
-        let mut registry = Registry::default();
-        registry.register("my_counter", "My counter", counter);
+    fn encode_counter_without_type_suffix() {
+        let mut registry = <Registry>::default();
+        let family = Family::<Vec<(String, String)>, Counter>::default();
+        registry.register_without_type_suffix(
+            "http_requests",
+            "Requests",
+            Box::new(family.clone()),
+        );
+        registry.register_without_type_suffix(
+            "errors_total",
+            "Errors",
+            Box::new(Counter::<u64>::default()),
+        );
+        family
+            .get_or_create(&vec![("method".to_string(), "GET".to_string())])
+            .inc();
 
         let mut encoded = Vec::new();
-
         encode(&mut encoded, &registry).unwrap();
 
-        parse_with_python_client(String::from_utf8(encoded).unwrap());
+        let expected = "# HELP http_requests Requests.\n".to_owned()
+            + "# TYPE http_requests counter\n"
+            + "http_requests{method=\"GET\"} 1\n"
+            + "# HELP errors_total Errors.\n"
+            + "# TYPE errors_total counter\n"
+            + "errors_total 0\n"
+            + "# EOF\n";
+        assert_eq!(expected, String::from_utf8(encoded).unwrap());
     }
 
     #[test]
-    fn encode_counter_with_unit() {
+    fn encode_with_limit_at_family_boundary() {
+        let mut registry = Registry::<Counter>::default();
+        registry.register("a", "A", Counter::default());
+        registry.register("b", "B", Counter::default());
+
+        let mut full = Vec::new();
+        let report = encode_with_limit(&mut full, &registry, usize::MAX).unwrap();
+        assert!(!report.truncated());
+        assert_eq!(full.len(), report.bytes_written());
+
+        let mut exact = Vec::new();
+        let report = encode_with_limit(&mut exact, &registry, full.len()).unwrap();
+        assert!(!report.truncated());
+        assert_eq!(full, exact);
+
+        let mut truncated = Vec::new();
+        let report = encode_with_limit(&mut truncated, &registry, full.len() - 1).unwrap();
+        assert!(report.truncated());
+        assert_eq!(
+            "# HELP a A.\n# TYPE a counter\na_total 0\n# EOF\n",
+            String::from_utf8(truncated).unwrap()
+        );
+
+        let mut eof_only = Vec::new();
+        let report = encode_with_limit(&mut eof_only, &registry, 0).unwrap();
+        assert!(report.truncated());
+        assert_eq!("# EOF\n", String::from_utf8(eof_only).unwrap());
+    }
+
+    #[test]
+    fn encode_with_limit_without_reset() {
         let mut registry = Registry::default();
-        let counter: Counter = Counter::default();
-        registry.register_with_unit("my_counter", "My counter", Unit::Seconds, counter);
+        let histogram = Histogram::new([1.0].into_iter()).with_reset_on_read();
+        registry.register("a", "A", histogram.clone());
+        registry.register("b", "B", Histogram::new([1.0].into_iter()));
+        histogram.observe(0.5);
+
+        let mut full = Vec::new();
+        let report = encode_with_limit(&mut full, &registry, usize::MAX).unwrap();
+        assert!(!report.truncated());
+        assert_eq!((0.5, 1), histogram.sum_and_count());
+
+        // Truncating the family in reset-on-read mode does not lose its
+        // observations either.
+        let report = encode_with_limit(&mut Vec::new(), &registry, 0).unwrap();
+        assert!(report.truncated());
+        assert_eq!((0.5, 1), histogram.sum_and_count());
+
+        let mut expected = Vec::new();
+        encode(&mut expected, &registry).unwrap();
+        assert_eq!(expected, full);
+        assert_eq!((0.0, 0), histogram.sum_and_count());
+    }
 
-        let mut encoded = Vec::new();
+    #[test]
+    fn encode_duplicate_family_name() {
+        let mut registry = Registry::<Counter>::default();
+        registry.register("requests", "Requests", Counter::default());
+        registry
+            .sub_registry_with_label((Cow::Borrowed("handler"), Cow::Borrowed("api")))
+            .register("requests_total", "Requests", Counter::default());
+
+        let mut encoded = vec![];
+        let error = encode(&mut encoded, &registry).unwrap_err();
+        assert_eq!(
+            "metric family `requests` registered more than once",
+            error.to_string()
+        );
+        assert!(encoded.is_empty());
+
+        let report = encode_lenient(&mut encoded, &registry).unwrap();
+        assert_eq!("requests", report.failures()[0].0);
+        assert!(String::from_utf8(encoded)
+            .unwrap()
+            .starts_with("# HELP requests Requests.\n"));
+    }
+
+    #[test]
+    fn encode_merged_duplicate_family_name() {
+        let mut registry: Registry = Registry::builder()
+            .with_encode_options(EncodeOptions::default().merge_duplicate_families())
+            .build();
+        let old = Counter::<u64>::default();
+        registry
+            .sub_registry_with_label((Cow::Borrowed("shard"), Cow::Borrowed("0")))
+            .register("requests", "Requests", Box::new(old.clone()));
+        registry.register("other", "Other", Box::new(Gauge::<u64>::default()));
+        let new = Counter::<u64>::default();
+        let sub_registry =
+            registry.sub_registry_with_label((Cow::Borrowed("shard"), Cow::Borrowed("1")));
+        sub_registry.register("requests_v2", "Requests", Box::new(new.clone()));
+        assert!(sub_registry.register_alias("requests_v2", "requests"));
+        old.inc();
+        new.inc_by(2);
+
+        let expected = "# HELP other Other.\n".to_owned()
+            + "# TYPE other gauge\n"
+            + "other 0\n"
+            + "# HELP requests Requests.\n"
+            + "# TYPE requests counter\n"
+            + "requests_total{shard=\"0\"} 1\n"
+            + "requests_total{shard=\"1\"} 2\n"
+            + "# HELP requests_v2 Requests.\n"
+            + "# TYPE requests_v2 counter\n"
+            + "requests_v2_total{shard=\"1\"} 2\n"
+            + "# EOF\n";
+        let mut encoded = vec![];
         encode(&mut encoded, &registry).unwrap();
+        assert_eq!(expected, String::from_utf8(encoded.clone()).unwrap());
+        parse_with_python_client(String::from_utf8(encoded).unwrap());
 
-        let expected = "# HELP my_counter_seconds My counter.\n".to_owned()
-            + "# TYPE my_counter_seconds counter\n"
-            + "# UNIT my_counter_seconds seconds\n"
-            + "my_counter_seconds_total 0\n"
+        let mut encoded = vec![];
+        let report = encode_lenient(&mut encoded, &registry).unwrap();
+        assert!(report.is_ok());
+        assert_eq!(expected, String::from_utf8(encoded).unwrap());
+
+        // Families of a different type are not merged.
+        registry.register("requests", "Requests", Box::new(Gauge::<u64>::default()));
+        let error = encode(&mut vec![], &registry).unwrap_err();
+        assert_eq!(
+            "metric family `requests` registered more than once",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn encode_lenient_skips_failing_families() {
+        #[derive(Clone, Hash, PartialEq, Eq)]
+        struct Broken;
+
+        impl Encode for Broken {
+            fn encode(&self, _writer: &mut dyn Write) -> Result<(), std::io::Error> {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "broken",
+                ))
+            }
+        }
+
+        let mut registry = <Registry>::default();
+        registry.register("a", "A", Box::new(Counter::<u64>::default()));
+        let family = Family::<Broken, Counter>::default();
+        family.get_or_create(&Broken).inc();
+        registry.register_with_unit("b", "B", Unit::Bytes, Box::new(family));
+        registry.register("c", "C", Box::new(Gauge::<u64>::default()));
+
+        let mut strict = vec![];
+        assert!(encode(&mut strict, &registry).is_err());
+
+        let mut encoded = vec![];
+        let report = encode_lenient(&mut encoded, &registry).unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(1, report.failures().len());
+        assert_eq!("b_bytes", report.failures()[0].0);
+        assert_eq!("broken", report.failures()[0].1.to_string());
+
+        let expected = "# HELP a A.\n".to_owned()
+            + "# TYPE a counter\n"
+            + "a_total 0\n"
+            + "# HELP c C.\n"
+            + "# TYPE c gauge\n"
+            + "c 0\n"
             + "# EOF\n";
         assert_eq!(expected, String::from_utf8(encoded.clone()).unwrap());
 
@@ -649,45 +3524,215 @@ mod tests {
     }
 
     #[test]
-    fn encode_counter_with_exemplar() {
+    fn encode_flushes_buffered_writer() {
         let mut registry = Registry::default();
+        registry.register("my_counter", "My counter", Counter::<u64>::default());
 
-        let counter_with_exemplar: CounterWithExemplar<(String, u64)> =
-            CounterWithExemplar::default();
-        registry.register_with_unit(
-            "my_counter_with_exemplar",
-            "My counter with exemplar",
-            Unit::Seconds,
-            counter_with_exemplar.clone(),
+        let mut writer = std::io::BufWriter::new(Vec::new());
+        encode(&mut writer, &registry).unwrap();
+
+        let expected = "# HELP my_counter My counter.\n".to_owned()
+            + "# TYPE my_counter counter\n"
+            + "my_counter_total 0\n"
+            + "# EOF\n";
+        assert!(writer.buffer().is_empty());
+        assert_eq!(expected, String::from_utf8_lossy(writer.get_ref()));
+    }
+
+    #[test]
+    fn encode_filtered() {
+        let mut registry = <Registry>::default();
+        registry.register("a", "A", Box::new(Counter::<u64>::default()));
+        registry.sub_registry_with_prefix("sub").register_sensitive(
+            "b",
+            "B",
+            Box::new(Gauge::<u64>::default()),
         );
 
-        counter_with_exemplar.inc_by(1, Some(("user_id".to_string(), 42)));
+        let encode_filtered = |authorized: bool| {
+            let mut encoded = vec![];
+            encode_with_filter(&mut encoded, &registry, |desc| {
+                authorized || !desc.sensitive()
+            })
+            .unwrap();
+            String::from_utf8(encoded).unwrap()
+        };
+
+        let mut all = vec![];
+        encode(&mut all, &registry).unwrap();
+        assert_eq!(String::from_utf8(all).unwrap(), encode_filtered(true));
+
+        let expected =
+            "# HELP a A.\n".to_owned() + "# TYPE a counter\n" + "a_total 0\n" + "# EOF\n";
+        assert_eq!(expected, encode_filtered(false));
+    }
+
+    #[test]
+    fn encode_single_family() {
+        let mut registry = <Registry>::default();
+        registry.register("a", "A", Box::new(Counter::<u64>::default()));
+        let family = Family::<Vec<(String, String)>, Counter>::default();
+        let sub_registry = registry.sub_registry_with_prefix("sub");
+        sub_registry.register("b", "B", Box::new(family.clone()));
+        assert!(sub_registry.register_alias("b", "c"));
+        family
+            .get_or_create(&vec![("method".to_string(), "GET".to_string())])
+            .inc();
+
+        let mut encoded = Vec::new();
+        assert!(encode_family(&mut encoded, &registry, "sub_c").unwrap());
+        let expected = "# HELP sub_c B.\n".to_owned()
+            + "# TYPE sub_c counter\n"
+            + "sub_c_total{method=\"GET\"} 1\n"
+            + "# EOF\n";
+        assert_eq!(expected, String::from_utf8(encoded).unwrap());
+
+        assert!(!encode_family(&mut Vec::new(), &registry, "b").unwrap());
+        assert!(!encode_family(&mut Vec::new(), &registry, "a_total").unwrap());
+    }
+
+    #[test]
+    fn encode_non_finite_values() {
+        let mut registry = <Registry>::default();
+        let gauge = Gauge::<f64, AtomicU64>::default();
+        registry.register("my_gauge", "My gauge", Box::new(gauge.clone()));
+        let histogram = Histogram::new([1.0].into_iter());
+        registry.register("my_histogram", "My histogram", Box::new(histogram.clone()));
+        gauge.set(f64::INFINITY);
+        histogram.observe(f64::INFINITY);
 
         let mut encoded = Vec::new();
         encode(&mut encoded, &registry).unwrap();
 
-        let expected = "# HELP my_counter_with_exemplar_seconds My counter with exemplar.\n"
-            .to_owned()
-            + "# TYPE my_counter_with_exemplar_seconds counter\n"
-            + "# UNIT my_counter_with_exemplar_seconds seconds\n"
-            + "my_counter_with_exemplar_seconds_total 1 # {user_id=\"42\"} 1\n"
+        let expected = "# HELP my_gauge My gauge.\n".to_owned()
+            + "# TYPE my_gauge gauge\n"
+            + "my_gauge +Inf\n"
+            + "# HELP my_histogram My histogram.\n"
+            + "# TYPE my_histogram histogram\n"
+            + "my_histogram_sum +Inf\n"
+            + "my_histogram_count 1\n"
+            + "my_histogram_bucket{le=\"1.0\"} 0\n"
+            + "my_histogram_bucket{le=\"+Inf\"} 1\n"
             + "# EOF\n";
         assert_eq!(expected, String::from_utf8(encoded.clone()).unwrap());
 
         parse_with_python_client(String::from_utf8(encoded).unwrap());
+
+        for (value, token) in [(f64::NEG_INFINITY, "-Inf"), (f64::NAN, "NaN"), (1.5, "1.5")] {
+            let mut encoded = Vec::new();
+            value.encode(&mut encoded).unwrap();
+            assert_eq!(token, String::from_utf8(encoded).unwrap());
+        }
     }
 
     #[test]
-    fn encode_gauge() {
+    fn encode_string_like_label_values() {
+        let value = "GET".to_string();
+        let labels = vec![("method", &value)];
+        assert_eq!("{method=\"GET\"}", encode_label_set(&labels).unwrap());
+
+        let labels: Vec<(Box<str>, Arc<str>)> = vec![("method".into(), "GET".into())];
+        assert_eq!("{method=\"GET\"}", encode_label_set(&labels).unwrap());
+    }
+
+    #[test]
+    fn encode_in_chunks() {
         let mut registry = Registry::default();
-        let gauge: Gauge = Gauge::default();
-        registry.register("my_gauge", "My gauge", gauge);
+        let family = Family::<Vec<(String, String)>, Counter>::default();
+        registry.register("my_counter_family", "My counter family", family.clone());
+        family
+            .get_or_create(&vec![("method".to_string(), "GET".to_string())])
+            .inc();
 
-        let mut encoded = Vec::new();
+        let mut expected = Vec::new();
+        encode(&mut expected, &registry).unwrap();
+
+        let mut encoder = ChunkEncoder::default();
+        // Encode twice to make sure the encoder starts over after the last chunk.
+        for _ in 0..2 {
+            let mut buffer = [0u8; 7];
+            let mut encoded = Vec::new();
+            loop {
+                match encoder.encode_chunk(&registry, &mut buffer) {
+                    Ok(written) => {
+                        encoded.extend_from_slice(&buffer[..written]);
+                        break;
+                    }
+                    Err(EncodeChunkError::BufferFull { written }) => {
+                        assert_eq!(buffer.len(), written);
+                        encoded.extend_from_slice(&buffer[..written]);
+                    }
+                    Err(EncodeChunkError::Io(e)) => panic!("{}", e),
+                }
+            }
 
-        encode(&mut encoded, &registry).unwrap();
+            assert_eq!(expected, encoded);
+        }
+    }
 
-        parse_with_python_client(String::from_utf8(encoded).unwrap());
+    #[test]
+    fn encode_in_chunks_with_registry_options() {
+        let mut registry: Registry = Registry::builder()
+            .with_encode_options(EncodeOptions::default().format(Format::PrometheusText))
+            .build();
+        registry.register("a", "A", Box::new(Counter::<u64>::default()));
+
+        let mut expected = Vec::new();
+        encode(&mut expected, &registry).unwrap();
+
+        let mut buffer = [0u8; 128];
+        let written = ChunkEncoder::default()
+            .encode_chunk(&registry, &mut buffer)
+            .unwrap();
+        assert_eq!(expected, &buffer[..written]);
+        assert!(!expected.ends_with(b"# EOF\n"));
+
+        let written = ChunkEncoder::with_options(EncodeOptions::default())
+            .encode_chunk(&registry, &mut buffer)
+            .unwrap();
+        assert!(buffer[..written].ends_with(b"# EOF\n"));
+    }
+
+    #[test]
+    fn encode_in_chunks_with_reset_on_read() {
+        let mut registry = <Registry>::default();
+        let histogram = Histogram::new([1.0].into_iter()).with_reset_on_read();
+        registry.register("my_histogram", "My histogram", Box::new(histogram.clone()));
+        registry.register(
+            "my_counter",
+            "My counter",
+            Box::new(Counter::<u64>::default()),
+        );
+        histogram.observe(0.5);
+
+        let mut expected = Vec::new();
+        encode_with_limit(&mut expected, &registry, usize::MAX).unwrap();
+
+        let mut encoder = ChunkEncoder::default();
+        for count in ["1", "0"] {
+            let mut buffer = [0u8; 7];
+            let mut chunked = Vec::new();
+            loop {
+                match encoder.encode_chunk(&registry, &mut buffer) {
+                    Ok(written) => {
+                        chunked.extend_from_slice(&buffer[..written]);
+                        break;
+                    }
+                    Err(EncodeChunkError::BufferFull { written }) => {
+                        chunked.extend_from_slice(&buffer[..written]);
+                    }
+                    Err(EncodeChunkError::Io(e)) => panic!("{}", e),
+                }
+            }
+
+            // Reset once per scrape, not per chunk.
+            let chunked = String::from_utf8(chunked).unwrap();
+            assert!(chunked.contains(&format!("my_histogram_count {}\n", count)));
+            if count == "1" {
+                assert_eq!(String::from_utf8(expected.clone()).unwrap(), chunked);
+            }
+            assert_eq!((0.0, 0), histogram.sum_and_count());
+        }
     }
 
     #[test]
@@ -710,6 +3755,46 @@ mod tests {
         parse_with_python_client(String::from_utf8(encoded).unwrap());
     }
 
+    #[test]
+    fn encode_labeled_counter_and_gauge() {
+        let label_set = vec![("method".to_string(), "GET".to_string())];
+
+        let mut labeled = <Registry>::default();
+        let counter: Counter = Counter::default();
+        labeled.register(
+            "my_counter",
+            "My counter",
+            Box::new(counter.clone().with_labels(label_set.clone())),
+        );
+        let gauge: Gauge = Gauge::default();
+        labeled.register(
+            "my_gauge",
+            "My gauge",
+            Box::new(gauge.clone().with_labels(label_set.clone())),
+        );
+        counter.inc();
+        gauge.set(2);
+
+        let mut families = <Registry>::default();
+        let counter_family = Family::<Vec<(String, String)>, Counter>::default();
+        families.register("my_counter", "My counter", Box::new(counter_family.clone()));
+        let gauge_family = Family::<Vec<(String, String)>, Gauge>::default();
+        families.register("my_gauge", "My gauge", Box::new(gauge_family.clone()));
+        counter_family.get_or_create(&label_set).inc();
+        gauge_family.get_or_create(&label_set).set(2);
+
+        let mut encoded = Vec::new();
+        encode(&mut encoded, &labeled).unwrap();
+        let mut expected = Vec::new();
+        encode(&mut expected, &families).unwrap();
+        assert_eq!(
+            String::from_utf8(expected).unwrap(),
+            String::from_utf8(encoded.clone()).unwrap()
+        );
+
+        parse_with_python_client(String::from_utf8(encoded).unwrap());
+    }
+
     #[test]
     fn encode_counter_family_with_prefix_with_label() {
         let mut registry = Registry::default();
@@ -823,6 +3908,175 @@ mod tests {
         parse_with_python_client(String::from_utf8(encoded).unwrap());
     }
 
+    #[test]
+    fn encode_histogram_with_non_finite_exemplars() {
+        let mut registry = Registry::default();
+        let histogram = HistogramWithExemplars::new(exponential_buckets(1.0, 2.0, 10));
+        registry.register("my_histogram", "My histogram", histogram.clone());
+        histogram.observe(f64::NAN, Some(("user_id".to_string(), 42u64)));
+        histogram.observe(f64::INFINITY, Some(("user_id".to_string(), 42u64)));
+        histogram.observe(f64::NEG_INFINITY, Some(("user_id".to_string(), 42u64)));
+
+        let mut encoded = Vec::new();
+        encode(&mut encoded, &registry).unwrap();
+
+        assert!(!String::from_utf8(encoded).unwrap().contains(" # {"));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Exemplar bucket index out of range")]
+    fn encode_histogram_with_out_of_range_exemplar() {
+        let buckets = [(1.0, 0), (f64::MAX, 1)];
+        let mut exemplars = HashMap::new();
+        exemplars.insert(
+            // One past the `+Inf` bucket.
+            buckets.len(),
+            Exemplar {
+                label_set: vec![("user_id".to_string(), "42".to_string())],
+                value: 2.0,
+            },
+        );
+
+        let mut encoded = Vec::new();
+        let encoder = Encoder {
+            writer: &mut encoded,
+            name: "my_histogram",
+            unit: &None,
+            const_labels: &[],
+            labels: None,
+            type_suffix: true,
+            reset_on_read: false,
+            options: &EncodeOptions::default(),
+        };
+        let _ = encode_histogram_with_maybe_exemplars(2.0, 1, &buckets, Some(&exemplars), encoder);
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, should_panic)]
+    fn encode_histogram_with_inconsistent_count() {
+        let buckets = [(1.0, 1), (f64::MAX, 1)];
+
+        let mut encoded = Vec::new();
+        let encoder = Encoder {
+            writer: &mut encoded,
+            name: "my_histogram",
+            unit: &None,
+            const_labels: &[],
+            labels: None,
+            type_suffix: true,
+            reset_on_read: false,
+            options: &EncodeOptions::default(),
+        };
+        encode_histogram_with_maybe_exemplars::<()>(1.0, 3, &buckets, None, encoder).unwrap();
+    }
+
+    #[test]
+    fn encode_int_histogram() {
+        let mut registry = Registry::default();
+        let histogram = IntHistogram::new([1024.0].into_iter());
+        registry.register("my_histogram", "My histogram", histogram.clone());
+        histogram.observe(9_007_199_254_740_993);
+
+        let mut encoded = Vec::new();
+        encode(&mut encoded, &registry).unwrap();
+
+        let expected = "# HELP my_histogram My histogram.\n".to_owned()
+            + "# TYPE my_histogram histogram\n"
+            + "my_histogram_sum 9007199254740993\n"
+            + "my_histogram_count 1\n"
+            + "my_histogram_bucket{le=\"1024.0\"} 0\n"
+            + "my_histogram_bucket{le=\"+Inf\"} 1\n"
+            + "# EOF\n";
+        assert_eq!(expected, String::from_utf8(encoded.clone()).unwrap());
+
+        parse_with_python_client(String::from_utf8(encoded).unwrap());
+    }
+
+    #[test]
+    fn encode_histogram_as_summary() {
+        let mut registry = Registry::default();
+        let histogram = Histogram::new([1.0, 2.0, 4.0].into_iter());
+        registry.register("my_histogram", "My histogram", histogram.clone());
+        for v in [0.5, 1.5, 1.5, 3.0, 5.0] {
+            histogram.observe(v);
+        }
+
+        let mut encoded = Vec::new();
+        let options = EncodeOptions::default().histograms_as_summaries(vec![0.0, 0.5, 0.9, 1.0]);
+        encode_with_options(&mut encoded, &registry, &options).unwrap();
+
+        let expected = "# HELP my_histogram My histogram.\n".to_owned()
+            + "# TYPE my_histogram summary\n"
+            + "my_histogram_sum 11.5\n"
+            + "my_histogram_count 5\n"
+            + "my_histogram{quantile=\"0.0\"} 0.0\n"
+            + "my_histogram{quantile=\"0.5\"} 1.75\n"
+            + "my_histogram{quantile=\"0.9\"} 4.0\n"
+            + "my_histogram{quantile=\"1.0\"} 4.0\n"
+            + "# EOF\n";
+        assert_eq!(expected, String::from_utf8(encoded.clone()).unwrap());
+
+        parse_with_python_client(String::from_utf8(encoded).unwrap());
+    }
+
+    #[test]
+    fn encode_empty_histogram_as_summary() {
+        let mut registry = Registry::default();
+        let histogram = Histogram::new([1.0, 2.0].into_iter());
+        registry.register("my_histogram", "My histogram", histogram);
+
+        let mut encoded = Vec::new();
+        let options = EncodeOptions::default().histograms_as_summaries(vec![0.5]);
+        encode_with_options(&mut encoded, &registry, &options).unwrap();
+
+        let expected = "# HELP my_histogram My histogram.\n".to_owned()
+            + "# TYPE my_histogram summary\n"
+            + "my_histogram_sum 0.0\n"
+            + "my_histogram_count 0\n"
+            + "my_histogram{quantile=\"0.5\"} NaN\n"
+            + "# EOF\n";
+        assert_eq!(expected, String::from_utf8(encoded.clone()).unwrap());
+    }
+
+    #[test]
+    fn encode_custom_histogram_with_summary_options() {
+        struct CustomHistogram;
+
+        impl EncodeMetric for CustomHistogram {
+            fn encode(&self, mut encoder: Encoder) -> Result<(), std::io::Error> {
+                encoder
+                    .encode_suffix("count")?
+                    .no_bucket()?
+                    .encode_value(1u64)?
+                    .no_exemplar()
+            }
+
+            fn metric_type(&self) -> MetricType {
+                MetricType::Histogram
+            }
+        }
+
+        let mut registry = <Registry>::default();
+        registry.register("custom", "Custom", Box::new(CustomHistogram));
+        let family = Family::<Vec<(String, String)>, Histogram>::new_with_constructor(|| {
+            Histogram::new([1.0].into_iter())
+        });
+        registry.register("family", "Family", Box::new(family));
+
+        let mut encoded = Vec::new();
+        let options = EncodeOptions::default().histograms_as_summaries(vec![0.5]);
+        encode_with_options(&mut encoded, &registry, &options).unwrap();
+
+        let expected = "# HELP custom Custom.\n".to_owned()
+            + "# TYPE custom histogram\n"
+            + "custom_count 1\n"
+            + "# HELP family Family.\n"
+            + "# TYPE family summary\n"
+            + "# EOF\n";
+        assert_eq!(expected, String::from_utf8(encoded).unwrap());
+    }
+
     fn parse_with_python_client(input: String) {
         pyo3::prepare_freethreaded_python();
 
@@ -0,0 +1,450 @@
+//! Parser for the Open Metrics text format, e.g. for round-trip tests or to
+//! relay scraped metrics.
+//!
+//! See [`parse`] for details.
+
+/// A metric family parsed via [`parse`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParsedFamily {
+    /// The name of the family, without any sample suffixes like `_total`.
+    pub name: String,
+    /// The metric type, e.g. `counter`, as declared by the `# TYPE` line.
+    /// `unknown` if not declared.
+    pub metric_type: String,
+    /// The unescaped `# HELP` text, if any.
+    pub help: Option<String>,
+    /// The `# UNIT`, if any.
+    pub unit: Option<String>,
+    pub samples: Vec<ParsedSample>,
+}
+
+/// A single sample line of a [`ParsedFamily`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParsedSample {
+    /// The name of the sample, including any suffix like `_total`.
+    pub name: String,
+    /// The unescaped labels in the order they appear in.
+    pub labels: Vec<(String, String)>,
+    pub value: f64,
+    pub timestamp: Option<f64>,
+    pub exemplar: Option<ParsedExemplar>,
+}
+
+/// An exemplar attached to a [`ParsedSample`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParsedExemplar {
+    pub labels: Vec<(String, String)>,
+    pub value: f64,
+    pub timestamp: Option<f64>,
+}
+
+/// Error returned by [`parse`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    line: usize,
+    message: String,
+}
+
+impl ParseError {
+    fn new<M: Into<String>>(line: usize, message: M) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+
+    /// The 1-based line number the error occurred on.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Suffixes a sample name may have in addition to its family name.
+const SAMPLE_SUFFIXES: [&str; 8] = [
+    "_total", "_created", "_count", "_sum", "_bucket", "_gcount", "_gsum", "_info",
+];
+
+/// Parse the Open Metrics text format into a list of [`ParsedFamily`].
+///
+/// Covers `# HELP`, `# TYPE` and `# UNIT` lines, samples with escaped label
+/// values, optional timestamps and exemplars, as well as the mandatory
+/// terminating `# EOF`. This is not a full validating parser. E.g. it does not
+/// check that histogram buckets are cumulative.
+///
+/// ```
+/// # use prometheus_client::encoding::text::{encode, parse};
+/// # use prometheus_client::metrics::counter::Counter;
+/// # use prometheus_client::registry::Registry;
+/// #
+/// let mut registry = Registry::default();
+/// let counter: Counter = Counter::default();
+/// registry.register("my_counter", "This is my counter", counter.clone());
+/// counter.inc();
+///
+/// let mut buffer = vec![];
+/// encode(&mut buffer, &registry).unwrap();
+///
+/// let families = parse(&String::from_utf8(buffer).unwrap()).unwrap();
+/// assert_eq!("my_counter", families[0].name);
+/// assert_eq!("counter", families[0].metric_type);
+/// assert_eq!("my_counter_total", families[0].samples[0].name);
+/// assert_eq!(1.0, families[0].samples[0].value);
+/// ```
+pub fn parse(input: &str) -> Result<Vec<ParsedFamily>, ParseError> {
+    let mut families: Vec<ParsedFamily> = vec![];
+    let mut lines = input.split('\n').enumerate().map(|(i, l)| (i + 1, l));
+
+    let mut last_line = 1;
+    loop {
+        let (n, line) = match lines.next() {
+            Some((n, line)) => (n, line),
+            None => return Err(ParseError::new(last_line, "missing `# EOF`")),
+        };
+        last_line = n;
+
+        if line == "# EOF" {
+            // Only allow a single trailing newline after `# EOF`.
+            return match (lines.next(), lines.next()) {
+                (None, _) | (Some((_, "")), None) => Ok(families),
+                _ => Err(ParseError::new(n + 1, "unexpected content after `# EOF`")),
+            };
+        }
+
+        if let Some(metadata) = line.strip_prefix("# ") {
+            let (keyword, rest) = split_once(metadata, ' ')
+                .ok_or_else(|| ParseError::new(n, "invalid metadata line"))?;
+            let (name, value) = split_once(rest, ' ').unwrap_or((rest, ""));
+            validate_name(n, name)?;
+            let family = family_mut(&mut families, name);
+
+            match keyword {
+                "HELP" => set_once(n, &mut family.help, unescape(n, value)?, "HELP")?,
+                "UNIT" => set_once(n, &mut family.unit, value.to_string(), "UNIT")?,
+                "TYPE" => {
+                    if !family.samples.is_empty() || family.metric_type != "unknown" {
+                        return Err(ParseError::new(n, "unexpected `# TYPE` line"));
+                    }
+                    family.metric_type = value.to_string();
+                }
+                _ => return Err(ParseError::new(n, format!("unknown keyword `{}`", keyword))),
+            }
+        } else if line.is_empty() {
+            return Err(ParseError::new(n, "unexpected empty line"));
+        } else if line.starts_with('#') {
+            return Err(ParseError::new(n, "invalid comment line"));
+        } else {
+            let sample = parse_sample(n, line)?;
+            let belongs_to_last = families.last().into_iter().any(|f| {
+                sample.name == f.name
+                    || matches!(
+                        sample.name.strip_prefix(f.name.as_str()),
+                        Some(suffix) if SAMPLE_SUFFIXES.contains(&suffix)
+                    )
+            });
+            if !belongs_to_last {
+                families.push(new_family(sample.name.clone()));
+            }
+            families
+                .last_mut()
+                .expect("Family to exist.")
+                .samples
+                .push(sample);
+        }
+    }
+}
+
+fn new_family(name: String) -> ParsedFamily {
+    ParsedFamily {
+        name,
+        metric_type: "unknown".to_string(),
+        help: None,
+        unit: None,
+        samples: vec![],
+    }
+}
+
+/// Returns the last family if it has the given name, otherwise starts a new
+/// one.
+fn family_mut<'a>(families: &'a mut Vec<ParsedFamily>, name: &str) -> &'a mut ParsedFamily {
+    if !families.last().into_iter().any(|f| f.name == name) {
+        families.push(new_family(name.to_string()));
+    }
+    families.last_mut().expect("Family to exist.")
+}
+
+fn set_once<T>(n: usize, slot: &mut Option<T>, value: T, keyword: &str) -> Result<(), ParseError> {
+    if slot.is_some() {
+        return Err(ParseError::new(
+            n,
+            format!("duplicate `# {}` line", keyword),
+        ));
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+fn parse_sample(n: usize, line: &str) -> Result<ParsedSample, ParseError> {
+    let name_end = line.find(['{', ' ']).unwrap_or(line.len());
+    let (name, mut rest) = line.split_at(name_end);
+    validate_name(n, name)?;
+
+    let mut labels = vec![];
+    if rest.starts_with('{') {
+        let (l, r) = parse_labels(n, rest)?;
+        labels = l;
+        rest = r;
+    }
+
+    let (values, exemplar) = match split_once(rest, '#') {
+        Some((values, exemplar)) => (values, Some(exemplar)),
+        None => (rest, None),
+    };
+    let (value, timestamp) = parse_value_and_timestamp(n, values)?;
+
+    let exemplar = exemplar
+        .map(|exemplar| {
+            let rest = exemplar
+                .strip_prefix(' ')
+                .filter(|rest| rest.starts_with('{'))
+                .ok_or_else(|| ParseError::new(n, "invalid exemplar"))?;
+            let (labels, rest) = parse_labels(n, rest)?;
+            let (value, timestamp) = parse_value_and_timestamp(n, rest)?;
+            Ok(ParsedExemplar {
+                labels,
+                value,
+                timestamp,
+            })
+        })
+        .transpose()?;
+
+    Ok(ParsedSample {
+        name: name.to_string(),
+        labels,
+        value,
+        timestamp,
+        exemplar,
+    })
+}
+
+/// Parses ` value [timestamp] ` following a sample name or exemplar labels.
+fn parse_value_and_timestamp(n: usize, input: &str) -> Result<(f64, Option<f64>), ParseError> {
+    let mut parts = input
+        .strip_prefix(' ')
+        .ok_or_else(|| ParseError::new(n, "expected space before value"))?
+        .trim_end_matches(' ')
+        .split(' ');
+
+    let value = parse_float(n, parts.next().unwrap_or_default())?;
+    let timestamp = parts.next().map(|t| parse_float(n, t)).transpose()?;
+    if parts.next().is_some() {
+        return Err(ParseError::new(n, "unexpected content after timestamp"));
+    }
+
+    Ok((value, timestamp))
+}
+
+fn parse_float(n: usize, input: &str) -> Result<f64, ParseError> {
+    match input {
+        "+Inf" | "Inf" => Ok(f64::INFINITY),
+        "-Inf" => Ok(f64::NEG_INFINITY),
+        "NaN" => Ok(f64::NAN),
+        _ if input
+            .chars()
+            .any(|c| c.is_ascii_alphabetic() && c != 'e' && c != 'E') =>
+        {
+            Err(ParseError::new(n, format!("invalid number `{}`", input)))
+        }
+        _ => input
+            .parse()
+            .map_err(|_| ParseError::new(n, format!("invalid number `{}`", input))),
+    }
+}
+
+type Labels = Vec<(String, String)>;
+
+/// Parses `{key="value",...}`, returning the labels and the remaining input.
+fn parse_labels(n: usize, input: &str) -> Result<(Labels, &str), ParseError> {
+    let mut labels = vec![];
+    let mut rest = &input[1..];
+
+    loop {
+        if let Some(r) = rest.strip_prefix('}') {
+            return Ok((labels, r));
+        }
+
+        let (key, r) =
+            split_once(rest, '=').ok_or_else(|| ParseError::new(n, "expected `=` in label"))?;
+        validate_name(n, key)?;
+        let r = r
+            .strip_prefix('"')
+            .ok_or_else(|| ParseError::new(n, "expected `\"` to open label value"))?;
+
+        let mut end = None;
+        let mut escaped = false;
+        for (i, c) in r.char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => {
+                    end = Some(i);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let end = end.ok_or_else(|| ParseError::new(n, "unterminated label value"))?;
+        labels.push((key.to_string(), unescape(n, &r[..end])?));
+
+        rest = &r[end + 1..];
+        if let Some(r) = rest.strip_prefix(',') {
+            rest = r;
+        } else if !rest.starts_with('}') {
+            return Err(ParseError::new(n, "expected `,` or `}` after label"));
+        }
+    }
+}
+
+fn unescape(n: usize, input: &str) -> Result<String, ParseError> {
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => output.push('\\'),
+            Some('"') => output.push('"'),
+            Some('n') => output.push('\n'),
+            _ => return Err(ParseError::new(n, "invalid escape sequence")),
+        }
+    }
+    Ok(output)
+}
+
+fn validate_name(n: usize, name: &str) -> Result<(), ParseError> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .into_iter()
+        .any(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':');
+    if !valid {
+        return Err(ParseError::new(n, format!("invalid name `{}`", name)));
+    }
+    Ok(())
+}
+
+fn split_once(input: &str, delimiter: char) -> Option<(&str, &str)> {
+    input
+        .find(delimiter)
+        .map(|i| (&input[..i], &input[i + delimiter.len_utf8()..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_families() {
+        let input = "# HELP my_histogram My \\\"histogram\\\".\n".to_owned()
+            + "# TYPE my_histogram histogram\n"
+            + "# UNIT my_histogram seconds\n"
+            + "my_histogram_sum 1.0\n"
+            + "my_histogram_count 1\n"
+            + "my_histogram_bucket{path=\"/a\\\\b\\\"c\\nd\",le=\"1.0\"} 1 # {trace_id=\"42\"} 0.5 123\n"
+            + "my_histogram_bucket{le=\"+Inf\"} 1\n"
+            + "untyped_metric{} -Inf 1000\n"
+            + "# EOF\n";
+
+        let families = parse(&input).unwrap();
+
+        assert_eq!(
+            vec![
+                ParsedFamily {
+                    name: "my_histogram".to_string(),
+                    metric_type: "histogram".to_string(),
+                    help: Some("My \"histogram\".".to_string()),
+                    unit: Some("seconds".to_string()),
+                    samples: vec![
+                        ParsedSample {
+                            name: "my_histogram_sum".to_string(),
+                            labels: vec![],
+                            value: 1.0,
+                            timestamp: None,
+                            exemplar: None,
+                        },
+                        ParsedSample {
+                            name: "my_histogram_count".to_string(),
+                            labels: vec![],
+                            value: 1.0,
+                            timestamp: None,
+                            exemplar: None,
+                        },
+                        ParsedSample {
+                            name: "my_histogram_bucket".to_string(),
+                            labels: vec![
+                                ("path".to_string(), "/a\\b\"c\nd".to_string()),
+                                ("le".to_string(), "1.0".to_string()),
+                            ],
+                            value: 1.0,
+                            timestamp: None,
+                            exemplar: Some(ParsedExemplar {
+                                labels: vec![("trace_id".to_string(), "42".to_string())],
+                                value: 0.5,
+                                timestamp: Some(123.0),
+                            }),
+                        },
+                        ParsedSample {
+                            name: "my_histogram_bucket".to_string(),
+                            labels: vec![("le".to_string(), "+Inf".to_string())],
+                            value: 1.0,
+                            timestamp: None,
+                            exemplar: None,
+                        },
+                    ],
+                },
+                ParsedFamily {
+                    name: "untyped_metric".to_string(),
+                    metric_type: "unknown".to_string(),
+                    help: None,
+                    unit: None,
+                    samples: vec![ParsedSample {
+                        name: "untyped_metric".to_string(),
+                        labels: vec![],
+                        value: f64::NEG_INFINITY,
+                        timestamp: Some(1000.0),
+                        exemplar: None,
+                    }],
+                },
+            ],
+            families
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(2, parse("my_counter_total 1\n").unwrap_err().line());
+        assert_eq!(1, parse("my_counter_total 1").unwrap_err().line());
+        assert_eq!(2, parse("# EOF\nfoo 1\n").unwrap_err().line());
+        assert_eq!(1, parse("1foo 1\n# EOF\n").unwrap_err().line());
+        assert_eq!(2, parse("foo 1\nfoo{a=\"b} 1\n# EOF\n").unwrap_err().line());
+        assert_eq!(1, parse("foo{a=\"\\x\"} 1\n# EOF\n").unwrap_err().line());
+        assert_eq!(1, parse("foo one\n# EOF\n").unwrap_err().line());
+        assert_eq!(
+            2,
+            parse("# HELP foo a\n# HELP foo b\n# EOF\n")
+                .unwrap_err()
+                .line()
+        );
+        assert!(parse("# EOF").unwrap().is_empty());
+    }
+}
@@ -10,9 +10,22 @@ pub mod info;
 /// A metric that is aware of its Open Metrics metric type.
 pub trait TypedMetric {
     const TYPE: MetricType = MetricType::Unknown;
+
+    /// Whether the metric is encoded as a summary with
+    /// [`EncodeOptions::histograms_as_summaries`](crate::encoding::text::EncodeOptions::histograms_as_summaries),
+    /// see [`EncodeMetric::encodes_as_summary`](crate::encoding::text::EncodeMetric::encodes_as_summary).
+    const ENCODES_AS_SUMMARY: bool = false;
 }
 
+/// Open Metrics metric types.
+///
+/// Note: [`MetricType`] is `#[non_exhaustive]`, as new metric types, e.g.
+/// summaries, are likely to be added in the future. Custom encoders matching
+/// on [`MetricType`] need to handle unknown variants, e.g. by encoding them as
+/// [`MetricType::Unknown`]. Adding a variant is not considered a breaking
+/// change.
 #[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
 pub enum MetricType {
     Counter,
     Gauge,
@@ -25,3 +38,31 @@ pub enum MetricType {
     // StateSet,
     // Summary
 }
+
+impl MetricType {
+    /// The Open Metrics keyword of the [`MetricType`], e.g. `counter`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetricType::Counter => "counter",
+            MetricType::Gauge => "gauge",
+            MetricType::Histogram => "histogram",
+            MetricType::Info => "info",
+            MetricType::Unknown => "unknown",
+        }
+    }
+
+    /// The suffix of the samples of a metric of the [`MetricType`], e.g.
+    /// `total` for a counter, or `None` if its samples carry no suffix, e.g.
+    /// for a gauge.
+    ///
+    /// A metric family's name never ends with its type's suffix. The
+    /// samples of a histogram carry the `bucket`, `sum` or `count` suffix
+    /// depending on the sample, thus it has no single suffix.
+    pub fn suffix(&self) -> Option<&'static str> {
+        match self {
+            MetricType::Counter => Some("total"),
+            MetricType::Info => Some("info"),
+            MetricType::Gauge | MetricType::Histogram | MetricType::Unknown => None,
+        }
+    }
+}
@@ -4,10 +4,11 @@
 
 use super::{MetricType, TypedMetric};
 use std::marker::PhantomData;
+use std::ops::{Add, AddAssign};
 #[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, MutexGuard};
 
 /// Open Metrics [`Counter`] to measure discrete events.
 ///
@@ -38,20 +39,42 @@ use std::sync::Arc;
 /// counter.inc();
 /// let _value: f64 = counter.get();
 /// ```
+///
+/// ## Using [`AtomicU32`] as storage and [`u32`] on the interface
+///
+/// Halves the memory of each [`Counter`], e.g. to reduce the footprint of a
+/// [`Family`](crate::metrics::family::Family) with many label sets. Note that
+/// a [`Counter`] of [`u32`] wraps around on overflow, i.e. after `u32::MAX` it
+/// continues at `0`. To Prometheus the wrap-around is indistinguishable from a
+/// counter reset.
+///
+/// ```
+/// # use prometheus_client::metrics::counter::Counter;
+/// # use std::sync::atomic::AtomicU32;
+/// let counter = Counter::<u32, AtomicU32>::default();
+/// counter.inc_by(u32::MAX);
+/// counter.inc();
+/// assert_eq!(0, counter.get());
+/// ```
 #[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
-#[derive(Debug)]
 pub struct Counter<N = u64, A = AtomicU64> {
     value: Arc<A>,
     phantom: PhantomData<N>,
 }
 
 #[cfg(any(target_arch = "mips", target_arch = "powerpc"))]
-#[derive(Debug)]
 pub struct Counter<N = u32, A = AtomicU32> {
     value: Arc<A>,
     phantom: PhantomData<N>,
 }
 
+/// Shows the current value, e.g. `Counter(42)`.
+impl<N: std::fmt::Debug, A: Atomic<N>> std::fmt::Debug for Counter<N, A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Counter").field(&self.get()).finish()
+    }
+}
+
 impl<N, A> Clone for Counter<N, A> {
     fn clone(&self) -> Self {
         Self {
@@ -81,6 +104,25 @@ impl<N, A: Atomic<N>> Counter<N, A> {
         self.value.inc_by(v)
     }
 
+    /// Increase the [`Counter`] by `v`, returning the new value.
+    ///
+    /// As opposed to calling [`Counter::inc_by`] followed by [`Counter::get`],
+    /// the returned value does not include concurrent increments, e.g. to
+    /// trigger an action exactly once when crossing a threshold.
+    ///
+    /// ```
+    /// # use prometheus_client::metrics::counter::Counter;
+    /// let counter: Counter = Counter::default();
+    /// counter.inc_by(41);
+    /// assert_eq!(42, counter.inc_by_returning(1));
+    /// ```
+    pub fn inc_by_returning(&self, v: N) -> N
+    where
+        N: Add<Output = N> + Clone,
+    {
+        self.value.inc_by_returning(v)
+    }
+
     /// Get the current value of the [`Counter`].
     pub fn get(&self) -> N {
         self.value.get()
@@ -99,12 +141,73 @@ impl<N, A: Atomic<N>> Counter<N, A> {
     }
 }
 
+impl<N, A: AtomicReset<N>> Counter<N, A> {
+    /// Atomically get the current value of the [`Counter`] and reset it to 0.
+    ///
+    /// Note: Resetting a [`Counter`] breaks the Open Metrics counter property
+    /// of being monotonically increasing. Prometheus interprets any decrease as
+    /// a counter reset, e.g. a process restart. Only use this method when
+    /// exporting per-interval deltas to non-Prometheus sinks and never
+    /// register such [`Counter`] with a [`Registry`](crate::registry::Registry)
+    /// scraped by Prometheus.
+    pub fn get_and_reset(&self) -> N {
+        self.value.get_and_reset()
+    }
+}
+
+/// Increase the [`Counter`] by `v`, same as [`Counter::inc_by`], i.e. with the
+/// same atomic ordering.
+///
+/// Implemented for `&Counter` as well, as the [`Counter`] is updated via a
+/// shared reference.
+///
+/// ```
+/// # use prometheus_client::metrics::counter::Counter;
+/// let mut counter: Counter = Counter::default();
+/// counter += 2;
+///
+/// let mut shared = &counter;
+/// shared += 1;
+/// assert_eq!(3, counter.get());
+/// ```
+impl<N, A: Atomic<N>> AddAssign<N> for Counter<N, A> {
+    fn add_assign(&mut self, v: N) {
+        self.inc_by(v);
+    }
+}
+
+impl<N, A: Atomic<N>> AddAssign<N> for &Counter<N, A> {
+    fn add_assign(&mut self, v: N) {
+        self.inc_by(v);
+    }
+}
+
 pub trait Atomic<N> {
     fn inc(&self) -> N;
 
     fn inc_by(&self, v: N) -> N;
 
     fn get(&self) -> N;
+
+    /// Increase by `v`, returning the new value, see
+    /// [`Counter::inc_by_returning`].
+    ///
+    /// Defaults to adding `v` to the previous value returned by
+    /// [`Atomic::inc_by`]. Implementations wrapping around on overflow override
+    /// this to wrap the returned value the same way.
+    fn inc_by_returning(&self, v: N) -> N
+    where
+        N: Add<Output = N> + Clone,
+    {
+        self.inc_by(v.clone()) + v
+    }
+}
+
+/// [`Atomic`] which can be reset to 0, see [`Counter::get_and_reset`].
+///
+/// Separate from [`Atomic`], not to require it from existing implementations.
+pub trait AtomicReset<N>: Atomic<N> {
+    fn get_and_reset(&self) -> N;
 }
 
 #[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
@@ -117,11 +220,22 @@ impl Atomic<u64> for AtomicU64 {
         self.fetch_add(v, Ordering::Relaxed)
     }
 
+    fn inc_by_returning(&self, v: u64) -> u64 {
+        self.inc_by(v).wrapping_add(v)
+    }
+
     fn get(&self) -> u64 {
         self.load(Ordering::Relaxed)
     }
 }
 
+#[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
+impl AtomicReset<u64> for AtomicU64 {
+    fn get_and_reset(&self) -> u64 {
+        self.swap(0, Ordering::Relaxed)
+    }
+}
+
 impl Atomic<u32> for AtomicU32 {
     fn inc(&self) -> u32 {
         self.inc_by(1)
@@ -131,11 +245,21 @@ impl Atomic<u32> for AtomicU32 {
         self.fetch_add(v, Ordering::Relaxed)
     }
 
+    fn inc_by_returning(&self, v: u32) -> u32 {
+        self.inc_by(v).wrapping_add(v)
+    }
+
     fn get(&self) -> u32 {
         self.load(Ordering::Relaxed)
     }
 }
 
+impl AtomicReset<u32> for AtomicU32 {
+    fn get_and_reset(&self) -> u32 {
+        self.swap(0, Ordering::Relaxed)
+    }
+}
+
 #[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
 impl Atomic<f64> for AtomicU64 {
     fn inc(&self) -> f64 {
@@ -162,10 +286,152 @@ impl Atomic<f64> for AtomicU64 {
     }
 }
 
+#[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
+impl AtomicReset<f64> for AtomicU64 {
+    fn get_and_reset(&self) -> f64 {
+        f64::from_bits(self.swap(f64::to_bits(0.0), Ordering::Relaxed))
+    }
+}
+
+/// Lock-based storage of a [`u128`], for a [`Counter`] exceeding the range of a
+/// [`u64`], e.g. an aggregate byte count over the lifetime of a service.
+///
+/// Stable Rust has no 128 bit atomic type, thus each operation takes a lock.
+/// A `Counter<u128, LockedU128>` is therefore considerably slower than a
+/// [`Counter`] of [`u64`], especially under contention. Only use it where the
+/// [`u64`] range is a realistic limit.
+///
+/// ```
+/// # use prometheus_client::metrics::counter::{Counter, LockedU128};
+/// let counter = Counter::<u128, LockedU128>::default();
+/// counter.inc_by(u64::MAX as u128);
+/// counter.inc_by(u64::MAX as u128);
+/// assert_eq!(2 * u64::MAX as u128, counter.get());
+/// ```
+#[derive(Debug, Default)]
+pub struct LockedU128(Mutex<u128>);
+
+impl LockedU128 {
+    fn lock(&self) -> MutexGuard<u128> {
+        self.0.lock().expect("Lock not to be poisoned.")
+    }
+}
+
+impl Atomic<u128> for LockedU128 {
+    fn inc(&self) -> u128 {
+        self.inc_by(1)
+    }
+
+    fn inc_by(&self, v: u128) -> u128 {
+        let mut value = self.lock();
+        let old = *value;
+        *value = old.wrapping_add(v);
+        old
+    }
+
+    fn get(&self) -> u128 {
+        *self.lock()
+    }
+
+    fn inc_by_returning(&self, v: u128) -> u128 {
+        self.inc_by(v).wrapping_add(v)
+    }
+}
+
+impl AtomicReset<u128> for LockedU128 {
+    fn get_and_reset(&self) -> u128 {
+        std::mem::take(&mut *self.lock())
+    }
+}
+
 impl<N, A> TypedMetric for Counter<N, A> {
     const TYPE: MetricType = MetricType::Counter;
 }
 
+impl<N, A> Counter<N, A> {
+    /// Bundle the [`Counter`] with a constant label set, see
+    /// [`LabeledCounter`].
+    pub fn with_labels<S>(self, label_set: S) -> LabeledCounter<S, N, A> {
+        LabeledCounter {
+            label_set: Arc::new(label_set),
+            counter: self,
+        }
+    }
+}
+
+/// A single [`Counter`] with a constant label set, e.g. a counter with a
+/// `version` label, created via [`Counter::with_labels`].
+///
+/// Cheaper than a [`Family`](crate::metrics::family::Family) with a single
+/// label set, as neither a map nor a lock is involved, neither on update nor
+/// on encoding. Keep a clone of the [`Counter`] to update it.
+///
+/// ```
+/// # use prometheus_client::encoding::text::encode;
+/// # use prometheus_client::metrics::counter::Counter;
+/// # use prometheus_client::registry::Registry;
+/// #
+/// let mut registry = <Registry>::default();
+/// let requests: Counter = Counter::default();
+/// registry.register(
+///     "requests",
+///     "Number of requests",
+///     Box::new(requests.clone().with_labels(vec![("method", "GET")])),
+/// );
+/// requests.inc();
+/// #
+/// # let mut buffer = vec![];
+/// # encode(&mut buffer, &registry).unwrap();
+/// # assert!(String::from_utf8(buffer).unwrap().contains("requests_total{method=\"GET\"} 1\n"));
+/// ```
+#[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
+pub struct LabeledCounter<S, N = u64, A = AtomicU64> {
+    label_set: Arc<S>,
+    counter: Counter<N, A>,
+}
+
+#[cfg(any(target_arch = "mips", target_arch = "powerpc"))]
+pub struct LabeledCounter<S, N = u32, A = AtomicU32> {
+    label_set: Arc<S>,
+    counter: Counter<N, A>,
+}
+
+impl<S: std::fmt::Debug, N: std::fmt::Debug, A: Atomic<N>> std::fmt::Debug
+    for LabeledCounter<S, N, A>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LabeledCounter")
+            .field("label_set", &self.label_set)
+            .field("counter", &self.counter)
+            .finish()
+    }
+}
+
+impl<S, N, A> Clone for LabeledCounter<S, N, A> {
+    fn clone(&self) -> Self {
+        Self {
+            label_set: self.label_set.clone(),
+            counter: self.counter.clone(),
+        }
+    }
+}
+
+impl<S, N, A> LabeledCounter<S, N, A> {
+    /// The constant label set of the [`Counter`].
+    pub fn label_set(&self) -> &S {
+        &self.label_set
+    }
+
+    /// The [`Counter`], e.g. to update it.
+    pub fn counter(&self) -> &Counter<N, A> {
+        &self.counter
+    }
+}
+
+impl<S, N, A> TypedMetric for LabeledCounter<S, N, A> {
+    const TYPE: MetricType = MetricType::Counter;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -178,6 +444,75 @@ mod tests {
         assert_eq!(1, counter.get());
     }
 
+    #[test]
+    fn debug() {
+        let counter: Counter = Counter::default();
+        counter.inc_by(42);
+        assert_eq!("Counter(42)", format!("{:?}", counter));
+    }
+
+    #[test]
+    fn locked_u128() {
+        let counter = Counter::<u128, LockedU128>::default();
+        assert_eq!(0, counter.inc_by(u128::MAX - 1));
+        assert_eq!(u128::MAX - 1, counter.inc());
+        assert_eq!(u128::MAX, counter.get());
+        assert_eq!(u128::MAX, counter.inc());
+        assert_eq!(0, counter.get_and_reset());
+    }
+
+    #[test]
+    fn get_and_reset() {
+        let counter: Counter = Counter::default();
+        counter.inc_by(5);
+        assert_eq!(5, counter.get_and_reset());
+        assert_eq!(0, counter.get());
+
+        counter.inc();
+        assert_eq!(1, counter.get_and_reset());
+    }
+
+    #[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
+    #[test]
+    fn inc_by_returning_under_contention() {
+        let counter: Counter = Counter::default();
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let counter = counter.clone();
+                std::thread::spawn(move || {
+                    (0..1000)
+                        .map(|_| counter.inc_by_returning(1))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        let mut all = vec![];
+        for thread in threads {
+            let returned = thread.join().unwrap();
+            assert!(returned.windows(2).all(|w| w[0] < w[1]));
+            all.extend(returned);
+        }
+        all.sort_unstable();
+        assert_eq!((1..=4000).collect::<Vec<_>>(), all);
+
+        let counter = Counter::<f64, AtomicU64>::default();
+        assert_eq!(1.5, counter.inc_by_returning(1.5));
+        assert_eq!(2.0, counter.inc_by_returning(0.5));
+    }
+
+    #[test]
+    fn inc_by_returning_wraps_around() {
+        let counter = Counter::<u32, AtomicU32>::default();
+        assert_eq!(u32::MAX, counter.inc_by_returning(u32::MAX));
+        assert_eq!(0, counter.inc_by_returning(1));
+        assert_eq!(1, counter.inc_by_returning(1));
+
+        let counter = Counter::<u128, LockedU128>::default();
+        assert_eq!(u128::MAX, counter.inc_by_returning(u128::MAX));
+        assert_eq!(1, counter.inc_by_returning(2));
+    }
+
     #[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
     #[test]
     fn f64_stored_in_atomic_u64() {
@@ -187,7 +522,7 @@ mod tests {
                 // Map infinite, subnormal and NaN to 0.0.
                 .map(|f| if f.is_normal() { f } else { 0.0 })
                 .collect();
-            let sum = fs.iter().sum();
+            let sum: f64 = fs.iter().sum();
             let counter = Counter::<f64, AtomicU64>::default();
             for f in fs {
                 counter.inc_by(f);
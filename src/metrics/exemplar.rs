@@ -3,21 +3,54 @@
 //! See [`CounterWithExemplar`] and [`HistogramWithExemplars`] for details.
 
 use super::counter::{self, Counter};
-use super::histogram::Histogram;
+use super::histogram::{Buckets, Histogram};
 use owning_ref::OwningRef;
+use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
 #[cfg(any(target_arch = "mips", target_arch = "powerpc"))]
 use std::sync::atomic::AtomicU32;
 #[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
 use std::sync::atomic::AtomicU64;
 use std::sync::{Arc, RwLock, RwLockReadGuard};
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Exemplar<S, V> {
     pub(crate) label_set: S,
     pub(crate) value: V,
 }
 
+impl<S, V> Exemplar<S, V> {
+    /// The label set of the [`Exemplar`], e.g. referencing a trace.
+    pub fn label_set(&self) -> &S {
+        &self.label_set
+    }
+
+    /// The observed value the [`Exemplar`] was recorded with.
+    pub fn value(&self) -> &V {
+        &self.value
+    }
+}
+
+/// The label set of an [`Exemplar`] referencing the current
+/// [`tracing`](https://docs.rs/tracing) span, i.e. `span_id` with the span's
+/// ID in hex, or `None` outside of a span or without a `tracing` subscriber.
+///
+/// Requires the `tracing` feature, see e.g.
+/// [`CounterWithExemplar::inc_with_current_trace_exemplar`]. Note that the ID
+/// of a `tracing` span is assigned by the subscriber and is only unique within
+/// the process. To reference a distributed trace, e.g. by its OpenTelemetry
+/// trace ID, extract the ID from the span context and pass it as a label set
+/// of its own, e.g. via [`HistogramWithExemplars::observe_with`].
+#[cfg(feature = "tracing")]
+pub fn current_span_exemplar() -> Option<Vec<(String, String)>> {
+    let id = tracing::Span::current().id()?;
+    Some(vec![(
+        "span_id".to_string(),
+        format!("{:016x}", id.into_u64()),
+    )])
+}
+
 /////////////////////////////////////////////////////////////////////////////////
 // Counter
 
@@ -31,13 +64,11 @@ pub struct Exemplar<S, V> {
 /// let _value: (u64, _) = counter_with_exemplar.get();
 /// ```
 #[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
-#[derive(Debug)]
 pub struct CounterWithExemplar<S, N = u64, A = AtomicU64> {
     pub(crate) inner: Arc<RwLock<CounterWithExemplarInner<S, N, A>>>,
 }
 
 #[cfg(any(target_arch = "mips", target_arch = "powerpc"))]
-#[derive(Debug)]
 pub struct CounterWithExemplar<S, N = u32, A = AtomicU32> {
     pub(crate) inner: Arc<RwLock<CounterWithExemplarInner<S, N, A>>>,
 }
@@ -50,12 +81,38 @@ impl<S, N, A> Clone for CounterWithExemplar<S, N, A> {
     }
 }
 
-#[derive(Debug)]
 pub struct CounterWithExemplarInner<S, N, A> {
     pub(crate) exemplar: Option<Exemplar<S, N>>,
     pub(crate) counter: Counter<N, A>,
 }
 
+impl<S, N, A> std::fmt::Debug for CounterWithExemplar<S, N, A>
+where
+    S: std::fmt::Debug,
+    N: std::fmt::Debug,
+    A: counter::Atomic<N>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CounterWithExemplar")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl<S, N, A> std::fmt::Debug for CounterWithExemplarInner<S, N, A>
+where
+    S: std::fmt::Debug,
+    N: std::fmt::Debug,
+    A: counter::Atomic<N>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CounterWithExemplarInner")
+            .field("exemplar", &self.exemplar)
+            .field("counter", &self.counter)
+            .finish()
+    }
+}
+
 impl<S, N, A: Default> Default for CounterWithExemplar<S, N, A> {
     fn default() -> Self {
         Self {
@@ -84,6 +141,21 @@ impl<S, N: Clone, A: counter::Atomic<N>> CounterWithExemplar<S, N, A> {
         inner.counter.inc_by(v)
     }
 
+    /// Increase the [`CounterWithExemplar`] by 1, updating the [`Exemplar`]
+    /// with the [`current_span_exemplar`] if within a span, returning the
+    /// previous value. Outside of a span, the [`Exemplar`] is cleared, same as
+    /// [`CounterWithExemplar::inc_by`] without label set.
+    ///
+    /// Requires the `tracing` feature.
+    #[cfg(feature = "tracing")]
+    pub fn inc_with_current_trace_exemplar(&self) -> N
+    where
+        S: From<Vec<(String, String)>>,
+        N: From<u8>,
+    {
+        self.inc_by(N::from(1), current_span_exemplar().map(S::from))
+    }
+
     /// Get the current value of the [`CounterWithExemplar`] as well as its
     /// [`Exemplar`] if any.
     pub fn get(&self) -> (N, RwLockGuardedCounterWithExemplar<S, N, A>) {
@@ -93,6 +165,29 @@ impl<S, N: Clone, A: counter::Atomic<N>> CounterWithExemplar<S, N, A> {
         (value, exemplar)
     }
 
+    /// Get a copy of the current value of the [`CounterWithExemplar`] and its
+    /// [`Exemplar`] if any.
+    ///
+    /// Both are read under the same lock, thus the [`Exemplar`] is the one
+    /// recorded by the last increment included in the value.
+    ///
+    /// ```
+    /// # use prometheus_client::metrics::exemplar::CounterWithExemplar;
+    /// let counter = CounterWithExemplar::<Vec<(String, String)>>::default();
+    /// counter.inc_by(2, Some(vec![("trace_id".to_string(), "42".to_string())]));
+    ///
+    /// let (value, exemplar) = counter.snapshot();
+    /// assert_eq!(2, value);
+    /// assert_eq!(&2, exemplar.unwrap().value());
+    /// ```
+    pub fn snapshot(&self) -> (N, Option<Exemplar<S, N>>)
+    where
+        S: Clone,
+    {
+        let inner = self.inner.read().expect("Lock not to be poisoned.");
+        (inner.counter.get(), inner.exemplar.clone())
+    }
+
     /// Exposes the inner atomic type of the [`CounterWithExemplar`].
     ///
     /// This should only be used for advanced use-cases which are not directly
@@ -138,35 +233,410 @@ impl<S> Clone for HistogramWithExemplars<S> {
 
 #[derive(Debug)]
 pub struct HistogramWithExemplarsInner<S> {
+    /// The exemplar exposed for each bucket.
     pub(crate) exemplars: HashMap<usize, Exemplar<S, f64>>,
     pub(crate) histogram: Histogram,
+    policy: ExemplarPolicy,
+    /// State of [`ExemplarPolicy::Reservoir`] per bucket.
+    reservoirs: HashMap<usize, Reservoir<S>>,
+    rng: XorShift,
+}
+
+/// Policy deciding which [`Exemplar`] a [`HistogramWithExemplars`] retains per
+/// bucket, see [`HistogramWithExemplars::with_policy`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExemplarPolicy {
+    /// Retain the exemplar of the most recent observation.
+    ///
+    /// Stores a single exemplar per bucket.
+    #[default]
+    Latest,
+    /// Retain the exemplar of the largest observation, e.g. to surface tail
+    /// latency outliers.
+    ///
+    /// Stores a single exemplar per bucket.
+    Largest,
+    /// Retain a uniform random sample of `n` exemplars of all observations,
+    /// exposing the largest among them.
+    ///
+    /// Stores up to `n` exemplars and a counter per bucket.
+    Reservoir(usize),
+}
+
+/// The samples of [`ExemplarPolicy::Reservoir`] for a single bucket, next to
+/// the exposed one in [`HistogramWithExemplarsInner::exemplars`].
+#[derive(Debug)]
+struct Reservoir<S> {
+    /// Number of observations with an exemplar so far.
+    seen: u64,
+    others: Vec<Exemplar<S, f64>>,
 }
 
 impl<S> HistogramWithExemplars<S> {
-    pub fn new(buckets: impl Iterator<Item = f64>) -> Self {
+    pub fn new(buckets: impl Buckets) -> Self {
         Self {
             inner: Arc::new(RwLock::new(HistogramWithExemplarsInner {
                 exemplars: Default::default(),
                 histogram: Histogram::new(buckets),
+                policy: ExemplarPolicy::default(),
+                reservoirs: Default::default(),
+                rng: XorShift::new(),
             })),
         }
     }
 
+    /// Set the [`ExemplarPolicy`] deciding which exemplar to retain per bucket.
+    /// Defaults to [`ExemplarPolicy::Latest`].
+    ///
+    /// ```
+    /// # use prometheus_client::metrics::exemplar::{ExemplarPolicy, HistogramWithExemplars};
+    /// # use prometheus_client::metrics::histogram::exponential_buckets;
+    /// let histogram = HistogramWithExemplars::new(exponential_buckets(1.0, 2.0, 10))
+    ///     .with_policy(ExemplarPolicy::Largest);
+    /// histogram.observe(600.0, Some(vec![("trace_id".to_string(), "slow".to_string())]));
+    /// histogram.observe(520.0, Some(vec![("trace_id".to_string(), "fast".to_string())]));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics on [`ExemplarPolicy::Reservoir`] with a size of `0`.
+    pub fn with_policy(self, policy: ExemplarPolicy) -> Self {
+        assert!(
+            policy != ExemplarPolicy::Reservoir(0),
+            "Expected reservoir size to be at least 1."
+        );
+        self.inner.write().expect("Lock not to be poisoned.").policy = policy;
+        self
+    }
+
     pub fn observe(&self, v: f64, label_set: Option<S>) {
         let mut inner = self.inner.write().expect("Lock not to be poisoned.");
         let bucket = inner.histogram.observe_and_bucket(v);
         if let (Some(bucket), Some(label_set)) = (bucket, label_set) {
-            inner.exemplars.insert(
-                bucket,
-                Exemplar {
-                    label_set,
-                    value: v,
-                },
-            );
+            inner.retain_exemplar(bucket, v, || Some(label_set));
         }
     }
 
+    /// Like [`HistogramWithExemplars::observe`], but constructs the label set
+    /// of the exemplar only if the exemplar is retained as per the
+    /// [`ExemplarPolicy`], e.g. to avoid formatting a trace ID on every
+    /// observation.
+    ///
+    /// With [`ExemplarPolicy::Reservoir`], an observation for which
+    /// `label_set` is not called counts towards the sampled observations, even
+    /// if `label_set` would have returned `None`.
+    ///
+    /// ```
+    /// # use prometheus_client::metrics::exemplar::{ExemplarPolicy, HistogramWithExemplars};
+    /// # use prometheus_client::metrics::histogram::exponential_buckets;
+    /// let histogram = HistogramWithExemplars::new(exponential_buckets(1.0, 2.0, 10))
+    ///     .with_policy(ExemplarPolicy::Largest);
+    /// histogram.observe(600.0, Some(vec![("trace_id".to_string(), "slow".to_string())]));
+    ///
+    /// // Not the largest observation, thus the closure is not called.
+    /// histogram.observe_with(520.0, || -> Option<Vec<(String, String)>> {
+    ///     unreachable!()
+    /// });
+    /// ```
+    pub fn observe_with<F: FnOnce() -> Option<S>>(&self, v: f64, label_set: F) {
+        let mut inner = self.inner.write().expect("Lock not to be poisoned.");
+        if let Some(bucket) = inner.histogram.observe_and_bucket(v) {
+            inner.retain_exemplar(bucket, v, label_set);
+        }
+    }
+
+    /// Observe the given value, retaining the [`current_span_exemplar`] as
+    /// the [`Exemplar`] as per the [`ExemplarPolicy`] if within a span. Same
+    /// as [`HistogramWithExemplars::observe`] without label set otherwise.
+    ///
+    /// Requires the `tracing` feature.
+    #[cfg(feature = "tracing")]
+    pub fn observe_with_current_trace_exemplar(&self, v: f64)
+    where
+        S: From<Vec<(String, String)>>,
+    {
+        self.observe_with(v, || current_span_exemplar().map(S::from))
+    }
+
     pub(crate) fn inner(&self) -> RwLockReadGuard<HistogramWithExemplarsInner<S>> {
         self.inner.read().expect("Lock not to be poisoned.")
     }
 }
+
+impl<S> HistogramWithExemplarsInner<S> {
+    /// Retain an exemplar with the given value as per the [`ExemplarPolicy`],
+    /// calling `label_set` only if the exemplar is retained.
+    fn retain_exemplar<F: FnOnce() -> Option<S>>(
+        &mut self,
+        bucket: usize,
+        value: f64,
+        label_set: F,
+    ) {
+        let exemplar = |label_set: F| label_set().map(|label_set| Exemplar { label_set, value });
+
+        let size = match self.policy {
+            ExemplarPolicy::Latest => {
+                if let Some(exemplar) = exemplar(label_set) {
+                    self.exemplars.insert(bucket, exemplar);
+                }
+                return;
+            }
+            ExemplarPolicy::Largest => {
+                let is_largest = match self.exemplars.get(&bucket) {
+                    // Replace a `NaN` exemplar, as it is not exposed anyways.
+                    Some(e) => e.value.is_nan() || value > e.value,
+                    None => true,
+                };
+                if let Some(exemplar) = is_largest.then(|| exemplar(label_set)).flatten() {
+                    self.exemplars.insert(bucket, exemplar);
+                }
+                return;
+            }
+            ExemplarPolicy::Reservoir(size) => size,
+        };
+
+        let reservoir = self.reservoirs.entry(bucket).or_insert_with(|| Reservoir {
+            seen: 0,
+            others: Vec::new(),
+        });
+        reservoir.seen += 1;
+
+        // Algorithm R: Retain the first `size` exemplars, thereafter replace a
+        // random one with probability `size / seen`. Index `0` refers to the
+        // exposed exemplar.
+        let index = if reservoir.seen <= size as u64 {
+            reservoir.seen as usize - 1
+        } else {
+            (self.rng.next() % reservoir.seen) as usize
+        };
+        if index >= size {
+            return;
+        }
+        // Only construct the exemplar once it is known to be retained. An
+        // observation without an exemplar does not count as seen.
+        let exemplar = match exemplar(label_set) {
+            Some(exemplar) => exemplar,
+            None => {
+                reservoir.seen -= 1;
+                return;
+            }
+        };
+        match index {
+            0 => {
+                self.exemplars.insert(bucket, exemplar);
+            }
+            i if i <= reservoir.others.len() => reservoir.others[i - 1] = exemplar,
+            _ => reservoir.others.push(exemplar),
+        }
+
+        // Expose the largest retained exemplar.
+        let exposed = self
+            .exemplars
+            .get_mut(&bucket)
+            .expect("First exemplar of a bucket to be exposed.");
+        if let Some(largest) = reservoir
+            .others
+            .iter_mut()
+            .filter(|e| e.value > exposed.value)
+            .max_by(|a, b| a.value.total_cmp(&b.value))
+        {
+            std::mem::swap(exposed, largest);
+        }
+    }
+}
+
+/// Minimal xorshift pseudo random number generator for
+/// [`ExemplarPolicy::Reservoir`], seeded from the randomly keyed [`RandomState`].
+#[derive(Debug)]
+struct XorShift(u64);
+
+impl XorShift {
+    fn new() -> Self {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(0);
+        // Xorshift requires a non-zero state.
+        XorShift(hasher.finish() | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::histogram::exponential_buckets;
+
+    fn exposed(histogram: &HistogramWithExemplars<u32>) -> Vec<(usize, u32, f64)> {
+        let inner = histogram.inner();
+        let mut exposed: Vec<_> = inner
+            .exemplars
+            .iter()
+            .map(|(bucket, e)| (*bucket, e.label_set, e.value))
+            .collect();
+        exposed.sort_by_key(|(bucket, _, _)| *bucket);
+        exposed
+    }
+
+    #[test]
+    fn counter_snapshot() {
+        let counter = CounterWithExemplar::<u32>::default();
+        assert_eq!((0, None), counter.snapshot());
+
+        counter.inc_by(1, Some(1));
+        counter.inc_by(2, Some(2));
+        let (value, exemplar) = counter.snapshot();
+        assert_eq!(3, value);
+        assert_eq!(
+            Some(Exemplar {
+                label_set: 2,
+                value: 2
+            }),
+            exemplar
+        );
+
+        counter.inc_by(1, None);
+        assert_eq!((4, None), counter.snapshot());
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn current_trace_exemplar() {
+        use std::sync::Mutex;
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata};
+        use tracing_core::span::Current;
+
+        /// Subscriber tracking the current span only, assigning the same ID to
+        /// all spans.
+        #[derive(Default)]
+        struct CurrentSpan(Mutex<Option<&'static Metadata<'static>>>);
+
+        impl tracing::Subscriber for CurrentSpan {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                *self.0.lock().unwrap() = Some(span.metadata());
+                Id::from_u64(42)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, _: &Event<'_>) {}
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {
+                *self.0.lock().unwrap() = None;
+            }
+            fn current_span(&self) -> Current {
+                match *self.0.lock().unwrap() {
+                    Some(metadata) => Current::new(Id::from_u64(42), metadata),
+                    None => Current::none(),
+                }
+            }
+        }
+
+        let counter = CounterWithExemplar::<Vec<(String, String)>>::default();
+        let histogram = HistogramWithExemplars::<Vec<(String, String)>>::new([1.0].into_iter());
+        let exemplar = Some(vec![(
+            "span_id".to_string(),
+            "000000000000002a".to_string(),
+        )]);
+
+        tracing::subscriber::with_default(CurrentSpan::default(), || {
+            tracing::info_span!("request").in_scope(|| {
+                counter.inc_with_current_trace_exemplar();
+                histogram.observe_with_current_trace_exemplar(0.5);
+            });
+            assert_eq!(exemplar, counter.snapshot().1.map(|e| e.label_set));
+            assert_eq!(
+                exemplar.as_ref(),
+                histogram.inner().exemplars.get(&0).map(|e| &e.label_set)
+            );
+
+            counter.inc_with_current_trace_exemplar();
+        });
+        assert_eq!((2, None), counter.snapshot());
+    }
+
+    #[test]
+    fn latest_policy() {
+        let histogram = HistogramWithExemplars::new(exponential_buckets(1.0, 2.0, 10));
+        histogram.observe(600.0, Some(1));
+        histogram.observe(520.0, Some(2));
+        histogram.observe(530.0, None);
+        assert_eq!(vec![(10, 2, 520.0)], exposed(&histogram));
+    }
+
+    #[test]
+    fn largest_policy() {
+        let histogram = HistogramWithExemplars::new(exponential_buckets(1.0, 2.0, 10))
+            .with_policy(ExemplarPolicy::Largest);
+        histogram.observe(600.0, Some(1));
+        histogram.observe(520.0, Some(2));
+        histogram.observe(3.0, Some(3));
+        assert_eq!(vec![(2, 3, 3.0), (10, 1, 600.0)], exposed(&histogram));
+    }
+
+    #[test]
+    fn reservoir_policy() {
+        let histogram = HistogramWithExemplars::new(exponential_buckets(1.0, 2.0, 10))
+            .with_policy(ExemplarPolicy::Reservoir(3));
+        for i in 0..100 {
+            histogram.observe(600.0 + i as f64, Some(i));
+        }
+
+        let inner = histogram.inner();
+        let others = &inner.reservoirs[&10].others;
+        assert_eq!(2, others.len());
+        let exposed = &inner.exemplars[&10];
+        assert!(others.iter().all(|e| e.value < exposed.value));
+        assert_eq!(600.0 + exposed.label_set as f64, exposed.value);
+    }
+
+    #[test]
+    fn observe_with_constructs_retained_exemplars_only() {
+        use std::cell::Cell;
+
+        for (policy, expected_calls) in [
+            (ExemplarPolicy::Latest, 100),
+            // Only increasing values are the largest so far.
+            (ExemplarPolicy::Largest, 50),
+            (ExemplarPolicy::Reservoir(3), 3),
+        ] {
+            let histogram =
+                HistogramWithExemplars::new(exponential_buckets(1.0, 2.0, 10)).with_policy(policy);
+            let calls = Cell::new(0);
+            for i in 0..100u32 {
+                let v = if i % 2 == 0 { 600.0 + i as f64 } else { 600.0 };
+                histogram.observe_with(v, || {
+                    calls.set(calls.get() + 1);
+                    Some(i)
+                });
+            }
+
+            assert_eq!(100, histogram.inner().histogram.count());
+            match policy {
+                ExemplarPolicy::Reservoir(_) => assert!(calls.get() >= expected_calls),
+                _ => assert_eq!(expected_calls, calls.get(), "{:?}", policy),
+            }
+            assert!(calls.get() < 100 || policy == ExemplarPolicy::Latest);
+        }
+
+        let histogram = HistogramWithExemplars::<u32>::new(exponential_buckets(1.0, 2.0, 10))
+            .with_policy(ExemplarPolicy::Reservoir(3));
+        histogram.observe_with(600.0, || None);
+        assert!(exposed(&histogram).is_empty());
+        assert_eq!(0, histogram.inner().reservoirs[&10].seen);
+    }
+
+    #[test]
+    #[should_panic(expected = "reservoir size")]
+    fn empty_reservoir() {
+        let _ = HistogramWithExemplars::<u32>::new(exponential_buckets(1.0, 2.0, 10))
+            .with_policy(ExemplarPolicy::Reservoir(0));
+    }
+}
@@ -2,10 +2,17 @@
 //!
 //! See [`Family`] for details.
 
+use super::counter::{self, Counter};
+use super::gauge::{self, Gauge};
+use super::histogram::{Histogram, HistogramTimer};
 use super::{MetricType, TypedMetric};
 use owning_ref::OwningRef;
+#[cfg(feature = "parking_lot")]
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::collections::HashMap;
-use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::sync::Arc;
+#[cfg(not(feature = "parking_lot"))]
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 /// Representation of the OpenMetrics *MetricFamily* data type.
 ///
@@ -96,6 +103,13 @@ use std::sync::{Arc, RwLock, RwLockReadGuard};
 /// #                "# EOF\n";
 /// # assert_eq!(expected, String::from_utf8(buffer).unwrap());
 /// ```
+///
+/// # Generic over the metric
+///
+/// Metrics are constructed in place by the [`MetricConstructor`] and never
+/// cloned. [`Family::get_or_create`] hands out a guard borrowing the metric.
+/// Thus the metric type does not need to implement [`Clone`], while
+/// [`Family`] itself is cheaply cloneable, sharing its metrics.
 // TODO: Consider exposing hash algorithm.
 #[derive(Debug)]
 pub struct Family<S, M, C = fn() -> M> {
@@ -157,6 +171,67 @@ impl<M, F: Fn() -> M> MetricConstructor<M> for F {
     }
 }
 
+/// A constructor for creating new metrics in a [`Family`] based on the label
+/// set of the metric, e.g. to choose histogram buckets per endpoint.
+///
+/// Implemented for every [`MetricConstructor`], ignoring the label set, and
+/// for closures passed to [`Family::new_with_constructor_labeled`]. For
+/// custom metric types implement it on a custom constructor instead.
+///
+/// ```
+/// # use prometheus_client::metrics::family::{Family, LabeledMetricConstructor};
+/// # use prometheus_client::metrics::histogram::Histogram;
+/// struct EndpointBuckets;
+///
+/// impl LabeledMetricConstructor<String, Histogram> for EndpointBuckets {
+///     fn new_metric_for(&self, endpoint: &String) -> Histogram {
+///         match endpoint.as_str() {
+///             "/slow" => Histogram::new([1.0, 10.0].into_iter()),
+///             _ => Histogram::new([0.01, 0.1].into_iter()),
+///         }
+///     }
+/// }
+///
+/// let family = Family::<String, Histogram, _>::new_with_constructor(EndpointBuckets);
+/// # family.get_or_create(&"/slow".to_string());
+/// ```
+pub trait LabeledMetricConstructor<S, M> {
+    fn new_metric_for(&self, label_set: &S) -> M;
+}
+
+impl<S, M, C: MetricConstructor<M>> LabeledMetricConstructor<S, M> for C {
+    fn new_metric_for(&self, _label_set: &S) -> M {
+        self.new_metric()
+    }
+}
+
+/// Constructor passing the label set to a closure, see
+/// [`Family::new_with_constructor_labeled`].
+#[derive(Clone, Debug)]
+pub struct LabeledConstructor<F>(F);
+
+impl<S, F: Fn(&S) -> Histogram> LabeledMetricConstructor<S, Histogram> for LabeledConstructor<F> {
+    fn new_metric_for(&self, label_set: &S) -> Histogram {
+        (self.0)(label_set)
+    }
+}
+
+impl<S, N, A, F: Fn(&S) -> Counter<N, A>> LabeledMetricConstructor<S, Counter<N, A>>
+    for LabeledConstructor<F>
+{
+    fn new_metric_for(&self, label_set: &S) -> Counter<N, A> {
+        (self.0)(label_set)
+    }
+}
+
+impl<S, N, A, F: Fn(&S) -> Gauge<N, A>> LabeledMetricConstructor<S, Gauge<N, A>>
+    for LabeledConstructor<F>
+{
+    fn new_metric_for(&self, label_set: &S) -> Gauge<N, A> {
+        (self.0)(label_set)
+    }
+}
+
 impl<S: Clone + std::hash::Hash + Eq, M: Default> Default for Family<S, M> {
     fn default() -> Self {
         Self {
@@ -179,7 +254,9 @@ impl<S: Clone + std::hash::Hash + Eq, M, C> Family<S, M, C> {
     /// [`Family`] to construct a
     /// [`Histogram`](crate::metrics::histogram::Histogram) with custom buckets
     /// (see example below). For such case one can use this method. For more
-    /// involved constructors see [`MetricConstructor`].
+    /// involved constructors see [`MetricConstructor`], for constructors
+    /// depending on the label set see
+    /// [`Family::new_with_constructor_labeled`].
     ///
     /// ```
     /// # use prometheus_client::metrics::family::Family;
@@ -196,7 +273,32 @@ impl<S: Clone + std::hash::Hash + Eq, M, C> Family<S, M, C> {
     }
 }
 
-impl<S: Clone + std::hash::Hash + Eq, M, C: MetricConstructor<M>> Family<S, M, C> {
+impl<S: Clone + std::hash::Hash + Eq, M, F> Family<S, M, LabeledConstructor<F>>
+where
+    LabeledConstructor<F>: LabeledMetricConstructor<S, M>,
+{
+    /// Like [`Family::new_with_constructor`], but passing the label set to
+    /// the constructor, e.g. to use different buckets per endpoint. Supported
+    /// for [`Histogram`], [`Counter`] and [`Gauge`], see
+    /// [`LabeledMetricConstructor`] for other metric types.
+    ///
+    /// ```
+    /// # use prometheus_client::metrics::family::Family;
+    /// # use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+    /// let family = Family::<String, Histogram, _>::new_with_constructor_labeled(|endpoint| {
+    ///     match endpoint.as_str() {
+    ///         "/slow" => Histogram::new(exponential_buckets(1.0, 2.0, 10)),
+    ///         _ => Histogram::new(exponential_buckets(0.001, 2.0, 10)),
+    ///     }
+    /// });
+    /// family.get_or_create(&"/slow".to_string()).observe(100.0);
+    /// ```
+    pub fn new_with_constructor_labeled(constructor: F) -> Self {
+        Self::new_with_constructor(LabeledConstructor(constructor))
+    }
+}
+
+impl<S: Clone + std::hash::Hash + Eq, M, C: LabeledMetricConstructor<S, M>> Family<S, M, C> {
     /// Access a metric with the given label set, creating it if one does not
     /// yet exist.
     ///
@@ -214,32 +316,385 @@ impl<S: Clone + std::hash::Hash + Eq, M, C: MetricConstructor<M>> Family<S, M, C
     /// // calls.
     /// family.get_or_create(&vec![("method".to_owned(), "GET".to_owned())]).inc();
     /// ```
+    ///
+    /// Panics if the lock of the [`Family`] is poisoned, see
+    /// [`Family::try_get_or_create`].
     pub fn get_or_create(&self, label_set: &S) -> OwningRef<RwLockReadGuard<HashMap<S, M>>, M> {
-        let read_guard = self.metrics.read().expect("Lock not to be poisoned.");
+        self.try_get_or_create(label_set)
+            .expect("Lock not to be poisoned.")
+    }
+
+    /// Like [`Family::get_or_create`], but returns an error instead of
+    /// panicking if the lock of the [`Family`] is poisoned.
+    ///
+    /// A [`Family`] guards its metrics with a [`std::sync::RwLock`], taken for
+    /// writing only while creating the metric for a new label set. The lock is
+    /// poisoned if a thread panics while holding it for writing, i.e. if the
+    /// [`MetricConstructor`] or the [`Hash`](std::hash::Hash) or [`Eq`]
+    /// implementation of the label set panics. A poisoned [`Family`] stays
+    /// poisoned, thus every subsequent call returns an error.
+    ///
+    /// With the `parking_lot` feature enabled, a [`Family`] uses a
+    /// `parking_lot::RwLock` instead, which does not support poisoning, thus
+    /// this method never fails.
+    ///
+    /// ```
+    /// # use prometheus_client::metrics::counter::{Atomic, Counter};
+    /// # use prometheus_client::metrics::family::Family;
+    /// #
+    /// let family = Family::<Vec<(String, String)>, Counter>::default();
+    /// match family.try_get_or_create(&vec![("method".to_owned(), "GET".to_owned())]) {
+    ///     Ok(counter) => {
+    ///         counter.inc();
+    ///     }
+    ///     Err(e) => eprintln!("Failed to record request: {}", e),
+    /// };
+    /// ```
+    pub fn try_get_or_create(
+        &self,
+        label_set: &S,
+    ) -> Result<OwningRef<RwLockReadGuard<HashMap<S, M>>, M>, PoisonedError> {
+        let read_guard = self.try_read()?;
         if let Ok(metric) =
             OwningRef::new(read_guard).try_map(|metrics| metrics.get(label_set).ok_or(()))
         {
-            return metric;
+            return Ok(metric);
         }
 
-        let mut write_guard = self.metrics.write().unwrap();
-        write_guard.insert(label_set.clone(), self.constructor.new_metric());
+        // Only clone the label set when inserting a new metric. Another thread
+        // might have inserted a metric for the same label set in between
+        // dropping the read and acquiring the write lock, in which case the
+        // existing metric is kept.
+        let mut write_guard = self.try_write()?;
+        write_guard
+            .entry(label_set.clone())
+            .or_insert_with(|| self.constructor.new_metric_for(label_set));
 
         drop(write_guard);
 
-        let read_guard = self.metrics.read().unwrap();
-        OwningRef::new(read_guard).map(|metrics| {
+        let read_guard = self.try_read()?;
+        Ok(OwningRef::new(read_guard).map(|metrics| {
             metrics
                 .get(label_set)
                 .expect("Metric to exist after creating it.")
-        })
+        }))
+    }
+
+    /// Run `f` with the metric with the given label set, creating it if it
+    /// does not yet exist, returning the result of `f`.
+    ///
+    /// Like [`Family::get_or_create`], but without exposing the guard. `f`
+    /// runs while holding the read lock of the [`Family`]. Thus `f` must not
+    /// call back into the same [`Family`], as that may deadlock, e.g. once
+    /// another thread waits to create a metric.
+    ///
+    /// ```
+    /// # use prometheus_client::metrics::counter::Counter;
+    /// # use prometheus_client::metrics::family::Family;
+    /// #
+    /// let family = Family::<Vec<(String, String)>, Counter>::default();
+    /// let get = vec![("method".to_owned(), "GET".to_owned())];
+    ///
+    /// let previous = family.with(&get, |counter| {
+    ///     counter.inc();
+    ///     counter.get()
+    /// });
+    /// assert_eq!(1, previous);
+    /// ```
+    pub fn with<R>(&self, label_set: &S, f: impl FnOnce(&M) -> R) -> R {
+        f(&self.get_or_create(label_set))
+    }
+
+    /// Like [`Family::get_or_create`], falling back to the `default` label set
+    /// if no label set is given, e.g. when a label could not be determined.
+    ///
+    /// ```
+    /// # use prometheus_client::metrics::counter::{Atomic, Counter};
+    /// # use prometheus_client::metrics::family::Family;
+    /// #
+    /// let family = Family::<Vec<(String, String)>, Counter>::default();
+    /// let unknown = vec![("status".to_owned(), "unknown".to_owned())];
+    ///
+    /// // E.g. the connection was dropped before a response was sent.
+    /// let status: Option<&Vec<(String, String)>> = None;
+    /// family.get_or_create_or_default(status, &unknown).inc();
+    ///
+    /// assert_eq!(1, family.get_or_create(&unknown).get());
+    /// ```
+    pub fn get_or_create_or_default(
+        &self,
+        label_set: Option<&S>,
+        default: &S,
+    ) -> OwningRef<RwLockReadGuard<HashMap<S, M>>, M> {
+        self.get_or_create(label_set.unwrap_or(default))
+    }
+
+    /// The label sets and the current state of all metrics of the [`Family`],
+    /// taken under a single read lock, e.g. to return them as plain data from
+    /// an RPC.
+    ///
+    /// Clones every label set of the [`Family`] and thus allocates
+    /// proportionally to its cardinality. Metrics can not be created while
+    /// the snapshot is taken.
+    ///
+    /// ```
+    /// # use prometheus_client::metrics::counter::Counter;
+    /// # use prometheus_client::metrics::family::{Family, Snapshot};
+    /// #
+    /// let family = Family::<Vec<(String, String)>, Counter>::default();
+    /// let get = vec![("method".to_owned(), "GET".to_owned())];
+    /// family.inc(&get);
+    ///
+    /// assert_eq!(vec![(get, Snapshot::Counter(1))], family.snapshot());
+    /// ```
+    pub fn snapshot(&self) -> Vec<(S, Snapshot<M::Value>)>
+    where
+        M: SnapshotMetric,
+    {
+        self.read()
+            .iter()
+            .map(|(label_set, metric)| (label_set.clone(), metric.snapshot()))
+            .collect()
+    }
+
+    /// Remove the metric with the given label set, e.g. once the peer it
+    /// tracks disconnected. Returns whether the metric existed.
+    ///
+    /// Prometheus marks a series as stale once it is missing from a scrape,
+    /// thus removing the metric ends the series without Prometheus
+    /// extrapolating it further. The text formats can not carry a staleness
+    /// marker themselves, a `NaN` value being ingested as a regular sample.
+    ///
+    /// ```
+    /// # use prometheus_client::metrics::counter::Counter;
+    /// # use prometheus_client::metrics::family::Family;
+    /// #
+    /// let family = Family::<Vec<(String, String)>, Counter>::default();
+    /// let peer = vec![("peer".to_owned(), "a".to_owned())];
+    /// family.inc(&peer);
+    ///
+    /// assert!(family.remove(&peer));
+    /// assert!(!family.remove(&peer));
+    /// ```
+    pub fn remove(&self, label_set: &S) -> bool {
+        self.try_write()
+            .expect("Lock not to be poisoned.")
+            .remove(label_set)
+            .is_some()
     }
 
     pub(crate) fn read(&self) -> RwLockReadGuard<HashMap<S, M>> {
-        self.metrics.read().unwrap()
+        self.try_read().expect("Lock not to be poisoned.")
+    }
+
+    #[cfg(not(feature = "parking_lot"))]
+    fn try_read(&self) -> Result<RwLockReadGuard<HashMap<S, M>>, PoisonedError> {
+        self.metrics.read().map_err(|_| PoisonedError(()))
+    }
+
+    #[cfg(not(feature = "parking_lot"))]
+    fn try_write(&self) -> Result<RwLockWriteGuard<HashMap<S, M>>, PoisonedError> {
+        self.metrics.write().map_err(|_| PoisonedError(()))
+    }
+
+    #[cfg(feature = "parking_lot")]
+    fn try_read(&self) -> Result<RwLockReadGuard<HashMap<S, M>>, PoisonedError> {
+        Ok(self.metrics.read())
+    }
+
+    #[cfg(feature = "parking_lot")]
+    fn try_write(&self) -> Result<RwLockWriteGuard<HashMap<S, M>>, PoisonedError> {
+        Ok(self.metrics.write())
     }
 }
 
+impl<S, N, A, C> Family<S, Counter<N, A>, C>
+where
+    S: Clone + std::hash::Hash + Eq,
+    A: counter::Atomic<N>,
+    C: LabeledMetricConstructor<S, Counter<N, A>>,
+{
+    /// Increase the [`Counter`] with the given label set by 1, creating it if
+    /// it does not yet exist, returning the previous value.
+    ///
+    /// Shorthand for `family.get_or_create(label_set).inc()`.
+    ///
+    /// ```
+    /// # use prometheus_client::metrics::counter::Counter;
+    /// # use prometheus_client::metrics::family::Family;
+    /// #
+    /// let family = Family::<Vec<(String, String)>, Counter>::default();
+    /// family.inc(&vec![("method".to_owned(), "GET".to_owned())]);
+    /// ```
+    pub fn inc(&self, label_set: &S) -> N {
+        self.get_or_create(label_set).inc()
+    }
+
+    /// The label sets and current values of all [`Counter`]s of the
+    /// [`Family`], taken under a single read lock.
+    ///
+    /// Like [`Family::snapshot`], but without wrapping each value in a
+    /// [`Snapshot`]. Clones every label set of the [`Family`].
+    ///
+    /// ```
+    /// # use prometheus_client::metrics::counter::Counter;
+    /// # use prometheus_client::metrics::family::Family;
+    /// #
+    /// let family = Family::<Vec<(String, String)>, Counter>::default();
+    /// let get = vec![("method".to_owned(), "GET".to_owned())];
+    /// family.inc(&get);
+    ///
+    /// assert_eq!(vec![(get, 1)], family.values());
+    /// ```
+    pub fn values(&self) -> Vec<(S, N)> {
+        self.read()
+            .iter()
+            .map(|(label_set, counter)| (label_set.clone(), counter.get()))
+            .collect()
+    }
+}
+
+impl<S, N, A, C> Family<S, Gauge<N, A>, C>
+where
+    S: Clone + std::hash::Hash + Eq,
+    A: gauge::Atomic<N>,
+    C: LabeledMetricConstructor<S, Gauge<N, A>>,
+{
+    /// Increase the [`Gauge`] with the given label set by 1, creating it if it
+    /// does not yet exist, returning the previous value.
+    ///
+    /// Shorthand for `family.get_or_create(label_set).inc()`.
+    pub fn inc(&self, label_set: &S) -> N {
+        self.get_or_create(label_set).inc()
+    }
+}
+
+impl<S, C> Family<S, Histogram, C>
+where
+    S: Clone + std::hash::Hash + Eq,
+    C: LabeledMetricConstructor<S, Histogram>,
+{
+    /// Observe `v` with the [`Histogram`] with the given label set, creating
+    /// it if it does not yet exist.
+    ///
+    /// Shorthand for `family.get_or_create(label_set).observe(v)`.
+    ///
+    /// ```
+    /// # use prometheus_client::metrics::family::Family;
+    /// # use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+    /// #
+    /// let family = Family::<Vec<(String, String)>, Histogram>::new_with_constructor(|| {
+    ///     Histogram::new(exponential_buckets(1.0, 2.0, 10))
+    /// });
+    /// family.observe(&vec![("method".to_owned(), "GET".to_owned())], 4.2);
+    /// ```
+    pub fn observe(&self, label_set: &S, v: f64) {
+        self.get_or_create(label_set).observe(v)
+    }
+
+    /// Starts a [`HistogramTimer`] for the [`Histogram`] with the given label
+    /// set, creating it if it does not yet exist. The elapsed time in seconds
+    /// is observed once the timer is dropped.
+    ///
+    /// The timer does not hold the lock of the [`Family`].
+    ///
+    /// ```
+    /// # use prometheus_client::metrics::family::Family;
+    /// # use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+    /// #
+    /// let family = Family::<Vec<(String, String)>, Histogram>::new_with_constructor(|| {
+    ///     Histogram::new(exponential_buckets(0.001, 2.0, 10))
+    /// });
+    /// {
+    ///     let _timer = family.start_timer(&vec![("route".to_owned(), "/".to_owned())]);
+    ///     // Handle the request.
+    /// }
+    /// ```
+    pub fn start_timer(&self, label_set: &S) -> HistogramTimer {
+        self.get_or_create(label_set).start_timer()
+    }
+}
+
+/// The current state of a metric, see [`Family::snapshot`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Snapshot<N = u64> {
+    Counter(N),
+    Gauge(N),
+    Histogram {
+        sum: f64,
+        count: u64,
+        /// The upper bound of each bucket and its cumulative count, i.e. the
+        /// number of observations less than or equal to the upper bound, the
+        /// last bucket being the `+Inf` bucket.
+        buckets: Vec<(f64, u64)>,
+    },
+}
+
+/// A metric that can report its current state as a [`Snapshot`].
+pub trait SnapshotMetric {
+    /// The value type of the [`Snapshot`].
+    type Value;
+
+    fn snapshot(&self) -> Snapshot<Self::Value>;
+}
+
+impl<N, A: counter::Atomic<N>> SnapshotMetric for Counter<N, A> {
+    type Value = N;
+
+    fn snapshot(&self) -> Snapshot<N> {
+        Snapshot::Counter(self.get())
+    }
+}
+
+impl<N, A: gauge::Atomic<N>> SnapshotMetric for Gauge<N, A> {
+    type Value = N;
+
+    fn snapshot(&self) -> Snapshot<N> {
+        Snapshot::Gauge(self.get())
+    }
+}
+
+impl SnapshotMetric for Histogram {
+    type Value = u64;
+
+    fn snapshot(&self) -> Snapshot<u64> {
+        let (sum, count, buckets) = self.get();
+        let last_bucket = buckets.len() - 1;
+        let buckets = buckets
+            .iter()
+            .enumerate()
+            .scan(0, |cumulative, (i, (upper_bound, count))| {
+                *cumulative += count;
+                let upper_bound = if i == last_bucket {
+                    f64::INFINITY
+                } else {
+                    *upper_bound
+                };
+                Some((upper_bound, *cumulative))
+            })
+            .collect();
+
+        Snapshot::Histogram {
+            sum,
+            count,
+            buckets,
+        }
+    }
+}
+
+/// Error returned by [`Family::try_get_or_create`] if the lock of the
+/// [`Family`] is poisoned.
+#[derive(Debug)]
+pub struct PoisonedError(());
+
+impl std::fmt::Display for PoisonedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "lock of metric family poisoned")
+    }
+}
+
+impl std::error::Error for PoisonedError {}
+
 impl<S, M, C: Clone> Clone for Family<S, M, C> {
     fn clone(&self) -> Self {
         Family {
@@ -251,13 +706,13 @@ impl<S, M, C: Clone> Clone for Family<S, M, C> {
 
 impl<S, M: TypedMetric, C> TypedMetric for Family<S, M, C> {
     const TYPE: MetricType = <M as TypedMetric>::TYPE;
+    const ENCODES_AS_SUMMARY: bool = <M as TypedMetric>::ENCODES_AS_SUMMARY;
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::metrics::counter::Counter;
-    use crate::metrics::histogram::{exponential_buckets, Histogram};
+    use crate::metrics::histogram::exponential_buckets;
 
     #[test]
     fn counter_family() {
@@ -275,6 +730,136 @@ mod tests {
         );
     }
 
+    #[cfg(not(feature = "parking_lot"))]
+    #[test]
+    fn try_get_or_create_on_poisoned_lock() {
+        let family = Family::<u64, Counter, _>::new_with_constructor(|| -> Counter {
+            panic!("constructor panicked")
+        });
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            family.get_or_create(&1);
+        }));
+        assert!(result.is_err());
+
+        assert_eq!(
+            "lock of metric family poisoned",
+            family.try_get_or_create(&2).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn get_or_create_does_not_clone_existing_label_set() {
+        #[derive(Hash, PartialEq, Eq)]
+        struct Labels(u64);
+
+        static CLONES: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+        impl Clone for Labels {
+            fn clone(&self) -> Self {
+                CLONES.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                Labels(self.0)
+            }
+        }
+
+        let family = Family::<Labels, Counter>::default();
+        family.get_or_create(&Labels(1)).inc();
+        assert_eq!(1, CLONES.load(std::sync::atomic::Ordering::Relaxed));
+
+        family.get_or_create(&Labels(1)).inc();
+        family.get_or_create(&Labels(1)).inc();
+        assert_eq!(1, CLONES.load(std::sync::atomic::Ordering::Relaxed));
+        assert_eq!(3, family.get_or_create(&Labels(1)).get());
+    }
+
+    #[test]
+    fn non_clone_metric() {
+        #[derive(Default)]
+        struct NonCloneMetric(std::sync::Mutex<Vec<u64>>);
+
+        let family = Family::<u64, NonCloneMetric>::default();
+        family.get_or_create(&1).0.lock().unwrap().push(42);
+        family.clone().get_or_create(&1).0.lock().unwrap().push(43);
+
+        assert_eq!(vec![42, 43], *family.get_or_create(&1).0.lock().unwrap());
+    }
+
+    #[test]
+    fn get_or_create_or_default() {
+        let family = Family::<Vec<(String, String)>, Counter>::default();
+        let ok = vec![("status".to_string(), "200".to_string())];
+        let unknown = vec![("status".to_string(), "unknown".to_string())];
+
+        family.get_or_create_or_default(Some(&ok), &unknown).inc();
+        family.get_or_create_or_default(None, &unknown).inc();
+        family.get_or_create_or_default(None, &unknown).inc();
+
+        assert_eq!(1, family.get_or_create(&ok).get());
+        assert_eq!(2, family.get_or_create(&unknown).get());
+    }
+
+    #[test]
+    fn inc_and_observe() {
+        let counters = Family::<Vec<(String, String)>, Counter>::default();
+        let labels = vec![("method".to_string(), "GET".to_string())];
+        assert_eq!(0, counters.inc(&labels));
+        assert_eq!(1, counters.inc(&labels));
+        assert_eq!(2, counters.get_or_create(&labels).get());
+
+        let gauges = Family::<Vec<(String, String)>, Gauge>::default();
+        gauges.inc(&labels);
+        assert_eq!(1, gauges.get_or_create(&labels).get());
+
+        let histograms = Family::<Vec<(String, String)>, Histogram>::new_with_constructor(|| {
+            Histogram::new(exponential_buckets(1.0, 2.0, 10))
+        });
+        histograms.observe(&labels, 4.2);
+        assert_eq!(1, histograms.get_or_create(&labels).count());
+    }
+
+    #[test]
+    fn snapshot() {
+        let counters = Family::<Vec<(String, String)>, Counter>::default();
+        assert!(counters.snapshot().is_empty());
+        let get = vec![("method".to_owned(), "GET".to_owned())];
+        let put = vec![("method".to_owned(), "PUT".to_owned())];
+        counters.inc(&get);
+        counters.inc(&get);
+        counters.inc(&put);
+
+        let mut snapshot = counters.snapshot();
+        snapshot.sort_by(|(a, _), (b, _)| a.cmp(b));
+        assert_eq!(
+            vec![
+                (get.clone(), Snapshot::Counter(2)),
+                (put, Snapshot::Counter(1))
+            ],
+            snapshot
+        );
+
+        let gauges = Family::<Vec<(String, String)>, Gauge>::default();
+        gauges.get_or_create(&get).set(3);
+        assert_eq!(vec![(get.clone(), Snapshot::Gauge(3))], gauges.snapshot());
+
+        let histograms = Family::<Vec<(String, String)>, Histogram>::new_with_constructor(|| {
+            Histogram::new([1.0, 2.0].into_iter())
+        });
+        histograms.observe(&get, 0.5);
+        histograms.observe(&get, 1.5);
+        histograms.observe(&get, 5.0);
+        assert_eq!(
+            vec![(
+                get,
+                Snapshot::Histogram {
+                    sum: 7.0,
+                    count: 3,
+                    buckets: vec![(1.0, 1), (2.0, 2), (f64::INFINITY, 3)],
+                }
+            )],
+            histograms.snapshot()
+        );
+    }
+
     #[test]
     fn histogram_family() {
         Family::<(), Histogram>::new_with_constructor(|| {
@@ -296,4 +881,77 @@ mod tests {
         let custom_builder = CustomBuilder { custom_start: 1.0 };
         Family::<(), Histogram, CustomBuilder>::new_with_constructor(custom_builder);
     }
+
+    #[test]
+    fn with() {
+        let family = Family::<u64, Counter>::default();
+        assert_eq!(1, family.with(&1, |counter| counter.inc() + 1));
+        assert_eq!(1, family.with(&1, Counter::get));
+    }
+
+    #[test]
+    fn counter_values() {
+        let family = Family::<u64, Counter>::default();
+        family.inc(&1);
+        family.inc(&2);
+        family.inc(&2);
+
+        let mut values = family.values();
+        values.sort();
+        assert_eq!(vec![(1, 1), (2, 2)], values);
+    }
+
+    #[test]
+    fn remove() {
+        let family = Family::<u64, Counter>::default();
+        family.inc(&1);
+        family.inc(&2);
+
+        assert!(family.remove(&1));
+        assert_eq!(vec![(2, Snapshot::Counter(1))], family.snapshot());
+
+        // The series starts anew when recreated.
+        assert_eq!(0, family.inc(&1));
+    }
+
+    #[test]
+    fn start_timer() {
+        let family = Family::<String, Histogram>::new_with_constructor(|| {
+            Histogram::new([0.001, 60.0].into_iter())
+        });
+        let route = "/".to_string();
+
+        {
+            let _timer = family.start_timer(&route);
+            // Creating a new series while the timer is running must not
+            // deadlock.
+            family.get_or_create(&"/other".to_string());
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let histogram = family.get_or_create(&route);
+        let (sum, count, buckets) = histogram.get();
+        assert_eq!(1, count);
+        assert!(sum >= 0.01);
+        assert_eq!(vec![(0.001, 0), (60.0, 1), (f64::MAX, 0)], *buckets);
+    }
+
+    #[test]
+    fn histogram_family_with_labeled_constructor() {
+        let family =
+            Family::<String, Histogram, _>::new_with_constructor_labeled(|endpoint| match endpoint
+                .as_str()
+            {
+                "/slow" => Histogram::new([1.0, 10.0].into_iter()),
+                _ => Histogram::new([0.01].into_iter()),
+            });
+
+        let bounds = |endpoint: &str| -> Vec<f64> {
+            let histogram = family.get_or_create(&endpoint.to_string());
+            let (_, _, buckets) = histogram.get();
+            buckets.iter().map(|(bound, _)| *bound).collect()
+        };
+        assert_eq!(vec![1.0, 10.0, f64::MAX], bounds("/slow"));
+        assert_eq!(vec![0.01, f64::MAX], bounds("/fast"));
+    }
 }
@@ -2,12 +2,14 @@
 //!
 //! See [`Gauge`] for details.
 
+use super::family::{Family, LabeledMetricConstructor};
 use super::{MetricType, TypedMetric};
 use std::marker::PhantomData;
+use std::ops::{Add, AddAssign, Sub, SubAssign};
 #[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 /// Open Metrics [`Gauge`] to record current measurements.
 ///
@@ -38,20 +40,41 @@ use std::sync::Arc;
 /// gauge.set(42.0);
 /// let _value: f64 = gauge.get();
 /// ```
+///
+/// ## Using [`AtomicU32`] as storage and [`u32`] on the interface
+///
+/// Halves the memory of each [`Gauge`], e.g. to reduce the footprint of a
+/// [`Family`](crate::metrics::family::Family) with many label sets. Note that
+/// a [`Gauge`] of [`u32`] wraps around on overflow, i.e. after `u32::MAX` it
+/// continues at `0`.
+///
+/// ```
+/// # use prometheus_client::metrics::gauge::Gauge;
+/// # use std::sync::atomic::AtomicU32;
+/// let gauge = Gauge::<u32, AtomicU32>::default();
+/// gauge.set(u32::MAX);
+/// gauge.inc();
+/// assert_eq!(0, gauge.get());
+/// ```
 #[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
-#[derive(Debug)]
 pub struct Gauge<N = u64, A = AtomicU64> {
     value: Arc<A>,
     phantom: PhantomData<N>,
 }
 
 #[cfg(any(target_arch = "mips", target_arch = "powerpc"))]
-#[derive(Debug)]
 pub struct Gauge<N = u32, A = AtomicU32> {
     value: Arc<A>,
     phantom: PhantomData<N>,
 }
 
+/// Shows the current value, e.g. `Gauge(42)`.
+impl<N: std::fmt::Debug, A: Atomic<N>> std::fmt::Debug for Gauge<N, A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Gauge").field(&self.get()).finish()
+    }
+}
+
 impl<N, A> Clone for Gauge<N, A> {
     fn clone(&self) -> Self {
         Self {
@@ -110,6 +133,48 @@ impl<N, A: Atomic<N>> Gauge<N, A> {
     }
 }
 
+/// Increase the [`Gauge`] by `v`, same as [`Gauge::inc_by`], i.e. with the
+/// same atomic ordering.
+///
+/// Implemented for `&Gauge` as well, as the [`Gauge`] is updated via a shared
+/// reference.
+///
+/// ```
+/// # use prometheus_client::metrics::gauge::Gauge;
+/// let mut gauge: Gauge = Gauge::default();
+/// gauge += 3;
+/// gauge -= 1;
+///
+/// let mut shared = &gauge;
+/// shared -= 2;
+/// assert_eq!(0, gauge.get());
+/// ```
+impl<N, A: Atomic<N>> AddAssign<N> for Gauge<N, A> {
+    fn add_assign(&mut self, v: N) {
+        self.inc_by(v);
+    }
+}
+
+impl<N, A: Atomic<N>> AddAssign<N> for &Gauge<N, A> {
+    fn add_assign(&mut self, v: N) {
+        self.inc_by(v);
+    }
+}
+
+/// Decrease the [`Gauge`] by `v`, same as [`Gauge::dec_by`], i.e. with the
+/// same atomic ordering.
+impl<N, A: Atomic<N>> SubAssign<N> for Gauge<N, A> {
+    fn sub_assign(&mut self, v: N) {
+        self.dec_by(v);
+    }
+}
+
+impl<N, A: Atomic<N>> SubAssign<N> for &Gauge<N, A> {
+    fn sub_assign(&mut self, v: N) {
+        self.dec_by(v);
+    }
+}
+
 pub trait Atomic<N> {
     fn inc(&self) -> N;
 
@@ -226,10 +291,361 @@ impl Atomic<f64> for AtomicU64 {
     }
 }
 
+/// Storage of a [`u64`] saturating at `0` instead of wrapping around on
+/// decrement, see [`NonNegativeGauge`].
+#[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
+#[derive(Debug, Default)]
+pub struct SaturatingU64(AtomicU64);
+
+#[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
+impl Atomic<u64> for SaturatingU64 {
+    fn inc(&self) -> u64 {
+        self.inc_by(1)
+    }
+
+    fn inc_by(&self, v: u64) -> u64 {
+        self.0.fetch_add(v, Ordering::Relaxed)
+    }
+
+    fn dec(&self) -> u64 {
+        self.dec_by(1)
+    }
+
+    fn dec_by(&self, v: u64) -> u64 {
+        self.0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |old| {
+                Some(old.saturating_sub(v))
+            })
+            .expect("Closure to always return Some.")
+    }
+
+    fn set(&self, v: u64) -> u64 {
+        self.0.swap(v, Ordering::Relaxed)
+    }
+
+    fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// [`Gauge`] saturating at `0` instead of wrapping around when decremented
+/// below zero, e.g. for a queue depth or a connection count.
+///
+/// A decrement below zero indicates an accounting bug, e.g. a connection
+/// closed twice. Saturating avoids exposing a nonsensical value close to
+/// `u64::MAX`, but it deliberately hides the magnitude of the bug, as the
+/// [`NonNegativeGauge`] no longer reflects the sum of its increments and
+/// decrements.
+///
+/// ```
+/// # use prometheus_client::metrics::gauge::NonNegativeGauge;
+/// let connections = NonNegativeGauge::default();
+/// connections.inc();
+/// connections.dec_by(2);
+/// assert_eq!(0, connections.get());
+/// ```
+#[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
+pub type NonNegativeGauge = Gauge<u64, SaturatingU64>;
+
 impl<N, A> TypedMetric for Gauge<N, A> {
     const TYPE: MetricType = MetricType::Gauge;
 }
 
+impl<N, A> Gauge<N, A> {
+    /// Bundle the [`Gauge`] with a constant label set, see [`LabeledGauge`].
+    pub fn with_labels<S>(self, label_set: S) -> LabeledGauge<S, N, A> {
+        LabeledGauge {
+            label_set: Arc::new(label_set),
+            gauge: self,
+        }
+    }
+}
+
+/// A single [`Gauge`] with a constant label set, created via
+/// [`Gauge::with_labels`].
+///
+/// Cheaper than a [`Family`] with a single label set, as neither a map nor a
+/// lock is involved, neither on update nor on encoding. Keep a clone of the
+/// [`Gauge`] to update it.
+///
+/// ```
+/// # use prometheus_client::encoding::text::encode;
+/// # use prometheus_client::metrics::gauge::Gauge;
+/// # use prometheus_client::registry::Registry;
+/// #
+/// let mut registry = <Registry>::default();
+/// let connections: Gauge = Gauge::default();
+/// registry.register(
+///     "connections",
+///     "Number of open connections",
+///     Box::new(connections.clone().with_labels(vec![("protocol", "tcp")])),
+/// );
+/// connections.set(3);
+/// #
+/// # let mut buffer = vec![];
+/// # encode(&mut buffer, &registry).unwrap();
+/// # assert!(String::from_utf8(buffer).unwrap().contains("connections{protocol=\"tcp\"} 3\n"));
+/// ```
+#[cfg(not(any(target_arch = "mips", target_arch = "powerpc")))]
+pub struct LabeledGauge<S, N = u64, A = AtomicU64> {
+    label_set: Arc<S>,
+    gauge: Gauge<N, A>,
+}
+
+#[cfg(any(target_arch = "mips", target_arch = "powerpc"))]
+pub struct LabeledGauge<S, N = u32, A = AtomicU32> {
+    label_set: Arc<S>,
+    gauge: Gauge<N, A>,
+}
+
+impl<S: std::fmt::Debug, N: std::fmt::Debug, A: Atomic<N>> std::fmt::Debug
+    for LabeledGauge<S, N, A>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LabeledGauge")
+            .field("label_set", &self.label_set)
+            .field("gauge", &self.gauge)
+            .finish()
+    }
+}
+
+impl<S, N, A> Clone for LabeledGauge<S, N, A> {
+    fn clone(&self) -> Self {
+        Self {
+            label_set: self.label_set.clone(),
+            gauge: self.gauge.clone(),
+        }
+    }
+}
+
+impl<S, N, A> LabeledGauge<S, N, A> {
+    /// The constant label set of the [`Gauge`].
+    pub fn label_set(&self) -> &S {
+        &self.label_set
+    }
+
+    /// The [`Gauge`], e.g. to update it.
+    pub fn gauge(&self) -> &Gauge<N, A> {
+        &self.gauge
+    }
+}
+
+impl<S, N, A> TypedMetric for LabeledGauge<S, N, A> {
+    const TYPE: MetricType = MetricType::Gauge;
+}
+
+/// Open Metrics gauge exposing the sum of all [`Gauge`]s of a [`Family`].
+///
+/// The sum is computed at encoding time, taking the [`Family`]'s read lock
+/// once. Thus the total can not drift from the individual series, as opposed
+/// to maintaining a separate total [`Gauge`] by hand.
+///
+/// ```
+/// # use prometheus_client::encoding::text::encode;
+/// # use prometheus_client::metrics::family::Family;
+/// # use prometheus_client::metrics::gauge::{Gauge, SumGauge};
+/// # use prometheus_client::registry::Registry;
+/// #
+/// let mut registry = <Registry>::default();
+/// let queue_depth = Family::<Vec<(String, String)>, Gauge>::default();
+/// registry.register(
+///     "queue_depth",
+///     "Queue depth per shard",
+///     Box::new(queue_depth.clone()),
+/// );
+/// registry.register(
+///     "queue_depth_total",
+///     "Queue depth across all shards",
+///     Box::new(SumGauge::new(queue_depth.clone())),
+/// );
+///
+/// queue_depth.get_or_create(&vec![("shard".to_owned(), "0".to_owned())]).set(2);
+/// queue_depth.get_or_create(&vec![("shard".to_owned(), "1".to_owned())]).set(3);
+/// #
+/// # let mut buffer = vec![];
+/// # encode(&mut buffer, &registry).unwrap();
+/// # assert!(String::from_utf8(buffer).unwrap().contains("queue_depth_total 5\n"));
+/// ```
+pub struct SumGauge<S, N, A, C = fn() -> Gauge<N, A>> {
+    family: Family<S, Gauge<N, A>, C>,
+}
+
+impl<S, N, A, C> std::fmt::Debug for SumGauge<S, N, A, C>
+where
+    S: std::fmt::Debug,
+    N: std::fmt::Debug,
+    A: Atomic<N>,
+    C: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SumGauge")
+            .field("family", &self.family)
+            .finish()
+    }
+}
+
+impl<S, N, A, C: Clone> Clone for SumGauge<S, N, A, C> {
+    fn clone(&self) -> Self {
+        Self {
+            family: self.family.clone(),
+        }
+    }
+}
+
+impl<S, N, A, C> SumGauge<S, N, A, C> {
+    /// Create a [`SumGauge`] summing the [`Gauge`]s of the given [`Family`].
+    pub fn new(family: Family<S, Gauge<N, A>, C>) -> Self {
+        Self { family }
+    }
+}
+
+impl<S, N, A, C> SumGauge<S, N, A, C>
+where
+    S: Clone + std::hash::Hash + Eq,
+    N: std::iter::Sum,
+    A: Atomic<N>,
+    C: LabeledMetricConstructor<S, Gauge<N, A>>,
+{
+    /// Get the current sum of all [`Gauge`]s of the [`Family`].
+    pub fn get(&self) -> N {
+        self.family.read().values().map(Gauge::get).sum()
+    }
+}
+
+impl<S, N, A, C> TypedMetric for SumGauge<S, N, A, C> {
+    const TYPE: MetricType = MetricType::Gauge;
+}
+
+/// Open Metrics gauge additionally tracking the maximum value it ever had,
+/// e.g. the number of connections of a pool in use and the most ever in use.
+///
+/// The maximum is updated together with the value under a single lock, thus
+/// it is never below the current value.
+///
+/// **Registering a [`GaugeWithMax`] via
+/// [`Registry::register`](crate::registry::Registry::register) exposes its
+/// value only, not its maximum.** Register both at once via
+/// [`Registry::register_gauge_with_max`](crate::registry::Registry::register_gauge_with_max),
+/// or expose the maximum as a separate metric via
+/// [`GaugeWithMax::max_gauge`].
+///
+/// ```
+/// # use prometheus_client::metrics::gauge::GaugeWithMax;
+/// let in_use = GaugeWithMax::<u64>::default();
+/// in_use.inc();
+/// in_use.inc();
+/// in_use.dec();
+/// assert_eq!(1, in_use.get());
+/// assert_eq!(2, in_use.max());
+/// ```
+#[derive(Debug)]
+pub struct GaugeWithMax<N = u64> {
+    // Value and maximum.
+    inner: Arc<Mutex<(N, N)>>,
+}
+
+impl<N> Clone for GaugeWithMax<N> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<N: Default> Default for GaugeWithMax<N> {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Default::default())),
+        }
+    }
+}
+
+impl<N> GaugeWithMax<N>
+where
+    N: Copy + PartialOrd + From<u8> + Add<Output = N> + Sub<Output = N>,
+{
+    /// Increase the [`GaugeWithMax`] by 1, returning the previous value.
+    pub fn inc(&self) -> N {
+        self.inc_by(N::from(1))
+    }
+
+    /// Increase the [`GaugeWithMax`] by `v`, returning the previous value.
+    pub fn inc_by(&self, v: N) -> N {
+        self.update(|value| value + v)
+    }
+
+    /// Decrease the [`GaugeWithMax`] by 1, returning the previous value.
+    pub fn dec(&self) -> N {
+        self.dec_by(N::from(1))
+    }
+
+    /// Decrease the [`GaugeWithMax`] by `v`, returning the previous value.
+    ///
+    /// Panics in debug builds when decreasing an unsigned value below zero.
+    pub fn dec_by(&self, v: N) -> N {
+        self.update(|value| value - v)
+    }
+
+    /// Sets the [`GaugeWithMax`] to `v`, returning the previous value.
+    pub fn set(&self, v: N) -> N {
+        self.update(|_| v)
+    }
+
+    /// Get the current value of the [`GaugeWithMax`].
+    pub fn get(&self) -> N {
+        self.inner.lock().unwrap().0
+    }
+
+    /// Get the maximum value the [`GaugeWithMax`] ever had.
+    pub fn max(&self) -> N {
+        self.inner.lock().unwrap().1
+    }
+
+    /// Get a [`GaugeMax`] exposing the maximum of this [`GaugeWithMax`].
+    pub fn max_gauge(&self) -> GaugeMax<N> {
+        GaugeMax(self.clone())
+    }
+
+    fn update(&self, f: impl FnOnce(N) -> N) -> N {
+        let mut inner = self.inner.lock().unwrap();
+        let previous = inner.0;
+        inner.0 = f(previous);
+        if inner.0 > inner.1 {
+            inner.1 = inner.0;
+        }
+        previous
+    }
+}
+
+impl<N> TypedMetric for GaugeWithMax<N> {
+    const TYPE: MetricType = MetricType::Gauge;
+}
+
+/// Open Metrics gauge exposing the maximum of a [`GaugeWithMax`], see
+/// [`GaugeWithMax::max_gauge`].
+#[derive(Debug)]
+pub struct GaugeMax<N = u64>(GaugeWithMax<N>);
+
+impl<N> Clone for GaugeMax<N> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<N> GaugeMax<N>
+where
+    N: Copy + PartialOrd + From<u8> + Add<Output = N> + Sub<Output = N>,
+{
+    /// Get the maximum value the [`GaugeWithMax`] ever had.
+    pub fn get(&self) -> N {
+        self.0.max()
+    }
+}
+
+impl<N> TypedMetric for GaugeMax<N> {
+    const TYPE: MetricType = MetricType::Gauge;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -246,4 +662,61 @@ mod tests {
         assert_eq!(0, gauge.set(10));
         assert_eq!(10, gauge.get());
     }
+
+    #[test]
+    fn add_and_sub_assign() {
+        let mut gauge = Gauge::<f64, AtomicU64>::default();
+        gauge += 1.5;
+        let mut shared = &gauge;
+        shared -= 0.5;
+        assert_eq!(1.0, gauge.get());
+    }
+
+    #[test]
+    fn debug() {
+        let gauge = Gauge::<f64, AtomicU64>::default();
+        gauge.set(-1.5);
+        assert_eq!("Gauge(-1.5)", format!("{:?}", gauge));
+    }
+
+    #[test]
+    fn non_negative_gauge() {
+        let gauge = NonNegativeGauge::default();
+        gauge.set(1);
+        assert_eq!(1, gauge.dec_by(5));
+        assert_eq!(0, gauge.dec());
+        assert_eq!(0, gauge.get());
+
+        gauge.inc();
+        assert_eq!(1, gauge.get());
+    }
+
+    #[test]
+    fn gauge_with_max() {
+        let gauge = GaugeWithMax::<f64>::default();
+        let max = gauge.max_gauge();
+
+        assert_eq!(0.0, gauge.inc_by(2.5));
+        assert_eq!(2.5, gauge.set(1.0));
+        assert_eq!(1.0, gauge.dec());
+        assert_eq!(0.0, gauge.get());
+        assert_eq!(2.5, max.get());
+
+        gauge.set(3.0);
+        assert_eq!(3.0, max.get());
+    }
+
+    #[test]
+    fn sum_gauge() {
+        let family = Family::<u32, Gauge>::default();
+        let sum = SumGauge::new(family.clone());
+        assert_eq!(0, sum.get());
+
+        family.get_or_create(&0).set(2);
+        family.get_or_create(&1).set(3);
+        assert_eq!(5, sum.get());
+
+        family.get_or_create(&0).dec();
+        assert_eq!(4, sum.get());
+    }
 }
@@ -5,7 +5,8 @@
 use super::{MetricType, TypedMetric};
 use owning_ref::OwningRef;
 use std::iter::{self, once};
-use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
 
 /// Open Metrics [`Histogram`] to measure distributions of discrete events.
 ///
@@ -31,11 +32,21 @@ use std::sync::{Arc, Mutex, MutexGuard};
 /// ```
 // TODO: Consider using atomics. See
 // https://github.com/tikv/rust-prometheus/pull/314.
-#[derive(Debug)]
 pub struct Histogram {
     inner: Arc<Mutex<Inner>>,
 }
 
+/// Shows the current count and sum, e.g. `Histogram { count: 10, sum: 3.5 }`.
+impl std::fmt::Debug for Histogram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        f.debug_struct("Histogram")
+            .field("count", &inner.count)
+            .field("sum", &inner.sum)
+            .finish()
+    }
+}
+
 impl Clone for Histogram {
     fn clone(&self) -> Self {
         Histogram {
@@ -51,50 +62,250 @@ pub(crate) struct Inner {
     count: u64,
     // TODO: Consider being generic over the bucket length.
     buckets: Vec<(f64, u64)>,
+    /// Smallest and largest observation, if tracking is enabled via
+    /// [`Histogram::with_min_max`].
+    min_max: Option<(f64, f64)>,
+    nan_policy: NanPolicy,
+    /// Whether encoding resets the histogram, see
+    /// [`Histogram::with_reset_on_read`].
+    reset_on_read: bool,
+}
+
+/// How a [`Histogram`] handles `NaN` observations, see
+/// [`Histogram::with_nan_policy`].
+///
+/// Infinite observations are always recorded, landing in the `+Inf` bucket
+/// and turning the sum infinite. They are valid observations, in contrast to
+/// `NaN`, which would make the sum `NaN` for the lifetime of the
+/// [`Histogram`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NanPolicy {
+    /// Drop `NaN` observations. Neither the count, the sum nor any bucket is
+    /// changed.
+    #[default]
+    Skip,
+    /// Count `NaN` observations in the `+Inf` bucket and the count, leaving
+    /// the sum untouched.
+    InfBucket,
 }
 
 impl Histogram {
-    pub fn new(buckets: impl Iterator<Item = f64>) -> Self {
+    /// Create a [`Histogram`] with the upper bounds of the given [`Buckets`],
+    /// e.g. an iterator of upper bounds or an [`ExponentialBuckets`]. The
+    /// `+Inf` bucket is added implicitly.
+    pub fn new(buckets: impl Buckets) -> Self {
         Self {
             inner: Arc::new(Mutex::new(Inner {
                 sum: Default::default(),
                 count: Default::default(),
                 buckets: buckets
+                    .bounds()
                     .into_iter()
                     .chain(once(f64::MAX))
                     .map(|upper_bound| (upper_bound, 0))
                     .collect(),
+                min_max: None,
+                nan_policy: Default::default(),
+                reset_on_read: false,
             })),
         }
     }
 
+    /// The number of observations.
+    ///
+    /// Use [`Histogram::sum_and_count`] to read both sum and count
+    /// consistently, e.g. to compute the mean.
+    pub fn count(&self) -> u64 {
+        self.inner.lock().unwrap().count
+    }
+
+    /// The sum of all observations.
+    ///
+    /// Use [`Histogram::sum_and_count`] to read both sum and count
+    /// consistently, e.g. to compute the mean.
+    pub fn sum(&self) -> f64 {
+        self.inner.lock().unwrap().sum
+    }
+
+    /// The sum of all observations and the number of observations, read
+    /// together under the [`Histogram`]'s lock.
+    ///
+    /// In contrast to calling [`Histogram::sum`] and [`Histogram::count`] one
+    /// after the other, no observation can happen in between, thus `sum /
+    /// count` is the coherent mean.
+    ///
+    /// ```
+    /// # use prometheus_client::metrics::histogram::{Histogram, exponential_buckets};
+    /// let histogram = Histogram::new(exponential_buckets(1.0, 2.0, 10));
+    /// histogram.observe(1.0);
+    /// histogram.observe(2.0);
+    ///
+    /// let (sum, count) = histogram.sum_and_count();
+    /// assert_eq!(1.5, sum / count as f64);
+    /// ```
+    pub fn sum_and_count(&self) -> (f64, u64) {
+        let inner = self.inner.lock().unwrap();
+        (inner.sum, inner.count)
+    }
+
+    /// Additionally track the smallest and the largest observed value, see
+    /// [`Histogram::min`] and [`Histogram::max`].
+    ///
+    /// Disabled by default. The extreme values are not encoded, they are
+    /// meant for debugging, e.g. during incident analysis.
+    ///
+    /// ```
+    /// # use prometheus_client::metrics::histogram::{Histogram, exponential_buckets};
+    /// let histogram = Histogram::new(exponential_buckets(1.0, 2.0, 10)).with_min_max();
+    /// histogram.observe(4.2);
+    /// histogram.observe(0.1);
+    /// assert_eq!(Some(0.1), histogram.min());
+    /// assert_eq!(Some(4.2), histogram.max());
+    /// ```
+    pub fn with_min_max(self) -> Self {
+        self.inner.lock().unwrap().min_max = Some((f64::INFINITY, f64::NEG_INFINITY));
+        self
+    }
+
+    /// Set how `NaN` observations are handled. Defaults to
+    /// [`NanPolicy::Skip`].
+    ///
+    /// ```
+    /// # use prometheus_client::metrics::histogram::{Histogram, NanPolicy};
+    /// let histogram = Histogram::new([1.0].into_iter()).with_nan_policy(NanPolicy::InfBucket);
+    /// histogram.observe(0.5);
+    /// histogram.observe(f64::NAN);
+    /// assert_eq!((0.5, 2), histogram.sum_and_count());
+    /// ```
+    pub fn with_nan_policy(self, policy: NanPolicy) -> Self {
+        self.inner.lock().unwrap().nan_policy = policy;
+        self
+    }
+
+    /// Reset the histogram each time it is encoded, thus each encoding
+    /// reporting the observations since the previous encoding only, e.g. for a
+    /// StatsD-style backend expecting deltas.
+    ///
+    /// **This breaks Prometheus' cumulative histogram semantics.** Prometheus
+    /// interprets each reset as a restart of the process, thus functions like
+    /// `rate` and `histogram_quantile` return wrong results. Multiple scrapers
+    /// each see only part of the observations. Only use this with backends
+    /// expecting deltas.
+    ///
+    /// Only entry points encoding each metric family exactly once per scrape
+    /// reset the histogram, i.e. [`encode`](crate::encoding::text::encode),
+    /// [`encode_with_options`](crate::encoding::text::encode_with_options),
+    /// [`encode_with_filter`](crate::encoding::text::encode_with_filter),
+    /// [`ChunkEncoder`](crate::encoding::text::ChunkEncoder) and the Graphite
+    /// and InfluxDB encoders building on them. All others, e.g.
+    /// [`encode_with_limit`](crate::encoding::text::encode_with_limit),
+    /// [`encode_lenient`](crate::encoding::text::encode_lenient),
+    /// [`encode_family`](crate::encoding::text::encode_family),
+    /// [`encode_by_family`](crate::encoding::text::encode_by_family),
+    /// [`Histogram::sum`] and [`Histogram::count`], leave it untouched.
+    ///
+    /// Reading and resetting happen atomically under the lock of the
+    /// histogram, thus no concurrent observation slips in between. Still, the
+    /// observations read are lost if the encoding fails afterwards, e.g. on a
+    /// write error. [`HistogramWithExemplars`](crate::metrics::exemplar::HistogramWithExemplars)
+    /// does not support this mode.
+    ///
+    /// ```
+    /// # use prometheus_client::encoding::text::encode;
+    /// # use prometheus_client::metrics::histogram::Histogram;
+    /// # use prometheus_client::registry::Registry;
+    /// #
+    /// let mut registry = Registry::default();
+    /// let histogram = Histogram::new([1.0].into_iter()).with_reset_on_read();
+    /// registry.register("my_histogram", "This is my histogram", histogram.clone());
+    /// histogram.observe(0.5);
+    ///
+    /// encode(&mut vec![], &registry).unwrap();
+    /// assert_eq!((0.0, 0), histogram.sum_and_count());
+    /// ```
+    pub fn with_reset_on_read(self) -> Self {
+        self.inner.lock().unwrap().reset_on_read = true;
+        self
+    }
+
+    /// The smallest observed value, `None` if min and max tracking is disabled
+    /// or no value has been observed yet.
+    pub fn min(&self) -> Option<f64> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .min_max
+            .map(|(min, _max)| min)
+            .filter(|_| inner.count > 0)
+    }
+
+    /// The largest observed value, `None` if min and max tracking is disabled
+    /// or no value has been observed yet.
+    pub fn max(&self) -> Option<f64> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .min_max
+            .map(|(_min, max)| max)
+            .filter(|_| inner.count > 0)
+    }
+
+    /// Observe the given value.
+    ///
+    /// Once the count reaches `u64::MAX`, further observations are dropped,
+    /// i.e. the count saturates, keeping the count and the buckets consistent.
+    /// Debug builds panic instead, as the count overflowing indicates a bug
+    /// rather than a long-running process.
     pub fn observe(&self, v: f64) {
         self.observe_and_bucket(v);
     }
 
+    /// Starts a [`HistogramTimer`], observing the elapsed time in seconds
+    /// once dropped.
+    ///
+    /// ```
+    /// # use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+    /// let histogram = Histogram::new(exponential_buckets(0.001, 2.0, 10));
+    /// {
+    ///     let _timer = histogram.start_timer();
+    ///     // Timed block.
+    /// }
+    /// assert_eq!(1, histogram.count());
+    /// ```
+    pub fn start_timer(&self) -> HistogramTimer {
+        HistogramTimer {
+            histogram: self.clone(),
+            start: Instant::now(),
+        }
+    }
+
     /// Observes the given value, returning the index of the first bucket the
-    /// value is added to.
+    /// value is added to, or `None` if the value is skipped as per the
+    /// [`NanPolicy`] or because the count is saturated.
     ///
     /// Needed in
     /// [`HistogramWithExemplars`](crate::metrics::exemplar::HistogramWithExemplars).
     pub(crate) fn observe_and_bucket(&self, v: f64) -> Option<usize> {
         let mut inner = self.inner.lock().unwrap();
+        if count_saturated(inner.count) {
+            return None;
+        }
+        if v.is_nan() {
+            return match inner.nan_policy {
+                NanPolicy::Skip => None,
+                NanPolicy::InfBucket => {
+                    inner.count += 1;
+                    Some(observe_into_buckets(&mut inner.buckets, v))
+                }
+            };
+        }
+
         inner.sum += v;
         inner.count += 1;
-
-        let first_bucket = inner
-            .buckets
-            .iter_mut()
-            .enumerate()
-            .find(|(_i, (upper_bound, _value))| upper_bound >= &v);
-
-        match first_bucket {
-            Some((i, (_upper_bound, value))) => {
-                *value += 1;
-                Some(i)
-            }
-            None => None,
+        if let Some((min, max)) = inner.min_max.as_mut() {
+            *min = min.min(v);
+            *max = max.max(v);
         }
+
+        Some(observe_into_buckets(&mut inner.buckets, v))
     }
 
     pub(crate) fn get(&self) -> (f64, u64, MutexGuardedBuckets) {
@@ -104,12 +315,350 @@ impl Histogram {
         let buckets = OwningRef::new(inner).map(|inner| &inner.buckets);
         (sum, count, buckets)
     }
+
+    /// Returns the sum, count and buckets and resets the histogram, if in
+    /// reset-on-read mode, see [`Histogram::with_reset_on_read`].
+    pub(crate) fn take_if_reset_on_read(&self) -> Option<(f64, u64, BucketCounts)> {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.reset_on_read {
+            return None;
+        }
+
+        let buckets = inner.buckets.clone();
+        for (_upper_bound, count) in inner.buckets.iter_mut() {
+            *count = 0;
+        }
+        if let Some(min_max) = inner.min_max.as_mut() {
+            *min_max = (f64::INFINITY, f64::NEG_INFINITY);
+        }
+
+        Some((
+            std::mem::take(&mut inner.sum),
+            std::mem::take(&mut inner.count),
+            buckets,
+        ))
+    }
 }
 
+pub(crate) type BucketCounts = Vec<(f64, u64)>;
+
 pub(crate) type MutexGuardedBuckets<'a> = OwningRef<MutexGuard<'a, Inner>, Vec<(f64, u64)>>;
 
+/// Whether the count of a histogram reached `u64::MAX`, i.e. whether further
+/// observations have to be dropped to keep the count and the buckets
+/// consistent.
+fn count_saturated(count: u64) -> bool {
+    debug_assert_ne!(count, u64::MAX, "Histogram count to not overflow.");
+    count == u64::MAX
+}
+
+/// Increments the first bucket `v` falls into, returning its index.
+fn observe_into_buckets(buckets: &mut [(f64, u64)], v: f64) -> usize {
+    // The last bucket is the `+Inf` bucket, catching every value not caught by
+    // a previous bucket, including infinity and `NaN`.
+    let last_bucket = buckets.len() - 1;
+    let (i, (_upper_bound, value)) = buckets
+        .iter_mut()
+        .enumerate()
+        .find(|(i, (upper_bound, _value))| upper_bound >= &v || *i == last_bucket)
+        .expect("`+Inf` bucket to catch all values.");
+    *value += 1;
+    i
+}
+
+/// Guard observing the time elapsed since its creation in seconds into a
+/// [`Histogram`] when dropped, see [`Histogram::start_timer`].
+///
+/// Holds a handle to the [`Histogram`], not a lock, thus one can hold the
+/// guard across a long-running block.
+#[must_use = "The elapsed time is observed when the timer is dropped."]
+#[derive(Debug)]
+pub struct HistogramTimer {
+    histogram: Histogram,
+    start: Instant,
+}
+
+impl Drop for HistogramTimer {
+    fn drop(&mut self) {
+        self.histogram.observe(self.start.elapsed().as_secs_f64());
+    }
+}
+
+/// Open Metrics histogram of integer observations, tracking its sum as a
+/// [`u64`] instead of an [`f64`].
+///
+/// An [`f64`] represents integers exactly only up to 2^53. Thus the sum of a
+/// [`Histogram`] loses precision once it grows beyond, e.g. when observing the
+/// size in bytes of many requests. Prefer [`IntHistogram`] for such large
+/// integer distributions and [`Histogram`] for everything else, e.g. latencies.
+/// The sum of an [`IntHistogram`] wraps around on overflow. Bucket bounds are
+/// floating point nonetheless.
+///
+/// ```
+/// # use prometheus_client::metrics::histogram::{exponential_buckets, IntHistogram};
+/// let histogram = IntHistogram::new(exponential_buckets(1024.0, 4.0, 10));
+/// histogram.observe(9_007_199_254_740_993);
+/// histogram.observe(1);
+/// assert_eq!(9_007_199_254_740_994, histogram.sum());
+/// ```
+#[derive(Debug)]
+pub struct IntHistogram {
+    inner: Arc<Mutex<IntInner>>,
+}
+
+impl Clone for IntHistogram {
+    fn clone(&self) -> Self {
+        IntHistogram {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct IntInner {
+    sum: u64,
+    count: u64,
+    buckets: Vec<(f64, u64)>,
+}
+
+impl IntHistogram {
+    pub fn new(buckets: impl Buckets) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(IntInner {
+                sum: 0,
+                count: 0,
+                buckets: buckets
+                    .bounds()
+                    .into_iter()
+                    .chain(once(f64::MAX))
+                    .map(|upper_bound| (upper_bound, 0))
+                    .collect(),
+            })),
+        }
+    }
+
+    /// Observe the given value.
+    ///
+    /// The count saturates at `u64::MAX`, see [`Histogram::observe`].
+    pub fn observe(&self, v: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        if count_saturated(inner.count) {
+            return;
+        }
+        inner.sum = inner.sum.wrapping_add(v);
+        inner.count += 1;
+        observe_into_buckets(&mut inner.buckets, v as f64);
+    }
+
+    /// The number of observations.
+    pub fn count(&self) -> u64 {
+        self.inner.lock().unwrap().count
+    }
+
+    /// The sum of all observations.
+    pub fn sum(&self) -> u64 {
+        self.inner.lock().unwrap().sum
+    }
+
+    /// The sum of all observations and the number of observations, read
+    /// together under the [`IntHistogram`]'s lock, see
+    /// [`Histogram::sum_and_count`].
+    pub fn sum_and_count(&self) -> (u64, u64) {
+        let inner = self.inner.lock().unwrap();
+        (inner.sum, inner.count)
+    }
+
+    pub(crate) fn get(&self) -> (u64, u64, MutexGuardedIntBuckets) {
+        let inner = self.inner.lock().unwrap();
+        let sum = inner.sum;
+        let count = inner.count;
+        let buckets = OwningRef::new(inner).map(|inner| &inner.buckets);
+        (sum, count, buckets)
+    }
+}
+
+pub(crate) type MutexGuardedIntBuckets<'a> = OwningRef<MutexGuard<'a, IntInner>, Vec<(f64, u64)>>;
+
+impl TypedMetric for IntHistogram {
+    const TYPE: MetricType = MetricType::Histogram;
+    const ENCODES_AS_SUMMARY: bool = true;
+}
+
 impl TypedMetric for Histogram {
     const TYPE: MetricType = MetricType::Histogram;
+    const ENCODES_AS_SUMMARY: bool = true;
+}
+
+/// Open Metrics histogram with fixed, already aggregated buckets, e.g. to
+/// bridge a histogram from another instrumentation library without replaying
+/// its observations.
+///
+/// ```
+/// # use prometheus_client::encoding::text::encode;
+/// # use prometheus_client::metrics::histogram::ConstHistogram;
+/// # use prometheus_client::registry::Registry;
+/// #
+/// let histogram = ConstHistogram::new([(0.1, 2), (1.0, 5), (f64::INFINITY, 6)], 4.2).unwrap();
+///
+/// let mut registry = Registry::default();
+/// registry.register("latency", "Request latency", histogram);
+///
+/// let mut buffer = vec![];
+/// encode(&mut buffer, &registry).unwrap();
+/// assert!(String::from_utf8(buffer).unwrap().contains("latency_bucket{le=\"1.0\"} 5\n"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ConstHistogram {
+    sum: f64,
+    count: u64,
+    buckets: BucketCounts,
+}
+
+impl ConstHistogram {
+    /// Create a [`ConstHistogram`] from `(upper bound, cumulative count)`
+    /// pairs and the sum of all observations.
+    ///
+    /// Fails unless the upper bounds are strictly increasing, the counts are
+    /// non-decreasing and the last upper bound is [`f64::INFINITY`], its count
+    /// being the count of all observations.
+    pub fn new(
+        buckets: impl IntoIterator<Item = (f64, u64)>,
+        sum: f64,
+    ) -> Result<Self, InvalidBucketsError> {
+        let mut non_cumulative = Vec::new();
+        let mut previous: Option<(f64, u64)> = None;
+        for (upper_bound, count) in buckets {
+            if let Some((previous_bound, previous_count)) = previous {
+                if upper_bound.partial_cmp(&previous_bound) != Some(std::cmp::Ordering::Greater) {
+                    return Err(InvalidBucketsError("upper bounds not strictly increasing"));
+                }
+                if count < previous_count {
+                    return Err(InvalidBucketsError("cumulative counts decreasing"));
+                }
+            }
+            non_cumulative.push((upper_bound, count - previous.map_or(0, |(_, c)| c)));
+            previous = Some((upper_bound, count));
+        }
+
+        match (previous, non_cumulative.last_mut()) {
+            (Some((upper_bound, count)), Some(last)) if upper_bound == f64::INFINITY => {
+                // The `+Inf` bucket is represented by `f64::MAX`, see
+                // `Histogram::new`.
+                last.0 = f64::MAX;
+                Ok(Self {
+                    sum,
+                    count,
+                    buckets: non_cumulative,
+                })
+            }
+            _ => Err(InvalidBucketsError("last upper bound not `+Inf`")),
+        }
+    }
+
+    pub(crate) fn get(&self) -> (f64, u64, &[(f64, u64)]) {
+        (self.sum, self.count, &self.buckets)
+    }
+}
+
+impl TypedMetric for ConstHistogram {
+    const TYPE: MetricType = MetricType::Histogram;
+    const ENCODES_AS_SUMMARY: bool = true;
+}
+
+/// Error returned by [`ConstHistogram::new`] on inconsistent buckets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidBucketsError(&'static str);
+
+impl std::fmt::Display for InvalidBucketsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid histogram buckets: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidBucketsError {}
+
+/// Strategy producing the upper bounds of the buckets of a [`Histogram`], see
+/// [`Histogram::new`].
+///
+/// Implemented for any iterator of upper bounds, as well as for
+/// [`ExponentialBuckets`] and [`LinearBuckets`]. Implement it for custom
+/// strategies, e.g. bounds aligned with a service level objective:
+///
+/// ```
+/// # use prometheus_client::metrics::histogram::{Buckets, Histogram};
+/// struct SloBuckets {
+///     target: f64,
+/// }
+///
+/// impl Buckets for SloBuckets {
+///     fn bounds(self) -> Vec<f64> {
+///         [0.5, 1.0, 2.0].iter().map(|f| f * self.target).collect()
+///     }
+/// }
+///
+/// let histogram = Histogram::new(SloBuckets { target: 0.2 });
+/// histogram.observe(0.15);
+/// ```
+pub trait Buckets {
+    /// The upper bounds of the buckets in ascending order, excluding the
+    /// implicit `+Inf` bucket.
+    fn bounds(self) -> Vec<f64>;
+}
+
+impl<I: Iterator<Item = f64>> Buckets for I {
+    fn bounds(self) -> Vec<f64> {
+        self.collect()
+    }
+}
+
+/// [`Buckets`] with `length` upper bounds, starting at `start`, each `factor`
+/// times the previous one, see [`exponential_buckets`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExponentialBuckets {
+    start: f64,
+    factor: f64,
+    length: u16,
+}
+
+impl ExponentialBuckets {
+    pub fn new(start: f64, factor: f64, length: u16) -> Self {
+        Self {
+            start,
+            factor,
+            length,
+        }
+    }
+}
+
+impl Buckets for ExponentialBuckets {
+    fn bounds(self) -> Vec<f64> {
+        exponential_buckets(self.start, self.factor, self.length).collect()
+    }
+}
+
+/// [`Buckets`] with `length` upper bounds, starting at `start`, each `width`
+/// above the previous one, see [`linear_buckets`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinearBuckets {
+    start: f64,
+    width: f64,
+    length: u16,
+}
+
+impl LinearBuckets {
+    pub fn new(start: f64, width: f64, length: u16) -> Self {
+        Self {
+            start,
+            width,
+            length,
+        }
+    }
+}
+
+impl Buckets for LinearBuckets {
+    fn bounds(self) -> Vec<f64> {
+        linear_buckets(self.start, self.width, self.length).collect()
+    }
 }
 
 pub fn exponential_buckets(start: f64, factor: f64, length: u16) -> impl Iterator<Item = f64> {
@@ -136,6 +685,179 @@ mod tests {
         histogram.observe(1.0);
     }
 
+    #[test]
+    fn custom_buckets() {
+        struct Fibonacci(usize);
+
+        impl Buckets for Fibonacci {
+            fn bounds(self) -> Vec<f64> {
+                let mut bounds = vec![1.0, 2.0];
+                while bounds.len() < self.0 {
+                    bounds.push(bounds[bounds.len() - 2] + bounds[bounds.len() - 1]);
+                }
+                bounds.truncate(self.0);
+                bounds
+            }
+        }
+
+        let histogram = Histogram::new(Fibonacci(5));
+        histogram.observe(4.0);
+        let (_sum, _count, buckets) = histogram.get();
+        assert_eq!(
+            &vec![
+                (1.0, 0),
+                (2.0, 0),
+                (3.0, 0),
+                (5.0, 1),
+                (8.0, 0),
+                (f64::MAX, 0)
+            ],
+            &*buckets
+        );
+
+        assert_eq!(
+            vec![1.0, 2.0, 4.0],
+            ExponentialBuckets::new(1.0, 2.0, 3).bounds()
+        );
+        assert_eq!(
+            vec![1.0, 1.5, 2.0],
+            LinearBuckets::new(1.0, 0.5, 3).bounds()
+        );
+    }
+
+    #[test]
+    fn debug() {
+        let histogram = Histogram::new([1.0].into_iter());
+        histogram.observe(0.5);
+        histogram.observe(3.0);
+        assert_eq!(
+            "Histogram { count: 2, sum: 3.5 }",
+            format!("{:?}", histogram)
+        );
+    }
+
+    #[test]
+    fn observe_non_finite_into_inf_bucket() {
+        let histogram = Histogram::new([1.0].into_iter()).with_nan_policy(NanPolicy::InfBucket);
+        histogram.observe(f64::INFINITY);
+        histogram.observe(f64::NAN);
+
+        let (_sum, count, buckets) = histogram.get();
+        assert_eq!(2, count);
+        assert_eq!(&vec![(1.0, 0), (f64::MAX, 2)], &*buckets);
+    }
+
+    #[test]
+    fn skip_nan_by_default() {
+        let histogram = Histogram::new([1.0, 2.0].into_iter());
+        histogram.observe(0.5);
+        histogram.observe(f64::NAN);
+        histogram.observe(1.5);
+
+        let (sum, count, buckets) = histogram.get();
+        assert_eq!(2.0, sum);
+        assert_eq!(2, count);
+        assert_eq!(&vec![(1.0, 1), (2.0, 1), (f64::MAX, 0)], &*buckets);
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "Histogram count to not overflow.")
+    )]
+    fn saturate_count() {
+        let histogram = Histogram::new([1.0].into_iter());
+        {
+            let mut inner = histogram.inner.lock().unwrap();
+            inner.count = u64::MAX - 1;
+            inner.buckets[1].1 = u64::MAX - 1;
+        }
+
+        histogram.observe(0.5);
+        histogram.observe(0.5);
+
+        let (sum, count, buckets) = histogram.get();
+        assert_eq!(0.5, sum);
+        assert_eq!(u64::MAX, count);
+        assert_eq!(&vec![(1.0, 1), (f64::MAX, u64::MAX - 1)], &*buckets);
+    }
+
+    #[test]
+    fn reset_on_read() {
+        let histogram = Histogram::new([1.0].into_iter()).with_min_max();
+        histogram.observe(0.5);
+        assert!(histogram.take_if_reset_on_read().is_none());
+        assert_eq!(1, histogram.count());
+
+        let histogram = histogram.with_reset_on_read();
+        histogram.observe(2.0);
+        assert_eq!(
+            Some((2.5, 2, vec![(1.0, 1), (f64::MAX, 1)])),
+            histogram.take_if_reset_on_read()
+        );
+        assert_eq!(
+            Some((0.0, 0, vec![(1.0, 0), (f64::MAX, 0)])),
+            histogram.take_if_reset_on_read()
+        );
+        assert_eq!(None, histogram.min());
+
+        histogram.observe(0.25);
+        assert_eq!(Some(0.25), histogram.max());
+        assert_eq!(
+            Some((0.25, 1, vec![(1.0, 1), (f64::MAX, 0)])),
+            histogram.take_if_reset_on_read()
+        );
+    }
+
+    #[test]
+    fn sum_and_count() {
+        let histogram = Histogram::new(exponential_buckets(1.0, 2.0, 10));
+        assert_eq!((0.0, 0), histogram.sum_and_count());
+
+        histogram.observe(1.0);
+        histogram.observe(5.5);
+        assert_eq!(2, histogram.count());
+        assert_eq!(6.5, histogram.sum());
+        assert_eq!((6.5, 2), histogram.sum_and_count());
+    }
+
+    #[test]
+    fn min_max() {
+        let histogram = Histogram::new(exponential_buckets(1.0, 2.0, 10));
+        histogram.observe(1.0);
+        assert_eq!(None, histogram.min());
+        assert_eq!(None, histogram.max());
+
+        let histogram = Histogram::new(exponential_buckets(1.0, 2.0, 10)).with_min_max();
+        assert_eq!(None, histogram.min());
+        assert_eq!(None, histogram.max());
+
+        for v in [3.0, -1.5, 700.0, 2.0] {
+            histogram.observe(v);
+        }
+        assert_eq!(Some(-1.5), histogram.min());
+        assert_eq!(Some(700.0), histogram.max());
+    }
+
+    #[test]
+    fn const_histogram() {
+        let histogram = ConstHistogram::new([(1.0, 1), (2.0, 3), (f64::INFINITY, 3)], 4.0).unwrap();
+        assert_eq!(
+            (4.0, 3, &[(1.0, 1), (2.0, 2), (f64::MAX, 0)][..]),
+            histogram.get()
+        );
+
+        for buckets in [
+            vec![],
+            vec![(1.0, 1)],
+            vec![(1.0, 2), (f64::INFINITY, 1)],
+            vec![(1.0, 1), (1.0, 1), (f64::INFINITY, 1)],
+            vec![(f64::NAN, 1), (f64::INFINITY, 1)],
+        ] {
+            assert!(ConstHistogram::new(buckets, 0.0).is_err());
+        }
+    }
+
     #[test]
     fn exponential() {
         assert_eq!(
@@ -151,4 +873,18 @@ mod tests {
             linear_buckets(0.0, 1.0, 10).collect::<Vec<_>>()
         );
     }
+
+    #[test]
+    fn int_histogram() {
+        let histogram = IntHistogram::new([10.0, 100.0].into_iter());
+        histogram.observe(u64::MAX - 1);
+        histogram.observe(10);
+        histogram.observe(11);
+
+        let (sum, count, buckets) = histogram.get();
+        // Wrapped around.
+        assert_eq!(19, sum);
+        assert_eq!(3, count);
+        assert_eq!(&vec![(10.0, 1), (100.0, 1), (f64::MAX, 1)], &*buckets);
+    }
 }
@@ -2,7 +2,16 @@
 //!
 //! See [`Registry`] for details.
 
+use crate::encoding::text::{write_family_name, Encode, EncodeMetric, EncodeOptions};
+use crate::metrics::gauge::GaugeWithMax;
+use crate::metrics::info::Info;
+#[cfg(feature = "parking_lot")]
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 use std::borrow::Cow;
+use std::ops::{Add, Sub};
+use std::sync::Arc;
+#[cfg(not(feature = "parking_lot"))]
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 /// A metric registry.
 ///
@@ -63,26 +72,126 @@ pub struct Registry<M = Box<dyn crate::encoding::text::SendSyncEncodeMetric>> {
     labels: Vec<(Cow<'static, str>, Cow<'static, str>)>,
     metrics: Vec<(Descriptor, M)>,
     sub_registries: Vec<Registry<M>>,
+    pre_scrape_hooks: Vec<PreScrapeHook>,
+    name_validation: NameValidation,
+    encode_options: EncodeOptions,
 }
 
 impl<M> Default for Registry<M> {
     fn default() -> Self {
-        Self {
-            prefix: None,
-            labels: Default::default(),
-            metrics: Default::default(),
-            sub_registries: vec![],
-        }
+        RegistryBuilder::default().build()
+    }
+}
+
+impl Registry {
+    /// Creates a [`RegistryBuilder`] to configure a new [`Registry`] of any
+    /// metric type.
+    pub fn builder() -> RegistryBuilder {
+        RegistryBuilder::default()
+    }
+
+    /// Register the Open Metrics `target_info` metric, an [`Info`] metric
+    /// exposing metadata of the monitored target, e.g. the service name or the
+    /// deployment environment, with the given label set.
+    ///
+    /// In contrast to labels attached to each metric, e.g. via
+    /// [`RegistryBuilder::with_label`], the metadata is exposed once, not on
+    /// each series. Prometheus can join it onto other series where needed,
+    /// e.g. via `* on (instance, job) group_left(version) target_info`. Thus
+    /// adding or changing target metadata does not change the identity of
+    /// every other series.
+    ///
+    /// Register the metric with the root [`Registry`], as its name is subject
+    /// to the prefix of the [`Registry`] like any other metric.
+    ///
+    /// ```
+    /// # use prometheus_client::encoding::text::encode;
+    /// # use prometheus_client::registry::Registry;
+    /// #
+    /// let mut registry = <Registry>::default();
+    /// registry.register_target_info(vec![("service_name", "api"), ("version", "1.2.3")]);
+    ///
+    /// let mut buffer = vec![];
+    /// encode(&mut buffer, &registry).unwrap();
+    ///
+    /// let expected = "# HELP target Target metadata.\n".to_owned() +
+    ///                "# TYPE target info\n" +
+    ///                "target_info{service_name=\"api\",version=\"1.2.3\"} 1\n" +
+    ///                "# EOF\n";
+    /// assert_eq!(expected, String::from_utf8(buffer).unwrap());
+    /// ```
+    pub fn register_target_info<S>(&mut self, label_set: S)
+    where
+        S: Clone + std::hash::Hash + Eq + Encode + Send + Sync + 'static,
+    {
+        self.register("target", "Target metadata", Box::new(Info::new(label_set)));
+    }
+
+    /// Register a [`GaugeWithMax`] as two metrics, `name` exposing its value
+    /// with the given `help` and `name` suffixed with `_max` exposing its
+    /// maximum with the given `max_help`.
+    ///
+    /// Registering a [`GaugeWithMax`] via [`Registry::register`] exposes its
+    /// value only, not its maximum.
+    ///
+    /// ```
+    /// # use prometheus_client::encoding::text::encode;
+    /// # use prometheus_client::metrics::gauge::GaugeWithMax;
+    /// # use prometheus_client::registry::Registry;
+    /// #
+    /// let mut registry = <Registry>::default();
+    /// let connections = GaugeWithMax::<u64>::default();
+    /// registry.register_gauge_with_max(
+    ///     "connections",
+    ///     "Connections in use",
+    ///     "Most connections ever in use",
+    ///     connections.clone(),
+    /// );
+    /// connections.inc();
+    /// connections.dec();
+    ///
+    /// let mut buffer = vec![];
+    /// encode(&mut buffer, &registry).unwrap();
+    ///
+    /// let expected = "# HELP connections Connections in use.\n".to_owned() +
+    ///                "# TYPE connections gauge\n" +
+    ///                "connections 0\n" +
+    ///                "# HELP connections_max Most connections ever in use.\n" +
+    ///                "# TYPE connections_max gauge\n" +
+    ///                "connections_max 1\n" +
+    ///                "# EOF\n";
+    /// assert_eq!(expected, String::from_utf8(buffer).unwrap());
+    /// ```
+    pub fn register_gauge_with_max<N, H, HM>(
+        &mut self,
+        name: &str,
+        help: H,
+        max_help: HM,
+        gauge: GaugeWithMax<N>,
+    ) where
+        N: Encode + Copy + PartialOrd + From<u8> + Add<Output = N> + Sub<Output = N>,
+        N: Send + Sync + 'static,
+        H: Into<String>,
+        HM: Into<String>,
+    {
+        let max = gauge.max_gauge();
+        self.register(name, help, Box::new(gauge));
+        self.register(format!("{}_max", name), max_help, Box::new(max));
     }
 }
 
 impl<M> Registry<M> {
     /// Creates a new default [`Registry`] with the given prefix.
+    ///
+    /// Shorthand for `Registry::builder().with_prefix(prefix).build()`.
     pub fn with_prefix(prefix: impl Into<String>) -> Self {
-        Self {
-            prefix: Some(Prefix(prefix.into())),
-            ..Default::default()
-        }
+        RegistryBuilder::default().with_prefix(prefix).build()
+    }
+
+    /// The [`EncodeOptions`] used by [`encode`](crate::encoding::text::encode),
+    /// see [`RegistryBuilder::with_encode_options`].
+    pub fn encode_options(&self) -> &EncodeOptions {
+        &self.encode_options
     }
 
     /// Register a metric with the [`Registry`].
@@ -144,6 +253,76 @@ impl<M> Registry<M> {
         self.priv_register(name, help, metric, Some(unit))
     }
 
+    /// Register a metric with the [`Registry`], marking it as sensitive, see
+    /// [`Descriptor::sensitive`].
+    ///
+    /// Sensitive metrics are encoded like any other metric by
+    /// [`encode`](crate::encoding::text::encode). Use
+    /// [`encode_with_filter`](crate::encoding::text::encode_with_filter) to
+    /// omit them, e.g. for unauthorized scrapes.
+    ///
+    /// ```
+    /// # use prometheus_client::encoding::text::encode_with_filter;
+    /// # use prometheus_client::metrics::counter::Counter;
+    /// # use prometheus_client::registry::Registry;
+    /// #
+    /// let mut registry: Registry<Counter> = Registry::default();
+    /// registry.register("requests", "Number of requests", Counter::default());
+    /// registry.register_sensitive("logins", "Number of logins per user", Counter::default());
+    ///
+    /// let authorized = false;
+    /// let mut buffer = vec![];
+    /// encode_with_filter(&mut buffer, &registry, |desc| authorized || !desc.sensitive()).unwrap();
+    /// assert!(!String::from_utf8(buffer).unwrap().contains("logins"));
+    /// ```
+    pub fn register_sensitive<N: Into<String>, H: Into<String>>(
+        &mut self,
+        name: N,
+        help: H,
+        metric: M,
+    ) {
+        self.priv_register(name, help, metric, None);
+        if let Some((desc, _)) = self.metrics.last_mut() {
+            desc.sensitive = true;
+        }
+    }
+
+    /// Register a metric with the [`Registry`], without appending the suffix
+    /// of its metric type, e.g. `_total` of a counter, to its samples.
+    ///
+    /// This violates the Open Metrics specification and is meant only for
+    /// interoperability with consumers expecting fixed metric names. The
+    /// metric is still declared with its type, e.g. as a counter.
+    ///
+    /// ```
+    /// # use prometheus_client::encoding::text::encode;
+    /// # use prometheus_client::metrics::counter::Counter;
+    /// # use prometheus_client::registry::Registry;
+    /// #
+    /// let mut registry: Registry<Counter> = Registry::default();
+    /// registry.register_without_type_suffix("requests", "Number of requests", Counter::default());
+    ///
+    /// let mut buffer = vec![];
+    /// encode(&mut buffer, &registry).unwrap();
+    ///
+    /// let expected = "# HELP requests Number of requests.\n".to_owned() +
+    ///                "# TYPE requests counter\n" +
+    ///                "requests 0\n" +
+    ///                "# EOF\n";
+    /// assert_eq!(expected, String::from_utf8(buffer).unwrap());
+    /// ```
+    pub fn register_without_type_suffix<N: Into<String>, H: Into<String>>(
+        &mut self,
+        name: N,
+        help: H,
+        metric: M,
+    ) {
+        self.priv_register(name, help, metric, None);
+        if let Some((desc, _)) = self.metrics.last_mut() {
+            desc.type_suffix = false;
+        }
+    }
+
     fn priv_register<N: Into<String>, H: Into<String>>(
         &mut self,
         name: N,
@@ -151,22 +330,236 @@ impl<M> Registry<M> {
         metric: M,
         unit: Option<Unit>,
     ) {
-        let name = name.into();
         let help = help.into() + ".";
+        let name = self.prefixed_name(name.into());
+        self.name_validation.validate(&name);
         let descriptor = Descriptor {
-            name: self
-                .prefix
-                .as_ref()
-                .map(|p| (p.clone().0 + "_" + name.as_str()))
-                .unwrap_or(name),
+            name,
             help,
             unit,
             labels: self.labels.clone(),
+            aliases: vec![],
+            sensitive: false,
+            type_suffix: true,
         };
 
         self.metrics.push((descriptor, metric));
     }
 
+    /// Expose a metric previously registered with the [`Registry`] or one of
+    /// its sub-registries under an additional name, returning `false` if no
+    /// metric with the name `existing_name` has been registered.
+    ///
+    /// The metric is emitted once under each name, sharing help text, unit
+    /// and labels. This is useful for migrations renaming a metric, where both
+    /// the old and the new name need to be exposed for a deprecation window.
+    ///
+    /// Both names are resolved relative to the [`Registry`], the same way as
+    /// with [`Registry::set_help`].
+    ///
+    /// ```
+    /// # use prometheus_client::encoding::text::encode;
+    /// # use prometheus_client::metrics::counter::{Atomic as _, Counter};
+    /// # use prometheus_client::registry::Registry;
+    /// #
+    /// let mut registry: Registry<Counter> = Registry::default();
+    /// let counter = Counter::default();
+    ///
+    /// registry.register("my_new_counter", "This is my counter", counter.clone());
+    /// assert!(registry.register_alias("my_new_counter", "my_old_counter"));
+    /// counter.inc();
+    ///
+    /// # let mut buffer = vec![];
+    /// # encode(&mut buffer, &registry).unwrap();
+    /// #
+    /// # let expected = "# HELP my_new_counter This is my counter.\n".to_owned() +
+    /// #                "# TYPE my_new_counter counter\n" +
+    /// #                "my_new_counter_total 1\n" +
+    /// #                "# HELP my_old_counter This is my counter.\n" +
+    /// #                "# TYPE my_old_counter counter\n" +
+    /// #                "my_old_counter_total 1\n" +
+    /// #                "# EOF\n";
+    /// # assert_eq!(expected, String::from_utf8(buffer).unwrap());
+    /// ```
+    pub fn register_alias<N: Into<String>>(&mut self, existing_name: &str, alias_name: N) -> bool {
+        let existing_name = self.prefixed_name(existing_name.to_string());
+        let alias_name = self.prefixed_name(alias_name.into());
+        self.name_validation.validate(&alias_name);
+        self.update_descriptors(&existing_name, &mut |desc| {
+            desc.aliases.push(alias_name.clone())
+        })
+    }
+
+    /// Replace the help text of a metric previously registered with the
+    /// [`Registry`] or one of its sub-registries, returning `false` if no
+    /// metric with the name `name` has been registered.
+    ///
+    /// `name` is resolved relative to the [`Registry`], including the prefixes
+    /// of sub-registries, e.g. `subsystem_my_counter` for `my_counter`
+    /// registered with the sub-registry with prefix `subsystem`. Metrics
+    /// registered under the same name in multiple sub-registries, e.g.
+    /// differing in their labels only, are all updated.
+    ///
+    /// ```
+    /// # use prometheus_client::encoding::text::encode;
+    /// # use prometheus_client::metrics::counter::Counter;
+    /// # use prometheus_client::registry::Registry;
+    /// #
+    /// let mut registry: Registry<Counter> = Registry::default();
+    /// registry
+    ///     .sub_registry_with_prefix("subsystem")
+    ///     .register("my_counter", "", Counter::default());
+    ///
+    /// assert!(registry.set_help("subsystem_my_counter", "This is my counter"));
+    /// assert!(!registry.set_help("my_counter", "This is my counter"));
+    ///
+    /// let mut buffer = vec![];
+    /// encode(&mut buffer, &registry).unwrap();
+    /// assert!(String::from_utf8(buffer)
+    ///     .unwrap()
+    ///     .starts_with("# HELP subsystem_my_counter This is my counter.\n"));
+    /// ```
+    pub fn set_help<H: Into<String>>(&mut self, name: &str, help: H) -> bool {
+        let name = self.prefixed_name(name.to_string());
+        let help = help.into() + ".";
+        self.update_descriptors(&name, &mut |desc| desc.help = help.clone())
+    }
+
+    /// Rename a metric previously registered with the [`Registry`] or one of
+    /// its sub-registries, returning `false` if no metric with the name `name`
+    /// has been registered.
+    ///
+    /// This allows namespacing metrics registered by a third-party library
+    /// with hardcoded names. Both names are resolved relative to the
+    /// [`Registry`], the same way as with [`Registry::set_help`].
+    ///
+    /// ```
+    /// # use prometheus_client::encoding::text::encode;
+    /// # use prometheus_client::metrics::counter::Counter;
+    /// # use prometheus_client::registry::Registry;
+    /// #
+    /// fn third_party_init(registry: &mut Registry<Counter>) {
+    ///     registry.register("requests", "Number of requests", Counter::default());
+    /// }
+    ///
+    /// let mut registry: Registry<Counter> = Registry::default();
+    /// third_party_init(registry.sub_registry_with_prefix("library"));
+    ///
+    /// assert!(registry.rename("library_requests", "library_http_requests"));
+    /// assert_eq!("library_http_requests", registry.iter().next().unwrap().0.name());
+    /// ```
+    pub fn rename<N: Into<String>>(&mut self, name: &str, new_name: N) -> bool {
+        let name = self.prefixed_name(name.to_string());
+        let new_name = self.prefixed_name(new_name.into());
+        self.name_validation.validate(&new_name);
+        self.update_descriptors(&name, &mut |desc| desc.name = new_name.clone())
+    }
+
+    /// Call `f` with each [`Descriptor`] with the given full name, returning
+    /// whether there was any.
+    fn update_descriptors(&mut self, name: &str, f: &mut dyn FnMut(&mut Descriptor)) -> bool {
+        let mut found = false;
+        for (desc, _) in self
+            .metrics
+            .iter_mut()
+            .filter(|(desc, _)| desc.name == name)
+        {
+            f(desc);
+            found = true;
+        }
+
+        for sub_registry in self.sub_registries.iter_mut() {
+            found |= sub_registry.update_descriptors(name, f);
+        }
+
+        found
+    }
+
+    fn prefixed_name(&self, name: String) -> String {
+        self.prefix
+            .as_ref()
+            .map(|p| p.clone().0 + "_" + name.as_str())
+            .unwrap_or(name)
+    }
+
+    /// Reset the [`Registry`] to the state of [`Registry::default`], e.g. to
+    /// reuse a [`Registry`] across test cases.
+    ///
+    /// Removes all metrics, sub-registries and pre-scrape hooks, as well as
+    /// the prefix, the labels and any other configuration of the
+    /// [`RegistryBuilder`]. Metric handles held elsewhere stay valid, they are
+    /// simply no longer encoded, unless registered again.
+    ///
+    /// ```
+    /// # use prometheus_client::metrics::counter::Counter;
+    /// # use prometheus_client::registry::Registry;
+    /// #
+    /// let mut registry: Registry<Counter> = Registry::default();
+    /// let counter = Counter::default();
+    /// registry.register("my_counter", "This is my counter", counter.clone());
+    ///
+    /// registry.clear();
+    /// assert_eq!(0, registry.iter().count());
+    ///
+    /// registry.register("my_counter", "This is my counter", counter.clone());
+    /// assert_eq!(1, registry.iter().count());
+    /// ```
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Add a hook to be run right before the metrics of the [`Registry`] are
+    /// encoded, e.g. to update a metric derived from other metrics.
+    ///
+    /// Hooks are run in registration order, the hooks of the [`Registry`]
+    /// before the hooks of its sub-registries. Hooks are not isolated from the
+    /// encoding, i.e. a panicking hook panics the encoding call.
+    ///
+    /// ```
+    /// # use prometheus_client::encoding::text::encode;
+    /// # use prometheus_client::metrics::counter::Counter;
+    /// # use prometheus_client::metrics::gauge::Gauge;
+    /// # use prometheus_client::registry::Registry;
+    /// #
+    /// let mut registry = <Registry>::default();
+    /// let requests: Counter = Counter::default();
+    /// let errors: Counter = Counter::default();
+    /// let error_ratio_percent: Gauge = Gauge::default();
+    /// registry.register(
+    ///   "error_ratio_percent",
+    ///   "Percentage of requests failing",
+    ///   Box::new(error_ratio_percent.clone()),
+    /// );
+    ///
+    /// registry.add_pre_scrape_hook(Box::new(move || {
+    ///     let requests = requests.get();
+    ///     if requests > 0 {
+    ///         error_ratio_percent.set(errors.get() * 100 / requests);
+    ///     }
+    /// }));
+    /// #
+    /// # let mut buffer = vec![];
+    /// # encode(&mut buffer, &registry).unwrap();
+    /// ```
+    pub fn add_pre_scrape_hook(&mut self, hook: Box<dyn Fn() + Send + Sync>) {
+        self.pre_scrape_hooks.push(PreScrapeHook(hook));
+    }
+
+    /// Run the hooks added via [`Registry::add_pre_scrape_hook`] of the
+    /// [`Registry`] and all its sub-registries.
+    ///
+    /// Called by the encoders before encoding. Only needed when implementing a
+    /// custom encoder.
+    pub fn run_pre_scrape_hooks(&self) {
+        for hook in self.pre_scrape_hooks.iter() {
+            (hook.0)();
+        }
+
+        for sub_registry in self.sub_registries.iter() {
+            sub_registry.run_pre_scrape_hooks();
+        }
+    }
+
     // TODO: Update doc.
     /// Create a sub-registry to register metrics with a common prefix.
     ///
@@ -208,6 +601,7 @@ impl<M> Registry<M> {
                 self.prefix.clone().map(|p| p.0 + "_").unwrap_or_default() + prefix.as_ref(),
             )),
             labels: self.labels.clone(),
+            name_validation: self.name_validation,
             ..Default::default()
         };
 
@@ -219,11 +613,13 @@ impl<M> Registry<M> {
         &mut self,
         label: (Cow<'static, str>, Cow<'static, str>),
     ) -> &mut Self {
+        self.name_validation.validate(&label.0);
         let mut labels = self.labels.clone();
         labels.push(label);
         let sub_registry = Registry {
             prefix: self.prefix.clone(),
             labels,
+            name_validation: self.name_validation,
             ..Default::default()
         };
 
@@ -238,6 +634,9 @@ impl<M> Registry<M> {
             .expect("sub_registries not to be empty.")
     }
 
+    /// Iterate all metrics of the [`Registry`] in registration order, followed
+    /// by the metrics of each sub-registry, recursively, in the order the
+    /// sub-registries were created.
     pub fn iter(&self) -> RegistryIterator<M> {
         let metrics = self.metrics.iter();
         let sub_registries = self.sub_registries.iter();
@@ -249,6 +648,192 @@ impl<M> Registry<M> {
     }
 }
 
+impl<M: EncodeMetric> Registry<M> {
+    /// List the name of each metric of the [`Registry`], including those of
+    /// sub-registries, together with the number of series it currently
+    /// consists of, e.g. to fail a test if a metric exceeds its cardinality
+    /// budget.
+    ///
+    /// The number of series is 1 for metrics without labels of their own and
+    /// the number of label sets for a [`Family`](crate::metrics::family::Family).
+    /// Names are those of the encoded metric families, i.e. including the
+    /// prefix and the unit, but without the suffix of the metric type, e.g.
+    /// `_total`. Aliases are not listed
+    /// separately. Neither encodes the metrics nor runs the
+    /// pre-scrape hooks.
+    ///
+    /// ```
+    /// # use prometheus_client::metrics::counter::Counter;
+    /// # use prometheus_client::metrics::family::Family;
+    /// # use prometheus_client::metrics::gauge::Gauge;
+    /// # use prometheus_client::registry::{Registry, Unit};
+    /// #
+    /// let mut registry = <Registry>::default();
+    /// let requests = Family::<Vec<(String, String)>, Counter>::default();
+    /// registry.register("requests", "Number of requests", Box::new(requests.clone()));
+    /// let errors: Counter = Counter::default();
+    /// registry
+    ///     .sub_registry_with_prefix("db")
+    ///     .register("errors", "Number of errors", Box::new(errors));
+    /// let uptime: Gauge = Gauge::default();
+    /// registry.register_with_unit("uptime", "Time since start", Unit::Seconds, Box::new(uptime));
+    ///
+    /// for method in ["GET", "PUT"] {
+    ///     requests.get_or_create(&vec![("method".to_owned(), method.to_owned())]).inc();
+    /// }
+    ///
+    /// assert_eq!(
+    ///     vec![
+    ///         ("requests".to_owned(), 2),
+    ///         ("uptime_seconds".to_owned(), 1),
+    ///         ("db_errors".to_owned(), 1),
+    ///     ],
+    ///     registry.cardinality_report(),
+    /// );
+    /// ```
+    pub fn cardinality_report(&self) -> Vec<(String, usize)> {
+        self.iter()
+            .map(|(desc, metric)| {
+                let mut name = Vec::new();
+                write_family_name(&mut name, desc.name(), desc, metric.metric_type())
+                    .expect("writing to a Vec not to fail.");
+                let name = String::from_utf8(name).expect("metric names to be valid UTF-8.");
+                (name, metric.series_count())
+            })
+            .collect()
+    }
+}
+
+/// Builder for a [`Registry`], collecting its configuration in one place.
+///
+/// ```
+/// # use prometheus_client::encoding::text::EncodeOptions;
+/// # use prometheus_client::metrics::counter::Counter;
+/// # use prometheus_client::registry::{NameValidation, Registry};
+/// # use std::borrow::Cow;
+/// #
+/// let registry: Registry<Counter> = Registry::builder()
+///     .with_prefix("my_service")
+///     .with_label((Cow::Borrowed("region"), Cow::Borrowed("eu")))
+///     .with_name_validation(NameValidation::Strict)
+///     .with_encode_options(EncodeOptions::default().float_precision(3))
+///     .build();
+/// ```
+#[derive(Debug, Default)]
+pub struct RegistryBuilder {
+    prefix: Option<Prefix>,
+    labels: Vec<(Cow<'static, str>, Cow<'static, str>)>,
+    name_validation: NameValidation,
+    encode_options: EncodeOptions,
+}
+
+impl RegistryBuilder {
+    /// Prefix the names of all metrics registered with the [`Registry`].
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(Prefix(prefix.into()));
+        self
+    }
+
+    /// Add a constant label to all metrics registered with the [`Registry`].
+    pub fn with_label(mut self, label: (Cow<'static, str>, Cow<'static, str>)) -> Self {
+        self.labels.push(label);
+        self
+    }
+
+    /// Set the [`NameValidation`] applied to metric and label names. Defaults
+    /// to [`NameValidation::Disabled`].
+    pub fn with_name_validation(mut self, name_validation: NameValidation) -> Self {
+        self.name_validation = name_validation;
+        self
+    }
+
+    /// Set the [`EncodeOptions`] used when encoding the [`Registry`] via
+    /// [`encode`](crate::encoding::text::encode).
+    pub fn with_encode_options(mut self, encode_options: EncodeOptions) -> Self {
+        self.encode_options = encode_options;
+        self
+    }
+
+    /// Whether to encode exemplars when encoding the [`Registry`] via
+    /// [`encode`](crate::encoding::text::encode). Defaults to `true`.
+    ///
+    /// Shorthand for setting [`EncodeOptions::exemplars`] on the
+    /// [`EncodeOptions`] of the [`Registry`], thus to be called after
+    /// [`RegistryBuilder::with_encode_options`], if at all.
+    ///
+    /// ```
+    /// # use prometheus_client::encoding::text::encode;
+    /// # use prometheus_client::metrics::exemplar::CounterWithExemplar;
+    /// # use prometheus_client::registry::Registry;
+    /// #
+    /// let mut registry = Registry::builder().with_exemplars(false).build();
+    /// let counter = CounterWithExemplar::<Vec<(String, String)>>::default();
+    /// registry.register("my_counter", "This is my counter", counter.clone());
+    /// counter.inc_by(1, Some(vec![("trace_id".to_string(), "3a2f90c9f80b894f".to_string())]));
+    ///
+    /// let mut buffer = vec![];
+    /// encode(&mut buffer, &registry).unwrap();
+    /// assert!(!String::from_utf8(buffer).unwrap().contains("trace_id"));
+    /// ```
+    pub fn with_exemplars(mut self, enabled: bool) -> Self {
+        self.encode_options = self.encode_options.exemplars(enabled);
+        self
+    }
+
+    /// Build the [`Registry`].
+    ///
+    /// # Panics
+    ///
+    /// Panics on an invalid prefix or label name with
+    /// [`NameValidation::Strict`].
+    pub fn build<M>(self) -> Registry<M> {
+        if let Some(prefix) = &self.prefix {
+            self.name_validation.validate(&prefix.0);
+        }
+        for (key, _) in self.labels.iter() {
+            self.name_validation.validate(key);
+        }
+
+        Registry {
+            prefix: self.prefix,
+            labels: self.labels,
+            metrics: vec![],
+            sub_registries: vec![],
+            pre_scrape_hooks: vec![],
+            name_validation: self.name_validation,
+            encode_options: self.encode_options,
+        }
+    }
+}
+
+/// Validation of metric and label names, see
+/// [`RegistryBuilder::with_name_validation`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NameValidation {
+    /// Do not validate names.
+    #[default]
+    Disabled,
+    /// Panic when registering a metric, or adding a constant label, whose name
+    /// does not match the Open Metrics name syntax `[a-zA-Z_:][a-zA-Z0-9_:]*`.
+    Strict,
+}
+
+impl NameValidation {
+    fn validate(&self, name: &str) {
+        if *self == NameValidation::Disabled {
+            return;
+        }
+
+        let mut chars = name.chars();
+        let valid = chars
+            .next()
+            .into_iter()
+            .any(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':');
+        assert!(valid, "Expected valid Open Metrics name, got {:?}.", name);
+    }
+}
+
 /// Iterator iterating both the metrics registered directly with the registry as
 /// well as all metrics registered with sub-registries.
 #[derive(Debug)]
@@ -282,6 +867,124 @@ impl<'a, M> Iterator for RegistryIterator<'a, M> {
     }
 }
 
+/// A [`Registry`] shareable across threads, allowing concurrent registration
+/// via `&self`, e.g. by subsystems initializing in parallel.
+///
+/// Cloning a [`SharedRegistry`] creates a new handle to the same [`Registry`].
+/// Registration takes a write lock, encoding a read lock via
+/// [`SharedRegistry::read`].
+///
+/// Like a [`Family`](crate::metrics::family::Family), a [`SharedRegistry`]
+/// uses a [`std::sync::RwLock`], or a `parking_lot::RwLock` with the
+/// `parking_lot` feature enabled. A [`std::sync::RwLock`] poisoned by a thread
+/// panicking while holding it, e.g. in a pre-scrape hook, is recovered from
+/// instead of panicking, as each registration leaves the [`Registry`]
+/// consistent.
+///
+/// ```
+/// # use prometheus_client::encoding::text::encode;
+/// # use prometheus_client::metrics::counter::Counter;
+/// # use prometheus_client::registry::SharedRegistry;
+/// #
+/// let registry = <SharedRegistry>::default();
+///
+/// std::thread::scope(|s| {
+///     for subsystem in ["a", "b"] {
+///         let registry = registry.clone();
+///         s.spawn(move || {
+///             let counter: Counter = Counter::default();
+///             registry.register(
+///                 format!("{}_requests", subsystem),
+///                 "Number of requests",
+///                 Box::new(counter),
+///             );
+///         });
+///     }
+/// });
+///
+/// let mut buffer = vec![];
+/// encode(&mut buffer, &registry.read()).unwrap();
+/// assert_eq!(2, registry.read().iter().count());
+/// ```
+#[derive(Debug)]
+pub struct SharedRegistry<M = Box<dyn crate::encoding::text::SendSyncEncodeMetric>> {
+    inner: Arc<RwLock<Registry<M>>>,
+}
+
+impl<M> Clone for SharedRegistry<M> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<M> Default for SharedRegistry<M> {
+    fn default() -> Self {
+        Registry::default().into()
+    }
+}
+
+impl<M> From<Registry<M>> for SharedRegistry<M> {
+    fn from(registry: Registry<M>) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(registry)),
+        }
+    }
+}
+
+impl<M> SharedRegistry<M> {
+    /// Like [`Registry::register`], taking `&self`.
+    pub fn register<N: Into<String>, H: Into<String>>(&self, name: N, help: H, metric: M) {
+        self.write().register(name, help, metric)
+    }
+
+    /// Like [`Registry::register_with_unit`], taking `&self`.
+    pub fn register_with_unit<N: Into<String>, H: Into<String>>(
+        &self,
+        name: N,
+        help: H,
+        unit: Unit,
+        metric: M,
+    ) {
+        self.write().register_with_unit(name, help, unit, metric)
+    }
+
+    /// Acquire a read lock on the [`Registry`], e.g. to encode it.
+    #[cfg(not(feature = "parking_lot"))]
+    pub fn read(&self) -> RwLockReadGuard<'_, Registry<M>> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Acquire a write lock on the [`Registry`], e.g. to create a
+    /// sub-registry.
+    #[cfg(not(feature = "parking_lot"))]
+    pub fn write(&self) -> RwLockWriteGuard<'_, Registry<M>> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Acquire a read lock on the [`Registry`], e.g. to encode it.
+    #[cfg(feature = "parking_lot")]
+    pub fn read(&self) -> RwLockReadGuard<'_, Registry<M>> {
+        self.inner.read()
+    }
+
+    /// Acquire a write lock on the [`Registry`], e.g. to create a
+    /// sub-registry.
+    #[cfg(feature = "parking_lot")]
+    pub fn write(&self) -> RwLockWriteGuard<'_, Registry<M>> {
+        self.inner.write()
+    }
+}
+
+struct PreScrapeHook(Box<dyn Fn() + Send + Sync>);
+
+impl std::fmt::Debug for PreScrapeHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PreScrapeHook").finish()
+    }
+}
+
 #[derive(Clone, Debug)]
 struct Prefix(String);
 
@@ -303,6 +1006,9 @@ pub struct Descriptor {
     help: String,
     unit: Option<Unit>,
     labels: Vec<(Cow<'static, str>, Cow<'static, str>)>,
+    aliases: Vec<String>,
+    sensitive: bool,
+    type_suffix: bool,
 }
 
 impl Descriptor {
@@ -321,6 +1027,25 @@ impl Descriptor {
     pub fn labels(&self) -> &[(Cow<'static, str>, Cow<'static, str>)] {
         &self.labels
     }
+
+    /// Additional names the metric is exposed under, see
+    /// [`Registry::register_alias`].
+    pub fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    /// Whether the metric has been registered via
+    /// [`Registry::register_sensitive`].
+    pub fn sensitive(&self) -> bool {
+        self.sensitive
+    }
+
+    /// Whether the suffix of the metric type, e.g. `_total` of a counter, is
+    /// appended to the samples of the metric, i.e. unless registered via
+    /// [`Registry::register_without_type_suffix`].
+    pub fn type_suffix(&self) -> bool {
+        self.type_suffix
+    }
 }
 
 /// Metric units recommended by Open Metrics.
@@ -344,6 +1069,10 @@ pub enum Unit {
 mod tests {
     use super::*;
     use crate::metrics::counter::Counter;
+    use crate::metrics::family::Family;
+    use crate::metrics::gauge::Gauge;
+    use crate::metrics::histogram::Histogram;
+    use std::sync::atomic::AtomicU64;
 
     #[test]
     fn register_and_iterate() {
@@ -354,6 +1083,270 @@ mod tests {
         assert_eq!(1, registry.iter().count())
     }
 
+    #[test]
+    fn pre_scrape_hooks_in_registration_order() {
+        let calls = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let mut registry = Registry::<Counter>::default();
+
+        let c = calls.clone();
+        registry.add_pre_scrape_hook(Box::new(move || c.lock().unwrap().push(1)));
+        let c = calls.clone();
+        registry
+            .sub_registry_with_prefix("sub")
+            .add_pre_scrape_hook(Box::new(move || c.lock().unwrap().push(3)));
+        let c = calls.clone();
+        registry.add_pre_scrape_hook(Box::new(move || c.lock().unwrap().push(2)));
+
+        registry.run_pre_scrape_hooks();
+
+        assert_eq!(vec![1, 2, 3], *calls.lock().unwrap());
+    }
+
+    #[test]
+    fn clear() {
+        let mut registry = Registry::<Counter>::with_prefix("my_prefix");
+        registry.register("my_counter", "My counter", Default::default());
+        registry.sub_registry_with_prefix("sub").register(
+            "my_counter",
+            "My counter",
+            Default::default(),
+        );
+        registry.add_pre_scrape_hook(Box::new(|| panic!("Hook not to be called.")));
+
+        registry.clear();
+        registry.run_pre_scrape_hooks();
+        assert_eq!(0, registry.iter().count());
+
+        registry.register("my_counter", "My counter", Default::default());
+        let (desc, _) = registry.iter().next().unwrap();
+        assert_eq!("my_counter", desc.name());
+    }
+
+    #[test]
+    fn register_alias() {
+        let mut registry = Registry::<Counter>::with_prefix("my_prefix");
+        registry.register("my_counter", "My counter", Default::default());
+
+        assert!(registry.register_alias("my_counter", "my_alias"));
+        assert!(!registry.register_alias("unknown", "my_other_alias"));
+
+        let (desc, _) = registry.iter().next().unwrap();
+        assert_eq!("my_prefix_my_counter", desc.name());
+        assert_eq!(&["my_prefix_my_alias".to_string()], desc.aliases());
+    }
+
+    #[test]
+    fn register_alias_in_sub_registry() {
+        let mut registry = Registry::<Counter>::with_prefix("my_prefix");
+        registry.sub_registry_with_prefix("sub").register(
+            "my_counter",
+            "My counter",
+            Default::default(),
+        );
+
+        assert!(!registry.register_alias("my_counter", "my_alias"));
+        assert!(registry.register_alias("sub_my_counter", "sub_my_alias"));
+
+        let (desc, _) = registry.iter().next().unwrap();
+        assert_eq!("my_prefix_sub_my_counter", desc.name());
+        assert_eq!(&["my_prefix_sub_my_alias".to_string()], desc.aliases());
+
+        let mut buffer = vec![];
+        crate::encoding::text::encode(&mut buffer, &registry).unwrap();
+        assert!(String::from_utf8(buffer)
+            .unwrap()
+            .contains("my_prefix_sub_my_alias_total 0\n"));
+    }
+
+    #[test]
+    fn set_help() {
+        let mut registry = Registry::<Counter>::with_prefix("my_prefix");
+        registry.register("my_counter", "My counter", Default::default());
+        registry.sub_registry_with_prefix("sub").register(
+            "my_counter",
+            "My sub counter",
+            Default::default(),
+        );
+
+        let encode_help = |registry: &Registry<Counter>| {
+            let mut buffer = vec![];
+            crate::encoding::text::encode(&mut buffer, registry).unwrap();
+            String::from_utf8(buffer)
+                .unwrap()
+                .lines()
+                .filter(|line| line.starts_with("# HELP"))
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            vec![
+                "# HELP my_prefix_my_counter My counter.",
+                "# HELP my_prefix_sub_my_counter My sub counter.",
+            ],
+            encode_help(&registry)
+        );
+
+        assert!(registry.set_help("sub_my_counter", "Updated"));
+        assert!(!registry.set_help("unknown", "Unknown"));
+
+        assert_eq!(
+            vec![
+                "# HELP my_prefix_my_counter My counter.",
+                "# HELP my_prefix_sub_my_counter Updated.",
+            ],
+            encode_help(&registry)
+        );
+    }
+
+    #[test]
+    fn cardinality_report() {
+        let mut registry = <Registry>::default();
+        let latency = Family::<Vec<(String, String)>, Histogram>::new_with_constructor(|| {
+            Histogram::new([0.5].into_iter())
+        });
+        registry.register("latency", "Latency", Box::new(latency.clone()));
+        let sub_registry = registry
+            .sub_registry_with_prefix("my_prefix")
+            .sub_registry_with_label((Cow::Borrowed("my_key"), Cow::Borrowed("my_value")));
+        let counter: Counter = Counter::default();
+        sub_registry.register(
+            "my_counter",
+            "My counter",
+            Box::new(counter.with_labels(vec![("a", "b")])),
+        );
+
+        assert_eq!(
+            vec![
+                ("latency".to_owned(), 0),
+                ("my_prefix_my_counter".to_owned(), 1)
+            ],
+            registry.cardinality_report()
+        );
+
+        for path in ["/a", "/b", "/c"] {
+            latency
+                .get_or_create(&vec![("path".to_owned(), path.to_owned())])
+                .observe(1.0);
+        }
+        assert_eq!(("latency".to_owned(), 3), registry.cardinality_report()[0]);
+    }
+
+    #[cfg(not(feature = "parking_lot"))]
+    #[test]
+    fn shared_registry_recovers_from_poisoned_lock() {
+        let registry = SharedRegistry::<Counter>::default();
+        registry.register("my_counter", "My counter", Default::default());
+
+        let poisoning = registry.clone();
+        std::thread::spawn(move || {
+            let _guard = poisoning.write();
+            panic!("Poison lock.");
+        })
+        .join()
+        .unwrap_err();
+
+        registry.register("my_other_counter", "My counter", Default::default());
+        assert_eq!(2, registry.read().iter().count());
+    }
+
+    #[test]
+    fn shared_registry() {
+        let registry = SharedRegistry::<Counter>::from(Registry::with_prefix("my_prefix"));
+
+        std::thread::scope(|s| {
+            for i in 0..4 {
+                let registry = registry.clone();
+                s.spawn(move || {
+                    registry.register(
+                        format!("my_counter_{}", i),
+                        "My counter",
+                        Default::default(),
+                    );
+                });
+            }
+        });
+        registry.write().sub_registry_with_prefix("sub").register(
+            "my_counter",
+            "My counter",
+            Default::default(),
+        );
+
+        let mut names: Vec<_> = registry
+            .read()
+            .iter()
+            .map(|(desc, _)| desc.name().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(
+            vec![
+                "my_prefix_my_counter_0",
+                "my_prefix_my_counter_1",
+                "my_prefix_my_counter_2",
+                "my_prefix_my_counter_3",
+                "my_prefix_sub_my_counter",
+            ],
+            names
+        );
+    }
+
+    #[test]
+    fn rename() {
+        let mut registry = Registry::<Counter>::with_prefix("my_prefix");
+        registry.sub_registry_with_prefix("sub").register(
+            "my_counter",
+            "My counter",
+            Default::default(),
+        );
+
+        assert!(registry.rename("sub_my_counter", "renamed"));
+        assert!(!registry.rename("sub_my_counter", "renamed_again"));
+
+        let (desc, _) = registry.iter().next().unwrap();
+        assert_eq!("my_prefix_renamed", desc.name());
+        assert_eq!("My counter.", desc.help());
+    }
+
+    #[test]
+    fn builder() {
+        let mut registry: Registry<Gauge<f64, AtomicU64>> = Registry::builder()
+            .with_prefix("my_prefix")
+            .with_label((Cow::Borrowed("my_key"), Cow::Borrowed("my_value")))
+            .with_encode_options(EncodeOptions::default().float_precision(2))
+            .build();
+        let gauge = Gauge::<f64, AtomicU64>::default();
+        registry.register("my_gauge", "My gauge", gauge.clone());
+        gauge.set(1.0 / 3.0);
+
+        let (desc, _) = registry.iter().next().unwrap();
+        assert_eq!("my_prefix_my_gauge", desc.name());
+        assert_eq!(
+            &[(Cow::Borrowed("my_key"), Cow::Borrowed("my_value"))],
+            desc.labels()
+        );
+
+        let mut encoded = Vec::new();
+        crate::encoding::text::encode(&mut encoded, &registry).unwrap();
+        assert!(String::from_utf8(encoded)
+            .unwrap()
+            .contains("my_prefix_my_gauge{my_key=\"my_value\"} 0.33\n"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected valid Open Metrics name")]
+    fn strict_name_validation() {
+        let mut registry: Registry<Counter> = Registry::builder()
+            .with_name_validation(NameValidation::Strict)
+            .build();
+        registry.register("my_counter", "My counter", Default::default());
+
+        registry.sub_registry_with_prefix("sub").register(
+            "my-counter",
+            "My counter",
+            Default::default(),
+        );
+    }
+
     #[test]
     fn sub_registry_with_prefix_and_label() {
         let top_level_metric_name = "my_top_level_metric";